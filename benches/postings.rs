@@ -0,0 +1,309 @@
+// The benchmarks need a newer toolchain than the library itself, as
+// criterion does.
+#![allow(clippy::incompatible_msrv)]
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::mem::size_of;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use string_interner::{DefaultSymbol, Symbol};
+
+use ngrammatic::PostingList;
+use ngrammatic::{
+    Corpus, CorpusBuilder, IdentityKeyTransformer, LinkedKeyTransformer, LowerKeyTransformer,
+    Ngram, NgramBuilder, Pad,
+};
+
+// Compares the compressed posting lists mapping grams to entries with the
+// previous layout, a `Vec` of symbols per gram, side by side. Criterion
+// can't measure memory, so the heap bytes of both layouts are printed before
+// each group. The compressed layout is internal, exported for the
+// benchmarks by the bench feature:
+//
+//     cargo bench --features bench --bench postings
+
+fn data_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "data", name].iter().collect()
+}
+
+/// The words of one of the data files, in the order they appear in it.
+fn get_text(name: &str) -> Vec<String> {
+    let file = match File::open(data_path(name)) {
+        Ok(file) => BufReader::new(file),
+        Err(error) => {
+            eprintln!("skipping benchmarks of {}: {}", name, error);
+            return Vec::new();
+        }
+    };
+    let mut words = Vec::new();
+    for line in file.lines().map_while(Result::ok) {
+        words.extend(line.split_whitespace().map(str::to_string));
+    }
+    words
+}
+
+/// The distinct `words`, sorted.
+fn distinct(words: &[String]) -> Vec<String> {
+    let words: HashSet<&String> = words.iter().collect();
+    let mut words: Vec<String> = words.into_iter().cloned().collect();
+    words.sort_unstable();
+    words
+}
+
+/// Generate `count` distinct words of lowercase letters and digits, the same
+/// on every run.
+fn get_synthetic(count: usize) -> Vec<String> {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count)
+        .map(|i| {
+            let len = 6 + (next() % 8) as usize;
+            let mut word: String = (0..len)
+                .map(|_| LETTERS[(next() % LETTERS.len() as u64) as usize] as char)
+                .collect();
+            word.push_str(&i.to_string());
+            word
+        })
+        .collect()
+}
+
+fn ngram(word: &str) -> Ngram {
+    NgramBuilder::new(word)
+        .arity(2)
+        .pad_full(Pad::Auto)
+        .finish()
+}
+
+/// The layout posting lists had before they were compressed: a `Vec` of
+/// symbols, in the order they were added.
+#[derive(Debug, Default)]
+struct VecPostingList {
+    syms: Vec<DefaultSymbol>,
+}
+
+impl Extend<DefaultSymbol> for VecPostingList {
+    fn extend<It: IntoIterator<Item = DefaultSymbol>>(&mut self, syms: It) {
+        self.syms.extend(syms);
+    }
+}
+
+/// The operations the benchmarks need from both layouts.
+trait Layout: Default + Extend<DefaultSymbol> {
+    fn insert(&mut self, sym: DefaultSymbol) -> bool;
+    fn remove(&mut self, sym: DefaultSymbol) -> bool;
+    fn for_each(&self, f: impl FnMut(DefaultSymbol));
+    fn heap_bytes(&self) -> usize;
+}
+
+impl Layout for PostingList {
+    fn insert(&mut self, sym: DefaultSymbol) -> bool {
+        PostingList::insert(self, sym)
+    }
+
+    fn remove(&mut self, sym: DefaultSymbol) -> bool {
+        PostingList::remove(self, sym)
+    }
+
+    fn for_each(&self, f: impl FnMut(DefaultSymbol)) {
+        self.iter().for_each(f)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        PostingList::heap_bytes(self)
+    }
+}
+
+impl Layout for VecPostingList {
+    fn insert(&mut self, sym: DefaultSymbol) -> bool {
+        self.syms.push(sym);
+        true
+    }
+
+    fn remove(&mut self, sym: DefaultSymbol) -> bool {
+        let len = self.syms.len();
+        self.syms.retain(|other| *other != sym);
+        self.syms.len() < len
+    }
+
+    fn for_each(&self, f: impl FnMut(DefaultSymbol)) {
+        self.syms.iter().copied().for_each(f)
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.syms.capacity() * size_of::<DefaultSymbol>()
+    }
+}
+
+/// The posting lists of the `ngrams`, with the entry of each being its
+/// index, like interned symbols.
+fn index<L: Layout>(ngrams: &[Ngram]) -> HashMap<&str, L> {
+    let mut index: HashMap<&str, L> = HashMap::new();
+    for (i, ngram) in ngrams.iter().enumerate() {
+        let sym = DefaultSymbol::try_from_usize(i).unwrap();
+        for gram in ngram.grams.keys() {
+            index.entry(gram.as_str()).or_default().insert(sym);
+        }
+    }
+    index
+}
+
+/// Gather the entries sharing a gram with each query, as searches do.
+fn gather<L: Layout>(index: &HashMap<&str, L>, queries: &[Ngram]) -> usize {
+    let mut gathered = 0;
+    for query in queries {
+        let mut candidates = HashSet::new();
+        for gram in query.grams.keys() {
+            if let Some(list) = index.get(gram.as_str()) {
+                list.for_each(|sym| {
+                    candidates.insert(sym);
+                });
+            }
+        }
+        gathered += candidates.len();
+    }
+    gathered
+}
+
+/// Remove some of the entries from every posting list holding them, and add
+/// them back, as removing a text and adding it again does.
+fn readd<L: Layout>(index: &mut HashMap<&str, L>, ngrams: &[Ngram], entries: &[usize]) {
+    for &entry in entries {
+        let sym = DefaultSymbol::try_from_usize(entry).unwrap();
+        for gram in ngrams[entry].grams.keys() {
+            let list = index.get_mut(gram.as_str()).unwrap();
+            list.remove(sym);
+            list.insert(sym);
+        }
+    }
+}
+
+fn heap_bytes<L: Layout>(index: &HashMap<&str, L>) -> usize {
+    index.values().map(Layout::heap_bytes).sum()
+}
+
+fn build_corpus(words: &[String]) -> Corpus<IdentityKeyTransformer> {
+    CorpusBuilder::default()
+        .arity(2)
+        .pad_full(Pad::Auto)
+        .fill(words)
+        .finish()
+}
+
+fn build_corpus_insensitive(
+    words: &[String],
+) -> Corpus<LinkedKeyTransformer<IdentityKeyTransformer, LowerKeyTransformer>> {
+    CorpusBuilder::default()
+        .arity(2)
+        .pad_full(Pad::Auto)
+        .case_insensitive()
+        .fill(words)
+        .finish()
+}
+
+fn bench_postings(c: &mut Criterion, name: &str, text: Vec<String>, queries: &[&str]) {
+    if text.is_empty() {
+        return;
+    }
+    let words = distinct(&text);
+    let ngrams: Vec<Ngram> = words.iter().map(|word| ngram(word)).collect();
+    let queries: Vec<Ngram> = queries.iter().map(|query| ngram(query)).collect();
+    // Entries spread over the whole index, so most edits are in the middle
+    // of their posting lists
+    let entries: Vec<usize> = (0..100).map(|i| i * ngrams.len() / 100).collect();
+    let mut compressed: HashMap<&str, PostingList> = index(&ngrams);
+    let mut plain: HashMap<&str, VecPostingList> = index(&ngrams);
+    let postings: usize = ngrams.iter().map(|ngram| ngram.grams.len()).sum();
+    eprintln!(
+        "{}: {} entries, {} postings, heap bytes: Vec {} ({:.2} per posting), \
+         compressed {} ({:.2} per posting)",
+        name,
+        ngrams.len(),
+        postings,
+        heap_bytes(&plain),
+        heap_bytes(&plain) as f64 / postings as f64,
+        heap_bytes(&compressed),
+        heap_bytes(&compressed) as f64 / postings as f64,
+    );
+
+    let mut group = c.benchmark_group(format!("{} posting lists", name));
+
+    group.bench_function(format!("{} gather candidates Vec", name), |b| {
+        b.iter(|| gather(&plain, std::hint::black_box(&queries)));
+    });
+
+    group.bench_function(format!("{} gather candidates compressed", name), |b| {
+        b.iter(|| gather(&compressed, std::hint::black_box(&queries)));
+    });
+
+    group.bench_function(format!("{} remove and re-add 100 Vec", name), |b| {
+        b.iter(|| readd(&mut plain, &ngrams, std::hint::black_box(&entries)));
+    });
+
+    group.bench_function(format!("{} remove and re-add 100 compressed", name), |b| {
+        b.iter(|| readd(&mut compressed, &ngrams, std::hint::black_box(&entries)));
+    });
+
+    // Corpora are created from the whole text, repeats and all. Lowercased
+    // keys aren't interned in increasing order, so adding their postings one
+    // entry at a time would put most of them in the middle of their lists
+    group.bench_function(format!("{} corpus creation case sensitive", name), |b| {
+        b.iter(|| build_corpus(std::hint::black_box(&text)));
+    });
+
+    group.bench_function(format!("{} corpus creation case insensitive", name), |b| {
+        b.iter(|| build_corpus_insensitive(std::hint::black_box(&text)));
+    });
+
+    let mut corpus = build_corpus(&words);
+    group.bench_function(format!("{} corpus serial search", name), |b| {
+        b.iter(|| {
+            for query in &queries {
+                corpus.search(std::hint::black_box(&query.text), 0.10, 10);
+            }
+        });
+    });
+
+    group.bench_function(format!("{} corpus remove and re-add 100", name), |b| {
+        b.iter(|| {
+            for &entry in &entries {
+                corpus.remove_text(&words[entry]);
+                corpus.add_text(&words[entry]);
+            }
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_postings_novel(c: &mut Criterion) {
+    let queries = ["cabbage", "potato", "carrot"];
+    bench_postings(c, "novel", get_text("1984.txt"), &queries);
+}
+
+fn bench_postings_random(c: &mut Criterion) {
+    let queries = ["qsratf2ar", "fimm5mm2f", "d85rrz3l3a"];
+    bench_postings(c, "random text", get_text("random.txt"), &queries);
+}
+
+fn bench_postings_synthetic(c: &mut Criterion) {
+    let queries = ["qsratf2ar", "fimm5mm2f", "d85rrz3l3a"];
+    bench_postings(c, "synthetic", get_synthetic(500_000), &queries);
+}
+
+criterion_group!(
+    postings_benches,
+    bench_postings_novel,
+    bench_postings_random,
+    bench_postings_synthetic,
+);
+
+criterion_main!(postings_benches);
@@ -1,26 +1,55 @@
+// The benchmarks need a newer toolchain than the library itself, as
+// criterion does.
+#![allow(clippy::incompatible_msrv)]
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use ngrammatic;
-use ngrammatic::{IdentityKeyTransformer, LinkedKeyTransformer, LowerKeyTransformer};
+use ngrammatic::{
+    IdentityKeyTransformer, LinkedKeyTransformer, LowerKeyTransformer, ReadOptions, Splitter,
+};
 
 // these benchmarks were taken from https://github.com/bluecatengineering/fast_radix_trie/
 // which was taken from https://github.com/cloudflare/trie-hard/
 // which was taken from https://github.com/michaelsproul/rust_radix_trie/
 
-const OW_1984: &str = include_str!("../data/1984.txt");
-const RANDOM: &str = include_str!("../data/random.txt");
-const TOP_MILLION: &str = include_str!("../data/top-domains.txt");
+fn data_path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "data", name].iter().collect()
+}
+
+/// Open one of the data files, which may be absent (top-domains.txt is too
+/// large to be distributed with the crate).
+fn open_data(name: &str) -> Option<BufReader<File>> {
+    match File::open(data_path(name)) {
+        Ok(file) => Some(BufReader::new(file)),
+        Err(error) => {
+            eprintln!("skipping benchmarks of {}: {}", name, error);
+            None
+        }
+    }
+}
+
+fn get_words(name: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    for line in open_data(name)?.lines() {
+        words.extend(line.ok()?.split_whitespace().map(str::to_string));
+    }
+    Some(words)
+}
 
-fn get_novel() -> Vec<&'static str> {
-    OW_1984.split(|c: char| c.is_whitespace()).collect()
+fn get_novel() -> Vec<String> {
+    get_words("1984.txt").unwrap_or_default()
 }
 
-fn get_random() -> Vec<&'static str> {
-    RANDOM.split(|c: char| c.is_whitespace()).collect()
+fn get_random() -> Vec<String> {
+    get_words("random.txt").unwrap_or_default()
 }
 
-fn get_domains() -> Vec<&'static str> {
-    TOP_MILLION.split(|c: char| c.is_whitespace()).collect()
+fn get_domains() -> Option<Vec<String>> {
+    get_words("top-domains.txt")
 }
 
 fn build_corpus<It>(words: It) -> ngrammatic::Corpus<IdentityKeyTransformer>
@@ -35,7 +64,9 @@ where
         .finish()
 }
 
-fn build_corpus_insensitive<It>(words: It) -> ngrammatic::Corpus<LinkedKeyTransformer<IdentityKeyTransformer, LowerKeyTransformer>>
+fn build_corpus_insensitive<It>(
+    words: It,
+) -> ngrammatic::Corpus<LinkedKeyTransformer<IdentityKeyTransformer, LowerKeyTransformer>>
 where
     It: IntoIterator,
     It::Item: AsRef<str>,
@@ -57,10 +88,12 @@ where
         .arity(2)
         .pad_full(ngrammatic::Pad::Auto)
         .fill_par(words)
-        .finish()
+        .finish_par()
 }
 
-fn build_corpus_insensitive_par<It>(words: It) -> ngrammatic::Corpus<LinkedKeyTransformer<IdentityKeyTransformer, LowerKeyTransformer>>
+fn build_corpus_insensitive_par<It>(
+    words: It,
+) -> ngrammatic::Corpus<LinkedKeyTransformer<IdentityKeyTransformer, LowerKeyTransformer>>
 where
     It: IntoIterator + rayon::iter::IntoParallelIterator,
     String: From<<It as rayon::iter::IntoParallelIterator>::Item>,
@@ -70,7 +103,7 @@ where
         .case_insensitive()
         .pad_full(ngrammatic::Pad::Auto)
         .fill_par(words)
-        .finish()
+        .finish_par()
 }
 
 fn bench_corpus_novel(c: &mut Criterion) {
@@ -120,9 +153,12 @@ fn bench_corpus_random(c: &mut Criterion) {
 }
 
 fn bench_corpus_domainnames(c: &mut Criterion) {
+    let words = match get_domains() {
+        Some(words) => words,
+        None => return,
+    };
     let mut group = c.benchmark_group("domain names corpus creation");
 
-    let words = get_random();
     group.bench_function("domain names parallel insertion case sensitive", |b| {
         b.iter(|| build_corpus_par(std::hint::black_box(words.clone())));
     });
@@ -142,17 +178,38 @@ fn bench_corpus_domainnames(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_corpus_from_reader(c: &mut Criterion) {
+    let mut group = c.benchmark_group("novel corpus creation from reader");
+    let options = ReadOptions::new().splitter(Splitter::Whitespace);
+
+    if open_data("1984.txt").is_some() {
+        group.bench_function("novel streamed serial insertion case sensitive", |b| {
+            b.iter(|| {
+                ngrammatic::CorpusBuilder::default()
+                    .arity(2)
+                    .pad_full(ngrammatic::Pad::Auto)
+                    .fill_from_reader(open_data("1984.txt").unwrap(), &options)
+                    .unwrap()
+                    .finish()
+            });
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     corpus_benches,
     bench_corpus_novel,
     bench_corpus_random,
     bench_corpus_domainnames,
+    bench_corpus_from_reader,
 );
 
 fn bench_get_novel(c: &mut Criterion) {
     let mut group = c.benchmark_group("novel corpus search comparison");
     let words = get_novel();
-    let corpus = build_corpus(words.into_iter());
+    let corpus = build_corpus(words);
 
     group.bench_function("novel parallel search no match", |b| {
         b.iter(|| {
@@ -192,7 +249,7 @@ fn bench_get_novel(c: &mut Criterion) {
 fn bench_get_random(c: &mut Criterion) {
     let mut group = c.benchmark_group("random text corpus search comparison");
     let words = get_random();
-    let corpus = build_corpus(words.into_iter());
+    let corpus = build_corpus(words);
 
     group.bench_function("random text parallel search no match", |b| {
         b.iter(|| {
@@ -230,9 +287,12 @@ fn bench_get_random(c: &mut Criterion) {
 }
 
 fn bench_get_domainnames(c: &mut Criterion) {
+    let words = match get_domains() {
+        Some(words) => words,
+        None => return,
+    };
     let mut group = c.benchmark_group("domain names corpus search comparison");
-    let words = get_domains();
-    let corpus = build_corpus(words.into_iter());
+    let corpus = build_corpus(words);
 
     group.bench_function("domain names parallel search no match", |b| {
         b.iter(|| {
@@ -269,11 +329,70 @@ fn bench_get_domainnames(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_get_random_sharded(c: &mut Criterion) {
+    let mut group = c.benchmark_group("random text sharded corpus search comparison");
+    let corpus = ngrammatic::CorpusBuilder::default()
+        .arity(2)
+        .pad_full(ngrammatic::Pad::Auto)
+        .fill(get_random())
+        .finish_sharded_par(8);
+
+    group.bench_function("random text sharded parallel search match", |b| {
+        b.iter(|| {
+            corpus.search_par("qsratf2ar", 0.90, 10);
+            corpus.search_par("fimm5mm2f", 0.90, 10);
+            corpus.search_par("d85rrz3l3a", 0.90, 10);
+        });
+    });
+
+    group.bench_function("random text sharded serial search match", |b| {
+        b.iter(|| {
+            corpus.search("qsratf2ar", 0.90, 10);
+            corpus.search("fimm5mm2f", 0.90, 10);
+            corpus.search("d85rrz3l3a", 0.90, 10);
+        });
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     search_benches,
     bench_get_novel,
     bench_get_random,
     bench_get_domainnames,
+    bench_get_random_sharded,
 );
 
-criterion_main!(corpus_benches, search_benches);
+fn bench_compare_novel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("novel pairwise comparison");
+    let words = get_novel();
+    let words: Vec<&str> = words.iter().map(String::as_str).take(2000).collect();
+    let config = ngrammatic::CompareConfig::new().arity(2).case_insensitive();
+
+    group.bench_function("novel pairs comparer case insensitive", |b| {
+        let mut comparer = config.clone().comparer();
+        b.iter(|| {
+            words
+                .windows(2)
+                .map(|pair| comparer.compare(pair[0], pair[1]))
+                .sum::<f32>()
+        });
+    });
+
+    group.bench_function("novel pairs ngram builder case insensitive", |b| {
+        let ngram = |word: &str| ngrammatic::NgramBuilder::new(&word.to_lowercase()).finish();
+        b.iter(|| {
+            words
+                .windows(2)
+                .map(|pair| ngram(pair[0]).similarity_to(&ngram(pair[1]), 2.0))
+                .sum::<f32>()
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(compare_benches, bench_compare_novel);
+
+criterion_main!(corpus_benches, search_benches, compare_benches);
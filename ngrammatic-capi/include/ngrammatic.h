@@ -0,0 +1,149 @@
+/* C API of the ngrammatic fuzzy matching library. */
+
+#ifndef NGRAMMATIC_H
+#define NGRAMMATIC_H
+
+/* Generated from src/lib.rs by cbindgen. Don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Pad texts with `arity - 1` spaces on each side.
+#define NGRAMMATIC_PAD_AUTO 0
+
+// Don't pad texts.
+#define NGRAMMATIC_PAD_NONE 1
+
+// Pad texts with the `pad_text` of the `NgrammaticOptions` on each side.
+#define NGRAMMATIC_PAD_TEXT 2
+
+// The outcome of a call. On failure, `ngrammatic_last_error` describes what
+// went wrong.
+typedef enum {
+  // The call succeeded.
+  NGRAMMATIC_STATUS_OK = 0,
+  // A required pointer argument was null.
+  NGRAMMATIC_STATUS_NULL_POINTER,
+  // A string argument was not valid UTF-8.
+  NGRAMMATIC_STATUS_INVALID_UTF8,
+  // An option or argument was out of range, such as an arity of 0 or a
+  // threshold above 1.0.
+  NGRAMMATIC_STATUS_INVALID_ARGUMENT,
+  // Reading or writing a file failed.
+  NGRAMMATIC_STATUS_IO,
+  // A file was not a saved corpus.
+  NGRAMMATIC_STATUS_FORMAT,
+  // The text buffer was too small to hold the texts of all the results.
+  NGRAMMATIC_STATUS_BUFFER_TOO_SMALL,
+  // The library failed unexpectedly.
+  NGRAMMATIC_STATUS_PANIC,
+} NgrammaticStatus;
+
+// A corpus of texts to search, created with `ngrammatic_corpus_new` or
+// `ngrammatic_corpus_load`, and freed with `ngrammatic_corpus_free`.
+typedef struct NgrammaticCorpus NgrammaticCorpus;
+
+// Settings for creating a corpus with `ngrammatic_corpus_new`. Start from
+// `ngrammatic_options_default`.
+typedef struct {
+  // The number of characters in each gram, at least 1.
+  size_t arity;
+  // How texts are padded, one of the `NGRAMMATIC_PAD_` constants.
+  uint32_t pad;
+  // The padding added to each side of texts when `pad` is
+  // `NGRAMMATIC_PAD_TEXT`. Ignored otherwise.
+  const char *pad_text;
+  // Whether texts are lowercased before being indexed and searched.
+  bool case_insensitive;
+} NgrammaticOptions;
+
+// A search result, as written by `ngrammatic_corpus_search`.
+typedef struct {
+  // The matching text, NUL terminated, within the caller's text buffer.
+  const char *text;
+  // The similarity of the text to the query, from 0.0 to 1.0.
+  float similarity;
+} NgrammaticResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The default options: an arity of 2, automatic padding, and case sensitive
+// matching.
+NgrammaticOptions ngrammatic_options_default(void);
+
+// A description of the last failed call on the calling thread, NUL
+// terminated, or an empty string if none failed. It's valid until the next
+// failing call on the same thread.
+const char *ngrammatic_last_error(void);
+
+// Create an empty corpus with the given `options`, or the defaults if
+// `options` is null, storing it in `*corpus`.
+//
+// # Safety
+// `options` must be null or valid, and `corpus` must be valid for writes.
+NgrammaticStatus ngrammatic_corpus_new(const NgrammaticOptions *options, NgrammaticCorpus **corpus);
+
+// Free a `corpus`. Does nothing if `corpus` is null.
+//
+// # Safety
+// `corpus` must be null, or a corpus that hasn't been freed yet.
+void ngrammatic_corpus_free(NgrammaticCorpus *corpus);
+
+// Add the NUL terminated `text` to the `corpus`.
+//
+// # Safety
+// `corpus` must be a valid corpus, and `text` a NUL terminated string.
+NgrammaticStatus ngrammatic_corpus_add_text(NgrammaticCorpus *corpus, const char *text);
+
+// The number of texts in the `corpus`, or 0 if it's null.
+//
+// # Safety
+// `corpus` must be null or a valid corpus.
+size_t ngrammatic_corpus_len(const NgrammaticCorpus *corpus);
+
+// Search the `corpus` for up to `results_len` texts with a similarity to the
+// `query` of at least `threshold`, from most to least similar.
+//
+// Each result is written to `results`, and its text to `text_buffer`,
+// which is `text_buffer_len` bytes long, and `*found` is set to the number
+// of results written. If the texts don't all fit in the buffer, as many
+// results are written as fit, and `NGRAMMATIC_STATUS_BUFFER_TOO_SMALL` is
+// returned.
+//
+// # Safety
+// `corpus` must be a valid corpus, `query` a NUL terminated string,
+// `results` valid for `results_len` results, `text_buffer` valid for
+// `text_buffer_len` bytes, and `found` valid for writes. `results` and
+// `text_buffer` may be null if their lengths are 0.
+NgrammaticStatus ngrammatic_corpus_search(const NgrammaticCorpus *corpus,
+                                          const char *query,
+                                          float threshold,
+                                          NgrammaticResult *results,
+                                          size_t results_len,
+                                          char *text_buffer,
+                                          size_t text_buffer_len,
+                                          size_t *found);
+
+// Save the `corpus` to the file at the NUL terminated `path`, replacing it.
+//
+// # Safety
+// `corpus` must be a valid corpus, and `path` a NUL terminated string.
+NgrammaticStatus ngrammatic_corpus_save(const NgrammaticCorpus *corpus, const char *path);
+
+// Load a corpus saved by `ngrammatic_corpus_save` from the file at the NUL
+// terminated `path`, storing it in `*corpus`.
+//
+// # Safety
+// `path` must be a NUL terminated string, and `corpus` valid for writes.
+NgrammaticStatus ngrammatic_corpus_load(const char *path, NgrammaticCorpus **corpus);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NGRAMMATIC_H */
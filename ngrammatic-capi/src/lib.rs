@@ -0,0 +1,425 @@
+#![deny(missing_docs)]
+
+//! C API for building, searching, saving and loading corpora, so that
+//! programs written in other languages match texts just as Rust programs do.
+//! The declarations are in `include/ngrammatic.h`, generated from this crate
+//! by cbindgen.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use ngrammatic::{
+    Corpus, CorpusBuilder, Error, IdentityKeyTransformer, KeyTransformer, LinkedKeyTransformer,
+    LowerKeyTransformer, Pad, SearchOptions,
+};
+
+/// Pad texts with `arity - 1` spaces on each side.
+pub const NGRAMMATIC_PAD_AUTO: u32 = 0;
+/// Don't pad texts.
+pub const NGRAMMATIC_PAD_NONE: u32 = 1;
+/// Pad texts with the `pad_text` of the `NgrammaticOptions` on each side.
+pub const NGRAMMATIC_PAD_TEXT: u32 = 2;
+
+/// The outcome of a call. On failure, `ngrammatic_last_error` describes what
+/// went wrong.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NgrammaticStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer,
+    /// A string argument was not valid UTF-8.
+    InvalidUtf8,
+    /// An option or argument was out of range, such as an arity of 0 or a
+    /// threshold above 1.0.
+    InvalidArgument,
+    /// Reading or writing a file failed.
+    Io,
+    /// A file was not a saved corpus.
+    Format,
+    /// The text buffer was too small to hold the texts of all the results.
+    BufferTooSmall,
+    /// The library failed unexpectedly.
+    Panic,
+}
+
+/// Settings for creating a corpus with `ngrammatic_corpus_new`. Start from
+/// `ngrammatic_options_default`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct NgrammaticOptions {
+    /// The number of characters in each gram, at least 1.
+    pub arity: usize,
+    /// How texts are padded, one of the `NGRAMMATIC_PAD_` constants.
+    pub pad: u32,
+    /// The padding added to each side of texts when `pad` is
+    /// `NGRAMMATIC_PAD_TEXT`. Ignored otherwise.
+    pub pad_text: *const c_char,
+    /// Whether texts are lowercased before being indexed and searched.
+    pub case_insensitive: bool,
+}
+
+/// A search result, as written by `ngrammatic_corpus_search`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct NgrammaticResult {
+    /// The matching text, NUL terminated, within the caller's text buffer.
+    pub text: *const c_char,
+    /// The similarity of the text to the query, from 0.0 to 1.0.
+    pub similarity: f32,
+}
+
+/// A corpus of texts to search, created with `ngrammatic_corpus_new` or
+/// `ngrammatic_corpus_load`, and freed with `ngrammatic_corpus_free`.
+#[derive(Debug)]
+pub struct NgrammaticCorpus {
+    corpus: Corpus<LinkedKeyTransformer<IdentityKeyTransformer, Option<LowerKeyTransformer>>>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// A failed call, reported as `status`, with a `message` for
+/// `ngrammatic_last_error`.
+struct Failure {
+    status: NgrammaticStatus,
+    message: String,
+}
+
+impl Failure {
+    fn new(status: NgrammaticStatus, message: impl Into<String>) -> Self {
+        Failure {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        let status = match error {
+            Error::Io { .. } => NgrammaticStatus::Io,
+            Error::Format { .. } => NgrammaticStatus::Format,
+            _ => NgrammaticStatus::InvalidArgument,
+        };
+        Failure::new(status, error.to_string())
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(error: std::io::Error) -> Self {
+        Error::from(error).into()
+    }
+}
+
+/// Run `call`, converting failures and panics to a status, and recording
+/// their description for `ngrammatic_last_error`.
+fn guard(call: impl FnOnce() -> Result<(), Failure>) -> NgrammaticStatus {
+    let failure = match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => return NgrammaticStatus::Ok,
+        Ok(Err(failure)) => failure,
+        Err(_) => Failure::new(NgrammaticStatus::Panic, "ngrammatic panicked"),
+    };
+    // Strings from Rust can't contain NUL bytes, except those in texts
+    let message = CString::new(failure.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    failure.status
+}
+
+/// Borrow the C string `pointer`, the argument `name`d.
+///
+/// # Safety
+/// `pointer` must be null, or point to a NUL terminated string that outlives
+/// the borrow.
+unsafe fn borrow_str<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, Failure> {
+    if pointer.is_null() {
+        return Err(Failure::new(
+            NgrammaticStatus::NullPointer,
+            format!("{} is null", name),
+        ));
+    }
+    CStr::from_ptr(pointer).to_str().map_err(|_| {
+        Failure::new(
+            NgrammaticStatus::InvalidUtf8,
+            format!("{} is not valid UTF-8", name),
+        )
+    })
+}
+
+/// Check that the pointer argument `name`d isn't null.
+fn non_null<T>(pointer: *const T, name: &str) -> Result<(), Failure> {
+    if pointer.is_null() {
+        Err(Failure::new(
+            NgrammaticStatus::NullPointer,
+            format!("{} is null", name),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// The default options: an arity of 2, automatic padding, and case sensitive
+/// matching.
+#[no_mangle]
+pub extern "C" fn ngrammatic_options_default() -> NgrammaticOptions {
+    NgrammaticOptions {
+        arity: 2,
+        pad: NGRAMMATIC_PAD_AUTO,
+        pad_text: ptr::null(),
+        case_insensitive: false,
+    }
+}
+
+/// A description of the last failed call on the calling thread, NUL
+/// terminated, or an empty string if none failed. It's valid until the next
+/// failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn ngrammatic_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Create an empty corpus with the given `options`, or the defaults if
+/// `options` is null, storing it in `*corpus`.
+///
+/// # Safety
+/// `options` must be null or valid, and `corpus` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_new(
+    options: *const NgrammaticOptions,
+    corpus: *mut *mut NgrammaticCorpus,
+) -> NgrammaticStatus {
+    guard(|| {
+        non_null(corpus, "corpus")?;
+        let options = options
+            .as_ref()
+            .copied()
+            .unwrap_or_else(|| ngrammatic_options_default());
+        let pad = match options.pad {
+            NGRAMMATIC_PAD_AUTO => Pad::Auto,
+            NGRAMMATIC_PAD_NONE => Pad::None,
+            NGRAMMATIC_PAD_TEXT => Pad::Pad(borrow_str(options.pad_text, "pad_text")?.to_string()),
+            pad => {
+                return Err(Failure::new(
+                    NgrammaticStatus::InvalidArgument,
+                    format!("invalid pad {}", pad),
+                ))
+            }
+        };
+        let built = CorpusBuilder::default()
+            .arity(options.arity)
+            .pad_full(pad)
+            .link_key_transformer(options.case_insensitive.then(|| LowerKeyTransformer))
+            .try_finish()?;
+        *corpus = Box::into_raw(Box::new(NgrammaticCorpus { corpus: built }));
+        Ok(())
+    })
+}
+
+/// Free a `corpus`. Does nothing if `corpus` is null.
+///
+/// # Safety
+/// `corpus` must be null, or a corpus that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_free(corpus: *mut NgrammaticCorpus) {
+    if !corpus.is_null() {
+        drop(Box::from_raw(corpus));
+    }
+}
+
+/// Add the NUL terminated `text` to the `corpus`.
+///
+/// # Safety
+/// `corpus` must be a valid corpus, and `text` a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_add_text(
+    corpus: *mut NgrammaticCorpus,
+    text: *const c_char,
+) -> NgrammaticStatus {
+    guard(|| {
+        non_null(corpus, "corpus")?;
+        let text = borrow_str(text, "text")?;
+        (*corpus).corpus.add_text(text);
+        Ok(())
+    })
+}
+
+/// The number of texts in the `corpus`, or 0 if it's null.
+///
+/// # Safety
+/// `corpus` must be null or a valid corpus.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_len(corpus: *const NgrammaticCorpus) -> usize {
+    corpus.as_ref().map_or(0, |corpus| corpus.corpus.len())
+}
+
+/// Search the `corpus` for up to `results_len` texts with a similarity to the
+/// `query` of at least `threshold`, from most to least similar.
+///
+/// Each result is written to `results`, and its text to `text_buffer`,
+/// which is `text_buffer_len` bytes long, and `*found` is set to the number
+/// of results written. If the texts don't all fit in the buffer, as many
+/// results are written as fit, and `NGRAMMATIC_STATUS_BUFFER_TOO_SMALL` is
+/// returned.
+///
+/// # Safety
+/// `corpus` must be a valid corpus, `query` a NUL terminated string,
+/// `results` valid for `results_len` results, `text_buffer` valid for
+/// `text_buffer_len` bytes, and `found` valid for writes. `results` and
+/// `text_buffer` may be null if their lengths are 0.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn ngrammatic_corpus_search(
+    corpus: *const NgrammaticCorpus,
+    query: *const c_char,
+    threshold: f32,
+    results: *mut NgrammaticResult,
+    results_len: usize,
+    text_buffer: *mut c_char,
+    text_buffer_len: usize,
+    found: *mut usize,
+) -> NgrammaticStatus {
+    guard(|| {
+        non_null(corpus, "corpus")?;
+        non_null(found, "found")?;
+        *found = 0;
+        let query = borrow_str(query, "query")?;
+        let options = SearchOptions::new().threshold(threshold);
+        if results_len == 0 {
+            // Still report an invalid threshold, as searching would
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(Error::InvalidThreshold(threshold).into());
+            }
+            return Ok(());
+        }
+        non_null(results, "results")?;
+        let results = slice::from_raw_parts_mut(results, results_len);
+        let buffer: &mut [u8] = if text_buffer_len == 0 {
+            &mut []
+        } else {
+            non_null(text_buffer, "text_buffer")?;
+            slice::from_raw_parts_mut(text_buffer.cast(), text_buffer_len)
+        };
+        let matches = (*corpus)
+            .corpus
+            .search_with(query, &options.limit(results_len))?;
+        let mut used = 0;
+        for (slot, result) in results.iter_mut().zip(&matches) {
+            let text = result.text.as_bytes();
+            let end = used + text.len();
+            if end >= buffer.len() {
+                return Err(Failure::new(
+                    NgrammaticStatus::BufferTooSmall,
+                    format!(
+                        "the text buffer of {} bytes is too small for {} results",
+                        buffer.len(),
+                        matches.len()
+                    ),
+                ));
+            }
+            buffer[used..end].copy_from_slice(text);
+            buffer[end] = 0;
+            *slot = NgrammaticResult {
+                text: buffer[used..].as_ptr().cast(),
+                similarity: result.similarity,
+            };
+            used = end + 1;
+            *found += 1;
+        }
+        Ok(())
+    })
+}
+
+/// Save the `corpus` to the file at the NUL terminated `path`, replacing it.
+///
+/// # Safety
+/// `corpus` must be a valid corpus, and `path` a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_save(
+    corpus: *const NgrammaticCorpus,
+    path: *const c_char,
+) -> NgrammaticStatus {
+    guard(|| {
+        non_null(corpus, "corpus")?;
+        let path = borrow_str(path, "path")?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        (*corpus).corpus.save(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+/// Load a corpus saved by `ngrammatic_corpus_save` from the file at the NUL
+/// terminated `path`, storing it in `*corpus`.
+///
+/// # Safety
+/// `path` must be a NUL terminated string, and `corpus` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ngrammatic_corpus_load(
+    path: *const c_char,
+    corpus: *mut *mut NgrammaticCorpus,
+) -> NgrammaticStatus {
+    guard(|| {
+        non_null(corpus, "corpus")?;
+        let path = borrow_str(path, "path")?;
+        let reader = BufReader::new(File::open(path)?);
+        let loaded = Corpus::load_with(reader, |lowercase| {
+            IdentityKeyTransformer.link(lowercase.then(|| LowerKeyTransformer))
+        })?;
+        *corpus = Box::into_raw(Box::new(NgrammaticCorpus { corpus: loaded }));
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capi_failures() {
+        unsafe {
+            let mut corpus = ptr::null_mut();
+            let mut options = ngrammatic_options_default();
+            options.arity = 0;
+            assert_eq!(
+                ngrammatic_corpus_new(&options, &mut corpus),
+                NgrammaticStatus::InvalidArgument
+            );
+            assert!(corpus.is_null());
+            let error = CStr::from_ptr(ngrammatic_last_error()).to_str().unwrap();
+            assert!(error.contains("arity"));
+
+            options.arity = 3;
+            options.pad = NGRAMMATIC_PAD_TEXT;
+            assert_eq!(
+                ngrammatic_corpus_new(&options, &mut corpus),
+                NgrammaticStatus::NullPointer
+            );
+            options.pad = 7;
+            assert_eq!(
+                ngrammatic_corpus_new(&options, &mut corpus),
+                NgrammaticStatus::InvalidArgument
+            );
+
+            assert_eq!(
+                ngrammatic_corpus_new(ptr::null(), &mut corpus),
+                NgrammaticStatus::Ok
+            );
+            let invalid = b"\xff\0";
+            assert_eq!(
+                ngrammatic_corpus_add_text(corpus, invalid.as_ptr().cast()),
+                NgrammaticStatus::InvalidUtf8
+            );
+            assert_eq!(ngrammatic_corpus_len(corpus), 0);
+            ngrammatic_corpus_free(corpus);
+            ngrammatic_corpus_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Test program for the C API, compiled and run by `cargo test -p
+ * ngrammatic-capi`. Define NGRAMMATIC_CAPI_TEST_MAIN to build it as a
+ * standalone program instead, linked against the library.
+ */
+#include <math.h>
+#include <string.h>
+
+#include "ngrammatic.h"
+
+#define CHECK(condition)     \
+    do {                     \
+        if (!(condition)) {  \
+            return __LINE__; \
+        }                    \
+    } while (0)
+
+/* Returns the line of the first failed check, or 0 if all of them passed. */
+int ngrammatic_capi_test(const char *path) {
+    NgrammaticCorpus *corpus = NULL;
+    NgrammaticOptions options = ngrammatic_options_default();
+    NgrammaticResult results[4];
+    char texts[64];
+    size_t found = 0;
+    const char *words[] = {"tomato", "Potato", "carrot", "tornado"};
+    size_t i;
+
+    options.arity = 2;
+    options.case_insensitive = true;
+    CHECK(ngrammatic_corpus_new(&options, &corpus) == NGRAMMATIC_STATUS_OK);
+    for (i = 0; i < sizeof(words) / sizeof(words[0]); i++) {
+        CHECK(ngrammatic_corpus_add_text(corpus, words[i]) == NGRAMMATIC_STATUS_OK);
+    }
+    CHECK(ngrammatic_corpus_len(corpus) == 4);
+
+    CHECK(ngrammatic_corpus_search(corpus, "TOMACCO", 0.25f, results, 4, texts,
+                                   sizeof(texts), &found) == NGRAMMATIC_STATUS_OK);
+    CHECK(found >= 1);
+    CHECK(strcmp(results[0].text, "tomato") == 0);
+    CHECK(results[0].similarity > 0.5f && results[0].similarity < 1.0f);
+    for (i = 1; i < found; i++) {
+        CHECK(results[i].similarity <= results[i - 1].similarity);
+    }
+
+    /* Case insensitive keys match exactly */
+    CHECK(ngrammatic_corpus_search(corpus, "POTATO", 0.9f, results, 1, texts,
+                                   sizeof(texts), &found) == NGRAMMATIC_STATUS_OK);
+    CHECK(found == 1);
+    CHECK(strcmp(results[0].text, "potato") == 0);
+    CHECK(fabsf(results[0].similarity - 1.0f) < 1e-6f);
+
+    /* Only the results whose texts fit in the buffer are written */
+    CHECK(ngrammatic_corpus_search(corpus, "tomato", 0.0f, results, 4, texts, 10,
+                                   &found) == NGRAMMATIC_STATUS_BUFFER_TOO_SMALL);
+    CHECK(found == 1);
+    CHECK(strcmp(results[0].text, "tomato") == 0);
+    CHECK(strlen(ngrammatic_last_error()) > 0);
+
+    CHECK(ngrammatic_corpus_search(corpus, "tomato", 1.5f, results, 4, texts,
+                                   sizeof(texts), &found) == NGRAMMATIC_STATUS_INVALID_ARGUMENT);
+    CHECK(found == 0);
+    CHECK(ngrammatic_corpus_search(corpus, NULL, 0.5f, results, 4, texts, sizeof(texts),
+                                   &found) == NGRAMMATIC_STATUS_NULL_POINTER);
+
+    /* Saved corpora load with the same settings and texts */
+    CHECK(ngrammatic_corpus_save(corpus, path) == NGRAMMATIC_STATUS_OK);
+    ngrammatic_corpus_free(corpus);
+    corpus = NULL;
+    CHECK(ngrammatic_corpus_load(path, &corpus) == NGRAMMATIC_STATUS_OK);
+    CHECK(ngrammatic_corpus_len(corpus) == 4);
+    CHECK(ngrammatic_corpus_search(corpus, "TOMACCO", 0.25f, results, 1, texts,
+                                   sizeof(texts), &found) == NGRAMMATIC_STATUS_OK);
+    CHECK(found == 1);
+    CHECK(strcmp(results[0].text, "tomato") == 0);
+    ngrammatic_corpus_free(corpus);
+
+    CHECK(ngrammatic_corpus_load("/nonexistent/ngrammatic.corpus", &corpus) ==
+          NGRAMMATIC_STATUS_IO);
+
+    /* Padding with a custom text, or none at all */
+    options = ngrammatic_options_default();
+    options.arity = 3;
+    options.pad = NGRAMMATIC_PAD_TEXT;
+    options.pad_text = "$";
+    CHECK(ngrammatic_corpus_new(&options, &corpus) == NGRAMMATIC_STATUS_OK);
+    CHECK(ngrammatic_corpus_add_text(corpus, "spam") == NGRAMMATIC_STATUS_OK);
+    CHECK(ngrammatic_corpus_search(corpus, "spa", 0.1f, results, 4, texts, sizeof(texts),
+                                   &found) == NGRAMMATIC_STATUS_OK);
+    CHECK(found == 1);
+    ngrammatic_corpus_free(corpus);
+
+    options.pad = NGRAMMATIC_PAD_NONE;
+    CHECK(ngrammatic_corpus_new(&options, &corpus) == NGRAMMATIC_STATUS_OK);
+    CHECK(ngrammatic_corpus_add_text(corpus, "spam") == NGRAMMATIC_STATUS_OK);
+    CHECK(ngrammatic_corpus_search(corpus, "spa", 0.1f, results, 4, texts, sizeof(texts),
+                                   &found) == NGRAMMATIC_STATUS_OK);
+    CHECK(found == 1);
+    ngrammatic_corpus_free(corpus);
+
+    return 0;
+}
+
+#ifdef NGRAMMATIC_CAPI_TEST_MAIN
+#include <stdio.h>
+
+int main(int argc, char **argv) {
+    int failed = ngrammatic_capi_test(argc > 1 ? argv[1] : "ngrammatic-capi-test.corpus");
+    if (failed) {
+        fprintf(stderr, "check on line %d failed: %s\n", failed, ngrammatic_last_error());
+    }
+    return failed != 0;
+}
+#endif
@@ -0,0 +1,82 @@
+//! Tests of the C API from the outside: the checked in header must match the
+//! one cbindgen generates, and the C test program in tests/capi.c must pass
+//! when compiled and linked against the library, as a C program would be.
+
+use std::env;
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::process::Command;
+
+fn crate_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// The directory cargo put the shared library in for the tests, which is
+/// the one holding this test.
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    exe.parent().unwrap().to_path_buf()
+}
+
+#[test]
+fn capi_header_is_current() {
+    let crate_dir = crate_dir();
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("cbindgen.toml is invalid");
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .expect("generating the C header failed")
+        .write(&mut generated);
+    let checked_in = std::fs::read(crate_dir.join("include/ngrammatic.h")).unwrap();
+    assert!(
+        checked_in == generated,
+        "include/ngrammatic.h is out of date; regenerate it with \
+         `cbindgen --config cbindgen.toml --output include/ngrammatic.h src/lib.rs`"
+    );
+}
+
+/// Compile tests/capi.c into a program linked against the shared library,
+/// with the C compiler named by `CC`, or `cc`.
+#[cfg(unix)]
+fn compile_c_program(output: &std::path::Path) {
+    let crate_dir = crate_dir();
+    let library_dir = library_dir();
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .arg("-DNGRAMMATIC_CAPI_TEST_MAIN")
+        .arg("-I")
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/capi.c"))
+        .arg("-o")
+        .arg(output)
+        .arg("-L")
+        .arg(&library_dir)
+        .arg(format!("-Wl,-rpath,{}", library_dir.display()))
+        .arg("-lngrammatic_capi")
+        .arg("-lm")
+        .status()
+        .unwrap_or_else(|error| panic!("running {} failed: {}", compiler, error));
+    assert!(status.success(), "compiling tests/capi.c failed");
+}
+
+#[cfg(unix)]
+#[test]
+fn capi_c_program() {
+    let dir = env::temp_dir().join(format!("ngrammatic-capi-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("capi");
+    compile_c_program(&program);
+    let output = Command::new(&program)
+        .arg(dir.join("corpus"))
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        output.status.success(),
+        "tests/capi.c failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,710 @@
+//! Command-line tool for building, querying and inspecting ngrammatic
+//! corpora.
+#![deny(missing_docs)]
+// The cli feature's dependencies need a newer toolchain than the library
+#![allow(clippy::incompatible_msrv)]
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[cfg(feature = "server")]
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+
+#[cfg(feature = "server")]
+use ngrammatic::{ConcurrentCorpus, SearchServer};
+use ngrammatic::{
+    Corpus, CorpusBuilder, CorpusStats, Error, IdentityKeyTransformer, KeyTransformer,
+    LinkedKeyTransformer, LowerKeyTransformer, Pad, ReadOptions, ScanMatch, ScanOptions, Scoring,
+    SearchOptions, SearchResult, Splitter,
+};
+
+/// Key transformer of the corpora the tool builds, lowercasing keys when
+/// matching texts regardless of case. Saved corpora record which it is.
+type CaseKeyTransformer = LinkedKeyTransformer<IdentityKeyTransformer, Option<LowerKeyTransformer>>;
+
+/// A corpus built or loaded by the tool.
+type CaseCorpus = Corpus<CaseKeyTransformer>;
+
+/// Build, query and inspect fuzzy matching corpora.
+#[derive(Debug, Parser)]
+#[command(name = "ngrammatic", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Build a corpus from a word list, and save it.
+    Build {
+        /// The word list, one text per line, or `-` for standard input
+        #[arg(default_value = "-")]
+        words: PathBuf,
+        /// Where to save the corpus
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        settings: SettingsArgs,
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Search a corpus for the texts most similar to each query.
+    Search {
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Queries to search for. Read one per line from standard input if
+        /// none are given.
+        queries: Vec<String>,
+    },
+    /// Show statistics about a corpus.
+    Stats {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+    },
+    /// Match each line of one file against the texts of another.
+    Join {
+        /// The file whose lines are looked up, or `-` for standard input
+        left: PathBuf,
+        #[command(flatten)]
+        source: SourceArgs,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Find the spans of text that fuzzily match entries of a corpus.
+    Grep {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Files to scan, or `-` for standard input
+        #[arg(default_value = "-")]
+        inputs: Vec<PathBuf>,
+        /// Largest number of consecutive words matched together
+        #[arg(short, long, default_value_t = 1)]
+        max_words: usize,
+        /// Minimum similarity of matches, from 0.0 to 1.0
+        #[arg(short, long, default_value_t = 0.8)]
+        threshold: f32,
+        /// Report overlapping matches, rather than only the best of them
+        #[arg(long)]
+        overlapping: bool,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+    },
+    /// Serve searches of a corpus over HTTP, with JSON bodies.
+    #[cfg(feature = "server")]
+    Serve {
+        #[command(flatten)]
+        source: SourceArgs,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Number of worker threads handling requests
+        #[arg(long, default_value_t = 4)]
+        threads: usize,
+        /// Number of independently locked shards to split the corpus into
+        #[arg(long, default_value_t = 16)]
+        shards: usize,
+    },
+}
+
+/// Settings of a corpus built from a word list.
+#[derive(Debug, Args)]
+struct SettingsArgs {
+    /// Gram size
+    #[arg(short, long)]
+    arity: Option<usize>,
+    /// Padding around texts: `auto`, `none`, or the padding itself
+    #[arg(short, long)]
+    pad: Option<String>,
+    /// Match texts regardless of case
+    #[arg(short = 'i', long)]
+    case_insensitive: bool,
+}
+
+impl SettingsArgs {
+    fn is_set(&self) -> bool {
+        self.arity.is_some() || self.pad.is_some() || self.case_insensitive
+    }
+
+    fn pad(&self) -> Pad {
+        match self.pad.as_deref() {
+            None | Some("auto") => Pad::Auto,
+            Some("none") => Pad::None,
+            Some(pad) => Pad::Pad(pad.to_string()),
+        }
+    }
+
+    fn builder(&self) -> CorpusBuilder<CaseKeyTransformer> {
+        CorpusBuilder::default()
+            .arity(self.arity.unwrap_or(2))
+            .pad_full(self.pad())
+            .link_key_transformer(self.case_insensitive.then(|| LowerKeyTransformer))
+    }
+}
+
+/// How word lists are read.
+#[derive(Debug, Args)]
+struct ReadArgs {
+    /// Take each whitespace separated word as a text, rather than each line
+    #[arg(long)]
+    split_words: bool,
+    /// Skip lines starting with this prefix
+    #[arg(long)]
+    comment: Option<String>,
+}
+
+impl ReadArgs {
+    fn options(&self) -> ReadOptions {
+        let mut options = ReadOptions::new();
+        if self.split_words {
+            options = options.splitter(Splitter::Whitespace);
+        }
+        if let Some(prefix) = &self.comment {
+            options = options.comment_prefix(prefix);
+        }
+        options
+    }
+}
+
+/// Where the corpus to query comes from: either a saved corpus, or a word
+/// list to build one from.
+#[derive(Debug, Args)]
+struct SourceArgs {
+    /// A corpus saved by `build`
+    #[arg(
+        short,
+        long,
+        conflicts_with = "words",
+        required_unless_present = "words"
+    )]
+    corpus: Option<PathBuf>,
+    /// A word list to build the corpus from, one text per line
+    #[arg(short, long)]
+    words: Option<PathBuf>,
+    #[command(flatten)]
+    settings: SettingsArgs,
+    #[command(flatten)]
+    read: ReadArgs,
+}
+
+impl SourceArgs {
+    fn corpus(&self, stdin: &mut dyn BufRead) -> Result<CaseCorpus, CliError> {
+        match (&self.corpus, &self.words) {
+            (Some(path), _) => {
+                if self.settings.is_set() {
+                    return Err(usage(
+                        ErrorKind::ArgumentConflict,
+                        "corpus settings can't be changed when loading a saved corpus",
+                    ));
+                }
+                let reader = BufReader::new(File::open(path)?);
+                Ok(Corpus::load_with(reader, |lowercase| {
+                    IdentityKeyTransformer.link(lowercase.then(|| LowerKeyTransformer))
+                })?)
+            }
+            (None, Some(path)) => Ok(with_input(path, stdin, |reader| {
+                Ok(self
+                    .settings
+                    .builder()
+                    .fill_from_reader(reader, &self.read.options())?
+                    .finish())
+            })?),
+            (None, None) => Err(usage(
+                ErrorKind::MissingRequiredArgument,
+                "either --corpus or --words is required",
+            )),
+        }
+    }
+}
+
+/// How to search, and how to show the results.
+#[derive(Debug, Args)]
+struct SearchArgs {
+    /// Minimum similarity of results, from 0.0 to 1.0
+    #[arg(short, long, default_value_t = 0.0)]
+    threshold: f32,
+    /// Maximum number of results per query
+    #[arg(short, long, default_value_t = 10)]
+    limit: usize,
+    /// Warp factor of the similarity, from 1.0 to 3.0
+    #[arg(long, default_value_t = 2.0)]
+    warp: f32,
+    /// How the similarity is computed from the shared grams
+    #[arg(long, value_enum, default_value_t = ScoringArg::Ngram)]
+    scoring: ScoringArg,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Plain)]
+    format: Format,
+}
+
+impl SearchArgs {
+    fn options(&self) -> SearchOptions {
+        SearchOptions::new()
+            .threshold(self.threshold)
+            .limit(self.limit)
+            .warp(self.warp)
+            .scoring(self.scoring.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ScoringArg {
+    /// Warped ratio of shared grams to all grams
+    Ngram,
+    /// IDF weighted Jaccard similarity
+    WeightedJaccard,
+    /// IDF weighted cosine similarity
+    WeightedCosine,
+}
+
+impl From<ScoringArg> for Scoring {
+    fn from(scoring: ScoringArg) -> Self {
+        match scoring {
+            ScoringArg::Ngram => Scoring::Ngram,
+            ScoringArg::WeightedJaccard => Scoring::WeightedJaccard,
+            ScoringArg::WeightedCosine => Scoring::WeightedCosine,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Human readable text
+    Plain,
+    /// Tab separated values, one row per result
+    Tsv,
+    /// A JSON object per line
+    Json,
+}
+
+/// A failure of the tool: either it was invoked wrongly, or the library
+/// failed.
+#[derive(Debug)]
+enum CliError {
+    /// The arguments were invalid, reported by clap along with the usage
+    Usage(clap::Error),
+    /// Building, loading or searching a corpus failed
+    Library(Error),
+}
+
+impl From<Error> for CliError {
+    fn from(error: Error) -> Self {
+        CliError::Library(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Library(error.into())
+    }
+}
+
+/// An error in how the tool was invoked, of the given `kind`.
+fn usage(kind: ErrorKind, message: &str) -> CliError {
+    CliError::Usage(Cli::command().error(kind, message))
+}
+
+/// Run `f` with a reader of the file at `path`, or of `stdin` if the path is
+/// `-`.
+fn with_input<T, F>(path: &Path, stdin: &mut dyn BufRead, f: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut dyn BufRead) -> Result<T, Error>,
+{
+    if path == Path::new("-") {
+        f(stdin)
+    } else {
+        f(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// Write the `results` of searching for `query` in the given `format`.
+fn write_results(
+    out: &mut dyn Write,
+    format: Format,
+    query: &str,
+    results: &[SearchResult],
+) -> Result<(), Error> {
+    match format {
+        Format::Plain => {
+            writeln!(out, "{}", query)?;
+            for result in results {
+                writeln!(out, "  {:.4}  {}", result.similarity, result.text)?;
+            }
+        }
+        Format::Tsv => {
+            for result in results {
+                writeln!(out, "{}\t{}\t{}", query, result.text, result.similarity)?;
+            }
+        }
+        Format::Json => {
+            let line = json!({ "query": query, "results": results });
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the `stats` of a corpus to `out` in the given `format`. The TSV
+/// format has a line per statistic, and one per top gram.
+fn write_stats(out: &mut dyn Write, format: Format, stats: &CorpusStats) -> Result<(), Error> {
+    match format {
+        Format::Plain => write!(out, "{}", stats)?,
+        Format::Tsv => {
+            let postings = &stats.postings;
+            let heap = &stats.heap;
+            let rows = [
+                ("entries", stats.entries.to_string()),
+                ("distinct_grams", stats.distinct_grams.to_string()),
+                ("stop_grams", stats.stop_grams.to_string()),
+                ("postings", postings.total.to_string()),
+                ("postings_min", postings.min.to_string()),
+                ("postings_median", postings.median.to_string()),
+                ("postings_p99", postings.p99.to_string()),
+                ("postings_max", postings.max.to_string()),
+                ("grams_per_entry", stats.mean_grams_per_entry.to_string()),
+                ("heap_bytes", heap.total().to_string()),
+                ("heap_strings", heap.strings.to_string()),
+                ("heap_ngrams", heap.ngrams.to_string()),
+                ("heap_postings", heap.postings.to_string()),
+                ("heap_entries", heap.entries.to_string()),
+                ("heap_payloads", heap.payloads.to_string()),
+            ];
+            for (name, value) in rows {
+                writeln!(out, "{}\t{}", name, value)?;
+            }
+            for (gram, frequency) in &stats.top_grams {
+                writeln!(out, "top_gram\t{}\t{}", gram, frequency)?;
+            }
+        }
+        Format::Json => {
+            let mut line = json!(stats);
+            line["heap"]["total"] = json!(stats.heap.total());
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Search the `corpus` for each non-blank line of `queries`.
+fn search_lines(
+    corpus: &CaseCorpus,
+    queries: &mut dyn BufRead,
+    search: &SearchArgs,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    let options = search.options();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if queries.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let query = line.trim();
+        if !query.is_empty() {
+            write_results(
+                out,
+                search.format,
+                query,
+                &corpus.search_with(query, &options)?,
+            )?;
+        }
+    }
+}
+
+/// Run the tool as instructed by `cli`, with the given standard input and
+/// output.
+fn run(cli: Cli, stdin: &mut dyn BufRead, out: &mut dyn Write) -> Result<(), CliError> {
+    match cli.command {
+        Command::Build {
+            words,
+            output,
+            settings,
+            read,
+        } => {
+            let corpus = with_input(&words, stdin, |reader| {
+                Ok(settings
+                    .builder()
+                    .fill_from_reader(reader, &read.options())?
+                    .finish())
+            })?;
+            let mut writer = BufWriter::new(File::create(&output)?);
+            corpus.save(&mut writer)?;
+            writer.flush()?;
+            writeln!(
+                out,
+                "saved {} entries to {}",
+                corpus.len(),
+                output.display()
+            )?;
+        }
+        Command::Search {
+            source,
+            search,
+            queries,
+        } => {
+            let corpus = source.corpus(stdin)?;
+            if queries.is_empty() {
+                search_lines(&corpus, stdin, &search, out)?;
+            } else {
+                let options = search.options();
+                for query in &queries {
+                    let results = corpus.search_with(query, &options)?;
+                    write_results(out, search.format, query, &results)?;
+                }
+            }
+        }
+        Command::Stats { source, format } => {
+            let corpus = source.corpus(stdin)?;
+            write_stats(out, format, &corpus.stats())?;
+        }
+        Command::Join {
+            left,
+            source,
+            search,
+        } => {
+            let corpus = source.corpus(stdin)?;
+            with_input(&left, stdin, |reader| {
+                search_lines(&corpus, reader, &search, out)
+            })?;
+        }
+        Command::Grep {
+            source,
+            inputs,
+            max_words,
+            threshold,
+            overlapping,
+            format,
+        } => {
+            let corpus = source.corpus(stdin)?;
+            let options = ScanOptions::new()
+                .max_words(max_words)
+                .threshold(threshold)
+                .overlapping(overlapping);
+            for input in &inputs {
+                with_input(input, stdin, |reader| {
+                    for found in corpus.scan(reader, &options)? {
+                        write_match(out, format, &input.display().to_string(), &found?)?;
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+        #[cfg(feature = "server")]
+        Command::Serve {
+            source,
+            addr,
+            threads,
+            shards,
+        } => {
+            let corpus = ConcurrentCorpus::from_corpus(source.corpus(stdin)?, shards);
+            let server = SearchServer::bind(addr.as_str(), Arc::new(corpus))?.threads(threads);
+            if let Some(addr) = server.local_addr() {
+                writeln!(out, "listening on http://{}", addr)?;
+                out.flush()?;
+            }
+            server.run();
+        }
+    }
+    Ok(())
+}
+
+/// Write a match `found` in the file called `name` in the given `format`.
+fn write_match(
+    out: &mut dyn Write,
+    format: Format,
+    name: &str,
+    found: &ScanMatch,
+) -> Result<(), Error> {
+    match format {
+        Format::Plain => writeln!(
+            out,
+            "{}:{}:{}: {} -> {} ({:.4})",
+            name, found.line, found.column, found.text, found.result.text, found.result.similarity
+        )?,
+        Format::Tsv => writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            name,
+            found.line,
+            found.column,
+            found.start,
+            found.end,
+            found.text,
+            found.result.text,
+            found.result.similarity
+        )?,
+        Format::Json => {
+            let line = json!({
+                "file": name,
+                "line": found.line,
+                "column": found.column,
+                "start": found.start,
+                "end": found.end,
+                "text": found.text,
+                "entry": found.result.text,
+                "similarity": found.result.similarity,
+            });
+            writeln!(out, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let result = run(cli, &mut stdin.lock(), &mut out).and_then(|()| Ok(out.flush()?));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(error)) => error.exit(),
+        // Stop quietly when the output is closed early, e.g. by `head`
+        Err(CliError::Library(Error::Io {
+            kind: io::ErrorKind::BrokenPipe,
+            ..
+        })) => ExitCode::SUCCESS,
+        Err(CliError::Library(error)) => {
+            eprintln!("ngrammatic: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_args(args: &[&str], stdin: &str) -> Result<String, CliError> {
+        let cli = Cli::try_parse_from(std::iter::once("ngrammatic").chain(args.iter().copied()))
+            .map_err(CliError::Usage)?;
+        let mut out = Vec::new();
+        run(cli, &mut stdin.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn cli_build_and_search() {
+        let dir = std::env::temp_dir().join(format!("ngrammatic-cli-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let saved = dir.join("corpus.txt");
+        let saved = saved.to_str().unwrap();
+        let words = "tomato\nPotato\n# comment\ncarrot\n";
+        let output = run_args(
+            &["build", "-o", saved, "-i", "-a", "3", "--comment", "#"],
+            words,
+        )
+        .unwrap();
+        assert!(output.starts_with("saved 3 entries"));
+
+        let output = run_args(
+            &[
+                "search", "-c", saved, "-l", "1", "-f", "tsv", "TOMACCO", "potatoes",
+            ],
+            "",
+        )
+        .unwrap();
+        let rows: Vec<Vec<&str>> = output.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][..2], ["TOMACCO", "tomato"]);
+        assert_eq!(rows[1][..2], ["potatoes", "potato"]);
+
+        let output = run_args(&["search", "-c", saved, "-f", "json"], "carot\n\n").unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["query"], "carot");
+        assert_eq!(line["results"][0]["text"], "carrot");
+        let scoring = ["--scoring", "weighted-jaccard"];
+        let args = [&["search", "-c", saved, "-f", "tsv"][..], &scoring].concat();
+        let output = run_args(&args, "carot\n").unwrap();
+        assert!(output.starts_with("carot\tcarrot\t"));
+
+        let output = run_args(&["stats", "-c", saved], "").unwrap();
+        assert!(output.starts_with("entries: 3\ndistinct grams: "));
+        let output = run_args(&["stats", "-c", saved, "-f", "tsv"], "").unwrap();
+        assert!(output.starts_with("entries\t3\n"));
+        assert!(output.contains("\ntop_gram\tato\t2\n"));
+        let output = run_args(&["stats", "-c", saved, "-f", "json"], "").unwrap();
+        let stats: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(stats["entries"], 3);
+        assert!(stats["heap"]["total"].as_u64().unwrap() > 0);
+        assert!(matches!(
+            run_args(&["stats", "-c", saved, "-a", "2"], ""),
+            Err(CliError::Usage(error)) if error.kind() == ErrorKind::ArgumentConflict
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli_join() {
+        let dir = std::env::temp_dir().join(format!("ngrammatic-join-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let right = dir.join("right.txt");
+        std::fs::write(&right, "tomato\npotato\ncarrot\n").unwrap();
+        let output = run_args(
+            &[
+                "join",
+                "-",
+                "-w",
+                right.to_str().unwrap(),
+                "-t",
+                "0.3",
+                "-l",
+                "1",
+                "-f",
+                "tsv",
+            ],
+            "tomatos\nzzz\ncarrots\n",
+        )
+        .unwrap();
+        let matched: Vec<&str> = output
+            .lines()
+            .map(|line| line.split('\t').nth(1).unwrap())
+            .collect();
+        assert_eq!(matched, vec!["tomato", "carrot"]);
+        assert!(run_args(&["join", "-", "-w", "missing.txt"], "").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli_grep() {
+        let dir = std::env::temp_dir().join(format!("ngrammatic-grep-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let names = dir.join("names.txt");
+        std::fs::write(&names, "acme widget\nsprocket\n").unwrap();
+        let names = names.to_str().unwrap();
+        let log = "shipped 2 sprokets\nreturned: Acme Widgt\n";
+        let output = run_args(&["grep", "-w", names, "-i", "-m", "2", "-t", "0.5"], log).unwrap();
+        assert_eq!(
+            output,
+            "-:1:10: sprokets -> sprocket (0.7500)\n-:2:10: Acme Widgt -> acme widget (0.9467)\n"
+        );
+        let output = run_args(&["grep", "-w", names, "-t", "0.5", "-f", "json"], log).unwrap();
+        let found: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(found["entry"], "sprocket");
+        assert_eq!(found["start"], 10);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cli_invalid_options() {
+        assert!(matches!(
+            run_args(&["search", "tomato"], ""),
+            Err(CliError::Usage(_))
+        ));
+        assert!(matches!(
+            run_args(&["search", "-w", "-", "-t", "2", "tomato"], "tomato\n"),
+            Err(CliError::Library(Error::InvalidThreshold(_)))
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+#![deny(missing_docs)]
+
+use std::cmp::Ordering;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    IdentityKeyTransformer, KeyTransformer, LinkedKeyTransformer, LowerKeyTransformer, Ngram, Pad,
+};
+
+/// How the grams two texts share are turned into their similarity, from 0.0
+/// to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CompareMetric {
+    /// The similarity `Corpus` searches score results by: the shared grams
+    /// out of all the distinct grams of both texts, with a warp factor
+    /// (clamped to the range 1.0 to 3.0) that boosts partial matches.
+    Warped(f32),
+    /// The Sørensen–Dice coefficient: twice the shared grams, out of the
+    /// grams of both texts.
+    Dice,
+    /// The shared grams, out of the grams of the text with fewer of them.
+    Overlap,
+}
+
+impl Default for CompareMetric {
+    /// Default is a warp of 2.0, scoring as `Corpus::search` does.
+    fn default() -> Self {
+        CompareMetric::Warped(2.0)
+    }
+}
+
+impl CompareMetric {
+    /// The similarity of texts with `same` grams in common, out of `grams_a`
+    /// and `grams_b` grams.
+    fn score(&self, same: usize, grams_a: usize, grams_b: usize) -> f32 {
+        match *self {
+            CompareMetric::Warped(warp) => Ngram::similarity(same, grams_a + grams_b - same, warp),
+            CompareMetric::Dice if grams_a + grams_b > 0 => {
+                2.0 * same as f32 / (grams_a + grams_b) as f32
+            }
+            CompareMetric::Overlap if grams_a.min(grams_b) > 0 => {
+                same as f32 / grams_a.min(grams_b) as f32
+            }
+            CompareMetric::Dice | CompareMetric::Overlap => 0.0,
+        }
+    }
+}
+
+/// Settings for comparing pairs of texts with `compare` or a `Comparer`.
+/// Texts are compared by their grams of a single arity, as an `Ngram` built
+/// with the same arity and padding would be, without positional grams or
+/// skip-grams.
+/// ```rust
+/// # use ngrammatic::{compare, CompareConfig, CompareMetric, NgramBuilder, Pad};
+/// # fn main() {
+/// let config = CompareConfig::new().arity(3).pad_full(Pad::Auto).warp(2.0);
+/// let a = NgramBuilder::new("tomato").arity(3).finish();
+/// let b = NgramBuilder::new("tomacco").arity(3).finish();
+/// assert_eq!(compare("tomato", "tomacco", &config), a.similarity_to(&b, 2.0));
+///
+/// let config = config.case_insensitive().metric(CompareMetric::Dice);
+/// assert_eq!(compare("Tomato", "TOMATO", &config), 1.0);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CompareConfig<KT = IdentityKeyTransformer> {
+    arity: usize,
+    pad_left: Pad,
+    pad_right: Pad,
+    metric: CompareMetric,
+    key_transformer: KT,
+}
+
+impl Default for CompareConfig {
+    /// The settings `CorpusBuilder` and `Corpus::search` default to: an
+    /// arity of 2, `Auto` padding, a warp of 2.0, and texts compared as they
+    /// are.
+    fn default() -> Self {
+        CompareConfig {
+            arity: 2,
+            pad_left: Pad::Auto,
+            pad_right: Pad::Auto,
+            metric: CompareMetric::default(),
+            key_transformer: IdentityKeyTransformer,
+        }
+    }
+}
+
+impl CompareConfig {
+    /// Initialize a new instance of `CompareConfig` with the default
+    /// settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<KT> CompareConfig<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Set `arity` (the _n_ in _ngram_). An arity of 0 is treated as 1.
+    pub fn arity(mut self, arity: usize) -> Self {
+        self.arity = arity.max(1);
+        self
+    }
+
+    /// Set the left padding of texts.
+    pub fn pad_left(mut self, pad_left: Pad) -> Self {
+        self.pad_left = pad_left;
+        self
+    }
+
+    /// Set the right padding of texts.
+    pub fn pad_right(mut self, pad_right: Pad) -> Self {
+        self.pad_right = pad_right;
+        self
+    }
+
+    /// Set the padding of both sides of texts.
+    pub fn pad_full(mut self, pad: Pad) -> Self {
+        self.pad_left = pad.clone();
+        self.pad_right = pad;
+        self
+    }
+
+    /// Set the `metric` similarities are computed with.
+    pub fn metric(mut self, metric: CompareMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Compute similarities with the `CompareMetric::Warped` metric, and the
+    /// given `warp` factor.
+    pub fn warp(self, warp: f32) -> Self {
+        self.metric(CompareMetric::Warped(warp))
+    }
+
+    /// Transform texts with `key_transformer` before comparing them.
+    pub fn key_transformer<NKT>(self, key_transformer: NKT) -> CompareConfig<NKT>
+    where
+        NKT: KeyTransformer,
+    {
+        CompareConfig {
+            arity: self.arity,
+            pad_left: self.pad_left,
+            pad_right: self.pad_right,
+            metric: self.metric,
+            key_transformer,
+        }
+    }
+
+    /// Lowercase texts, after any other key transformation, before comparing
+    /// them.
+    pub fn case_insensitive(self) -> CompareConfig<LinkedKeyTransformer<KT, LowerKeyTransformer>>
+    where
+        KT: Clone,
+    {
+        let key_transformer = self.key_transformer.clone().lower();
+        self.key_transformer(key_transformer)
+    }
+
+    /// Create a `Comparer` with these settings.
+    pub fn comparer(self) -> Comparer<KT> {
+        Comparer::new(self)
+    }
+}
+
+/// The similarity of the texts `a` and `b`, with the settings in `config`.
+/// Use a `Comparer` to compare many pairs without allocating for each.
+/// ```rust
+/// # use ngrammatic::{compare, CompareConfig, Pad};
+/// # fn main() {
+/// let config = CompareConfig::new().pad_full(Pad::None).warp(1.0);
+/// // "ab" of "ab", "bc", "cd" and "bd"
+/// assert_eq!(compare("abcd", "abd", &config), 0.25);
+/// # }
+/// ```
+pub fn compare<KT>(a: &str, b: &str, config: &CompareConfig<KT>) -> f32
+where
+    KT: KeyTransformer,
+{
+    let padding = Padding::new(config);
+    Scratch::default().compare(a, b, config, &padding)
+}
+
+/// Compares pairs of texts with the same settings, reusing its buffers
+/// between comparisons, so that after the first few comparisons it only
+/// allocates for key transformers that don't implement
+/// `KeyTransformer::transform_into`.
+/// ```rust
+/// # use ngrammatic::{CompareConfig, Comparer};
+/// # fn main() {
+/// let mut comparer = Comparer::new(CompareConfig::new().arity(3));
+/// let names = ["tomato", "potato", "tornado"];
+/// let best = names
+///     .iter()
+///     .max_by(|a, b| {
+///         let a = comparer.compare("tomacco", a);
+///         let b = comparer.compare("tomacco", b);
+///         a.partial_cmp(&b).unwrap()
+///     })
+///     .unwrap();
+/// assert_eq!(*best, "tomato");
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Comparer<KT = IdentityKeyTransformer> {
+    config: CompareConfig<KT>,
+    padding: Padding,
+    scratch: Scratch,
+}
+
+impl<KT> Comparer<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Create a `Comparer` with the settings in `config`.
+    pub fn new(config: CompareConfig<KT>) -> Self {
+        Comparer {
+            padding: Padding::new(&config),
+            config,
+            scratch: Scratch::default(),
+        }
+    }
+
+    /// The settings texts are compared with.
+    pub fn config(&self) -> &CompareConfig<KT> {
+        &self.config
+    }
+
+    /// The similarity of the texts `a` and `b`.
+    pub fn compare(&mut self, a: &str, b: &str) -> f32 {
+        self.scratch.compare(a, b, &self.config, &self.padding)
+    }
+}
+
+/// The characters added to each side of texts.
+#[derive(Debug, Clone)]
+struct Padding {
+    left: String,
+    right: String,
+}
+
+impl Padding {
+    fn new<KT>(config: &CompareConfig<KT>) -> Self {
+        Padding {
+            left: config.pad_left.to_string(config.arity - 1),
+            right: config.pad_right.to_string(config.arity - 1),
+        }
+    }
+}
+
+/// Buffers for comparing a pair of texts.
+#[derive(Debug, Clone, Default)]
+struct Scratch {
+    key: String,
+    a: Grams,
+    b: Grams,
+}
+
+impl Scratch {
+    fn compare<KT>(
+        &mut self,
+        a: &str,
+        b: &str,
+        config: &CompareConfig<KT>,
+        padding: &Padding,
+    ) -> f32
+    where
+        KT: KeyTransformer,
+    {
+        let arity = config.arity;
+        self.a.fill(a, config, padding, &mut self.key);
+        self.b.fill(b, config, padding, &mut self.key);
+        let same = self.a.count_shared(&self.b, arity);
+        config
+            .metric
+            .score(same, self.a.starts.len(), self.b.starts.len())
+    }
+}
+
+/// The padded characters of a text, and the offsets its grams start at,
+/// ordered by gram.
+#[derive(Debug, Clone, Default)]
+struct Grams {
+    chars: Vec<char>,
+    starts: Vec<usize>,
+}
+
+impl Grams {
+    fn fill<KT>(
+        &mut self,
+        text: &str,
+        config: &CompareConfig<KT>,
+        padding: &Padding,
+        key: &mut String,
+    ) where
+        KT: KeyTransformer,
+    {
+        key.clear();
+        config.key_transformer.transform_into(text, key);
+        self.chars.clear();
+        self.chars.extend(padding.left.chars());
+        self.chars.extend(key.chars());
+        self.chars.extend(padding.right.chars());
+
+        let arity = config.arity;
+        self.starts.clear();
+        if self.chars.len() >= arity {
+            self.starts.extend(0..=self.chars.len() - arity);
+        }
+        let chars = &self.chars;
+        self.starts
+            .sort_unstable_by(|&i, &j| chars[i..i + arity].cmp(&chars[j..j + arity]));
+    }
+
+    /// The number of grams shared with `other`, counting repeated grams as
+    /// many times as both texts have them.
+    fn count_shared(&self, other: &Grams, arity: usize) -> usize {
+        let (mut i, mut j, mut same) = (0, 0, 0);
+        while i < self.starts.len() && j < other.starts.len() {
+            let ours = &self.chars[self.starts[i]..self.starts[i] + arity];
+            let theirs = &other.chars[other.starts[j]..other.starts[j] + arity];
+            match ours.cmp(theirs) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => {
+                    same += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        same
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NgramBuilder;
+
+    const WORDS: [&str; 12] = [
+        "",
+        "a",
+        "to",
+        "tomato",
+        "tomacco",
+        "potato",
+        "banana",
+        "bandana",
+        "Ωμέγα",
+        "ΟΔΟΣ",
+        "aaaa",
+        "aa aa",
+    ];
+
+    #[test]
+    fn compare_matches_ngram_similarity() {
+        let pads = [Pad::Auto, Pad::None, Pad::Pad("$".to_string())];
+        for arity in 1..=4 {
+            for pad in &pads {
+                for warp in [1.0, 2.0, 3.0] {
+                    let config = CompareConfig::new()
+                        .arity(arity)
+                        .pad_full(pad.clone())
+                        .warp(warp);
+                    let mut comparer = config.clone().comparer();
+                    for a in WORDS {
+                        for b in WORDS {
+                            let ngram = |text: &str| {
+                                NgramBuilder::new(text)
+                                    .arity(arity)
+                                    .pad_full(pad.clone())
+                                    .finish()
+                            };
+                            let expected = ngram(a).similarity_to(&ngram(b), warp);
+                            assert_eq!(compare(a, b, &config), expected, "{:?} {:?}", a, b);
+                            assert_eq!(comparer.compare(a, b), expected, "{:?} {:?}", a, b);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compare_metrics() {
+        let config = CompareConfig::new().arity(2).pad_full(Pad::None);
+        // "ab" of "ab", "bc", "cd", and "ab", "bd"
+        let dice = config.clone().metric(CompareMetric::Dice);
+        assert_eq!(compare("abcd", "abd", &dice), 2.0 / 5.0);
+        let overlap = config.clone().metric(CompareMetric::Overlap);
+        assert_eq!(compare("abcd", "abd", &overlap), 1.0 / 2.0);
+        assert_eq!(compare("abcd", "bcd", &overlap), 1.0);
+        assert_eq!(compare("", "", &overlap), 0.0);
+        assert_eq!(compare("a", "abc", &dice), 0.0);
+        assert_eq!(compare("abcd", "abcd", &config), 1.0);
+    }
+
+    #[test]
+    fn compare_key_transformers() {
+        let config = CompareConfig::new().arity(3).case_insensitive();
+        assert_eq!(compare("TOMATO", "tomato", &config), 1.0);
+        // A final sigma lowercases differently from a medial one
+        assert_eq!(compare("ΟΔΟΣ", "οδος", &config), 1.0);
+        let mut comparer = config
+            .key_transformer(crate::SoundexKeyTransformer)
+            .comparer();
+        assert_eq!(comparer.compare("Robert", "Rupert"), 1.0);
+        assert!(comparer.compare("Robert", "Tomato") < 0.5);
+
+        let mut key = String::from("x");
+        LowerKeyTransformer.transform_into("ABC", &mut key);
+        assert_eq!(key, "xabc");
+    }
+}
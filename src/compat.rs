@@ -0,0 +1,569 @@
+#![deny(missing_docs)]
+
+//! A facade with the interface and semantics of the python
+//! [`ngram`](https://pythonhosted.org/ngram/ngram.html) module's `NGram`
+//! class, for porting python code. It wraps a `Corpus`.
+//!
+//! Like python-ngram, and unlike `Corpus`, an `NGram` is a set of arbitrary
+//! items, which are indexed by the string their `key` function gives. Texts
+//! are padded with `N - 1` `$` characters by default, searches return the
+//! results strictly above the threshold, and queries passed to `search` and
+//! `find` are used as they are, without applying the `key` function.
+//! ```rust
+//! # use ngrammatic::compat::NGram;
+//! # fn main() {
+//! let ngram = NGram::new(["SPAM", "SPAN", "EG"]);
+//! assert_eq!(ngram.search("M", None), vec![(&"SPAM".to_string(), 0.125)]);
+//! assert_eq!(ngram.search("EG", None), vec![(&"EG".to_string(), 1.0)]);
+//! assert_eq!(NGram::compare("spa", "spam"), 0.375);
+//! # }
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{
+    Corpus, CorpusBuilder, Error, IdentityKeyTransformer, Ngram, Pad, SearchOptions, TieBreak,
+};
+
+type KeyFn<T> = Box<dyn Fn(&T) -> String + Send + Sync>;
+
+/// Builder for an `NGram`, taking the keyword arguments of the python
+/// `NGram` constructor.
+/// ```rust
+/// # use ngrammatic::compat::NGramBuilder;
+/// # fn main() -> Result<(), ngrammatic::Error> {
+/// let ngram = NGramBuilder::with_key(|item: &(u32, &str)| item.1.to_lowercase())
+///     .fill(vec![(0, "Spam"), (1, "Ham"), (2, "Eggsy"), (3, "Egggsy")])
+///     .threshold(0.5)
+///     .finish()?;
+/// assert_eq!(ngram.finditem(&(0, "SPAM"), None), Some(&(0, "Spam")));
+/// assert!(ngram.search("SPAM", None).is_empty());
+/// assert_eq!(ngram.search("spam", None), vec![(&(0, "Spam"), 1.0)]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct NGramBuilder<T> {
+    items: Vec<T>,
+    threshold: f32,
+    warp: f32,
+    key: KeyFn<T>,
+    n: usize,
+    pad_len: Option<usize>,
+    pad_char: char,
+}
+
+impl<T> std::fmt::Debug for NGramBuilder<T> {
+    /// Debug format for an `NGramBuilder`. Omits the items and key function.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NGramBuilder")
+            .field("threshold", &self.threshold)
+            .field("warp", &self.warp)
+            .field("n", &self.n)
+            .field("pad_len", &self.pad_len)
+            .field("pad_char", &self.pad_char)
+            .finish()
+    }
+}
+
+impl<T> Default for NGramBuilder<T>
+where
+    T: AsRef<str>,
+{
+    /// The python `NGram` defaults: items are their own keys, the threshold
+    /// is 0.0, the warp 1.0, `N` is 3, and texts are padded with `N - 1` `$`
+    /// characters.
+    fn default() -> Self {
+        NGramBuilder::with_key(|item: &T| item.as_ref().to_string())
+    }
+}
+
+impl<T> NGramBuilder<T> {
+    /// A builder with the python `NGram` defaults, indexing items by the
+    /// string `key` returns for them.
+    pub fn with_key<F>(key: F) -> Self
+    where
+        F: Fn(&T) -> String + Send + Sync + 'static,
+    {
+        NGramBuilder {
+            items: Vec::new(),
+            threshold: 0.0,
+            warp: 1.0,
+            key: Box::new(key),
+            n: 3,
+            pad_len: None,
+            pad_char: '$',
+        }
+    }
+
+    /// Index items by the string `key` returns for them.
+    pub fn key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&T) -> String + Send + Sync + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Add the `items` to the resulting `NGram`.
+    pub fn fill<It>(mut self, items: It) -> Self
+    where
+        It: IntoIterator<Item = T>,
+    {
+        self.items.extend(items);
+        self
+    }
+
+    /// Set the default `threshold` of searches, which results must be
+    /// strictly above.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the `warp` factor of similarities, from 1.0 to 3.0.
+    pub fn warp(mut self, warp: f32) -> Self {
+        self.warp = warp;
+        self
+    }
+
+    /// Set `N`, the number of characters in each gram.
+    pub fn n(mut self, n: usize) -> Self {
+        self.n = n;
+        self
+    }
+
+    /// Set the number of padding characters added to each side of texts,
+    /// which must be less than `N`. Defaults to `N - 1`.
+    pub fn pad_len(mut self, pad_len: usize) -> Self {
+        self.pad_len = Some(pad_len);
+        self
+    }
+
+    /// Set the character texts are padded with.
+    pub fn pad_char(mut self, pad_char: char) -> Self {
+        self.pad_char = pad_char;
+        self
+    }
+
+    /// Yield an `NGram` of the items filled in, or an error if `N` is 0, the
+    /// padding length isn't less than `N`, or the threshold or warp is out of
+    /// range.
+    pub fn finish(self) -> Result<NGram<T>, Error>
+    where
+        T: PartialEq,
+    {
+        if self.n == 0 {
+            return Err(Error::InvalidArity(self.n));
+        }
+        let pad_len = self.pad_len.unwrap_or(self.n - 1);
+        if pad_len >= self.n {
+            return Err(Error::InvalidPadLength {
+                pad_len,
+                arity: self.n,
+            });
+        }
+        SearchOptions::new()
+            .threshold(self.threshold)
+            .warp(self.warp)
+            .validate()?;
+        let padding = self.pad_char.to_string().repeat(pad_len);
+        let pad = if pad_len == 0 {
+            Pad::None
+        } else {
+            Pad::Pad(padding.clone())
+        };
+        let mut ngram = NGram {
+            corpus: CorpusBuilder::default()
+                .arity(self.n)
+                .pad_full(pad)
+                .finish(),
+            items: HashMap::new(),
+            len: 0,
+            key: self.key,
+            threshold: self.threshold,
+            warp: self.warp,
+            n: self.n,
+            padding,
+        };
+        for item in self.items {
+            ngram.add(item);
+        }
+        Ok(ngram)
+    }
+}
+
+impl NGramBuilder<String> {
+    /// The similarity of `s2` to `s1`, with these settings, or 0.0 if it's
+    /// not above the threshold. Like the python `NGram.compare`, only `s1` is
+    /// transformed by the key function.
+    /// ```rust
+    /// # use ngrammatic::compat::NGramBuilder;
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// assert_eq!(NGramBuilder::default().n(2).compare("spam", "pam")?, 0.5);
+    /// assert_eq!(NGramBuilder::default().n(1).compare("ham", "ams")?, 0.5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compare(self, s1: &str, s2: &str) -> Result<f32, Error> {
+        let ngram = self.fill([s1.to_string()]).finish()?;
+        Ok(ngram
+            .search(s2, None)
+            .first()
+            .map_or(0.0, |&(_, similarity)| similarity))
+    }
+}
+
+/// A set of items searchable by the similarity of their keys, with the
+/// interface of the python `NGram` class. Build one with `NGramBuilder`, or
+/// `NGram::new` for strings and the default settings.
+pub struct NGram<T = String> {
+    corpus: Corpus<IdentityKeyTransformer>,
+    /// The items with each key, in the order they were added
+    items: HashMap<String, Vec<T>>,
+    len: usize,
+    key: KeyFn<T>,
+    threshold: f32,
+    warp: f32,
+    n: usize,
+    padding: String,
+}
+
+impl<T> std::fmt::Debug for NGram<T> {
+    /// Debug format for an `NGram`. Omits the items and key function.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NGram")
+            .field("len", &self.len)
+            .field("threshold", &self.threshold)
+            .field("warp", &self.warp)
+            .field("n", &self.n)
+            .field("padding", &self.padding)
+            .finish()
+    }
+}
+
+impl NGram<String> {
+    /// An `NGram` of the strings `items`, with the python defaults.
+    pub fn new<It>(items: It) -> Self
+    where
+        It: IntoIterator,
+        It::Item: Into<String>,
+    {
+        NGramBuilder::default()
+            .fill(items.into_iter().map(Into::into))
+            .finish()
+            .expect("the default settings are valid")
+    }
+
+    /// The similarity of `s2` to `s1`, with the python defaults, or 0.0 if
+    /// they share no grams. Use `NGramBuilder::compare` for other settings.
+    /// ```rust
+    /// # use ngrammatic::compat::NGram;
+    /// # fn main() {
+    /// assert_eq!(NGram::compare("spa", "spam"), 0.375);
+    /// assert_eq!(NGram::compare("ham", "bam"), 0.25);
+    /// # }
+    /// ```
+    pub fn compare(s1: &str, s2: &str) -> f32 {
+        NGramBuilder::default()
+            .compare(s1, s2)
+            .expect("the default settings are valid")
+    }
+}
+
+impl<T> NGram<T>
+where
+    T: PartialEq,
+{
+    /// The similarity of texts with `samegrams` grams in common, out of
+    /// `allgrams` distinct grams, for a given `warp`.
+    /// ```rust
+    /// # use ngrammatic::compat::NGram;
+    /// # fn main() {
+    /// assert_eq!(NGram::<String>::ngram_similarity(5, 10, 1.0), 0.5);
+    /// assert_eq!(NGram::<String>::ngram_similarity(5, 10, 2.0), 0.75);
+    /// # }
+    /// ```
+    pub fn ngram_similarity(samegrams: usize, allgrams: usize, warp: f32) -> f32 {
+        Ngram::similarity(samegrams, allgrams, warp)
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// If the `item` is in the set.
+    pub fn contains(&self, item: &T) -> bool {
+        self.items
+            .get(&self.key(item))
+            .map_or(false, |items| items.contains(item))
+    }
+
+    /// Iterate over the items, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.items.values().flatten()
+    }
+
+    /// The default threshold of searches.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// The key the `item` is indexed by.
+    pub fn key(&self, item: &T) -> String {
+        (self.key)(item)
+    }
+
+    /// The `string` with padding added to each side.
+    pub fn pad(&self, string: &str) -> String {
+        format!("{}{}{}", self.padding, string, self.padding)
+    }
+
+    /// The grams of the padded `string`, in order, including repeats.
+    /// ```rust
+    /// # use ngrammatic::compat::NGram;
+    /// # fn main() {
+    /// let ngram = NGram::new(Vec::<String>::new());
+    /// assert_eq!(ngram.split("SPAM"), ["$$S", "$SP", "SPA", "PAM", "AM$", "M$$"]);
+    /// # }
+    /// ```
+    pub fn split(&self, string: &str) -> Vec<String> {
+        self.ngrams(&self.pad(string))
+    }
+
+    /// The grams of the padded key of the `item`.
+    pub fn splititem(&self, item: &T) -> Vec<String> {
+        self.split(&self.key(item))
+    }
+
+    /// The grams of the `string`, in order, without padding it first.
+    pub fn ngrams(&self, string: &str) -> Vec<String> {
+        let chars: Vec<char> = string.chars().collect();
+        chars
+            .windows(self.n)
+            .map(|gram| gram.iter().collect())
+            .collect()
+    }
+
+    /// Add the `item`, if it's not already in the set. Returns whether it
+    /// was added.
+    pub fn add(&mut self, item: T) -> bool {
+        let key = self.key(&item);
+        let items = self.items.entry(key.clone()).or_default();
+        if items.contains(&item) {
+            return false;
+        }
+        if items.is_empty() {
+            self.corpus.add_text(&key);
+        }
+        items.push(item);
+        self.len += 1;
+        true
+    }
+
+    /// Remove the `item`. Returns whether it was in the set, where python's
+    /// `remove` raises a `KeyError` if it wasn't.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let key = self.key(item);
+        let items = match self.items.get_mut(&key) {
+            Some(items) => items,
+            None => return false,
+        };
+        let position = match items.iter().position(|other| other == item) {
+            Some(position) => position,
+            None => return false,
+        };
+        items.remove(position);
+        if items.is_empty() {
+            self.items.remove(&key);
+            self.corpus.remove_text(&key);
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Remove the `item`, if it's in the set.
+    pub fn discard(&mut self, item: &T) {
+        self.remove(item);
+    }
+
+    /// The items whose keys are more similar to the `query` than the
+    /// `threshold`, or the default threshold if `None`, from most similar to
+    /// least. Items with equally similar keys are in the order they were
+    /// added, where python-ngram's order depends on the order of the grams
+    /// the keys share with the query.
+    pub fn search(&self, query: &str, threshold: Option<f32>) -> Vec<(&T, f32)> {
+        let threshold = threshold.unwrap_or(self.threshold);
+        let options = SearchOptions::new()
+            .threshold(0.0)
+            .warp(self.warp)
+            .limit(usize::MAX)
+            .tie_break(TieBreak::InsertionOrder);
+        self.corpus
+            .search_with(query, &options)
+            .expect("the warp was validated")
+            .into_iter()
+            .filter(|result| result.similarity > threshold)
+            .flat_map(|result| {
+                self.items
+                    .get(&result.text)
+                    .into_iter()
+                    .flatten()
+                    .map(move |item| (item, result.similarity))
+            })
+            .collect()
+    }
+
+    /// Search for the key of the `item`, as `search` does.
+    /// ```rust
+    /// # use ngrammatic::compat::NGramBuilder;
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let ngram = NGramBuilder::with_key(|item: &(u32, &str)| item.1.to_string())
+    ///     .fill(vec![(0, "SPAM"), (1, "SPAN"), (2, "EG")])
+    ///     .finish()?;
+    /// assert_eq!(
+    ///     ngram.searchitem(&(2, "SPA"), Some(0.35)),
+    ///     vec![(&(0, "SPAM"), 0.375), (&(1, "SPAN"), 0.375)]
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn searchitem(&self, item: &T, threshold: Option<f32>) -> Vec<(&T, f32)> {
+        self.search(&self.key(item), threshold)
+    }
+
+    /// The item most similar to the `query`, if any is above the
+    /// `threshold`.
+    pub fn find(&self, query: &str, threshold: Option<f32>) -> Option<&T> {
+        self.search(query, threshold).first().map(|&(item, _)| item)
+    }
+
+    /// The item whose key is most similar to the key of the `item`, if any is
+    /// above the `threshold`.
+    pub fn finditem(&self, item: &T, threshold: Option<f32>) -> Option<&T> {
+        self.find(&self.key(item), threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Search results with owned items, for comparisons.
+    fn owned<T: Clone>(results: Vec<(&T, f32)>) -> Vec<(T, f32)> {
+        results
+            .into_iter()
+            .map(|(item, similarity)| (item.clone(), similarity))
+            .collect()
+    }
+
+    #[test]
+    fn compat_python_reference_values() {
+        // The examples in python-ngram's documentation
+        assert_eq!(NGram::compare("spa", "spam"), 0.375);
+        assert_eq!(NGram::compare("ham", "bam"), 0.25);
+        assert_eq!(NGram::compare("spam", "eggs"), 0.0);
+        let builder = || NGramBuilder::default();
+        // Documented with the comment "N=2", but computed with the default
+        assert_eq!(NGram::compare("spam", "pam"), 0.375);
+        assert_eq!(builder().n(2).compare("spam", "pam").unwrap(), 0.5);
+        assert_eq!(builder().n(1).compare("ham", "ams").unwrap(), 0.5);
+
+        let ngram = NGram::new(["SPAM", "SPAN", "EG"]);
+        assert_eq!(
+            owned(ngram.search("SPA", None)),
+            [("SPAM".to_string(), 0.375), ("SPAN".to_string(), 0.375)]
+        );
+        assert_eq!(
+            owned(ngram.search("M", None)),
+            [("SPAM".to_string(), 0.125)]
+        );
+        assert_eq!(owned(ngram.search("EG", None)), [("EG".to_string(), 1.0)]);
+        assert!(ngram.search("SPA", Some(0.375)).is_empty());
+        assert_eq!(ngram.pad("ham"), "$$ham$$");
+        assert_eq!(
+            ngram.split("SPAM"),
+            ["$$S", "$SP", "SPA", "PAM", "AM$", "M$$"]
+        );
+        assert_eq!(ngram.ngrams("SPAM"), ["SPA", "PAM"]);
+    }
+
+    #[test]
+    fn compat_warp_and_padding() {
+        let ngram = NGramBuilder::default()
+            .fill(vec!["SPAM"])
+            .warp(2.0)
+            .finish()
+            .unwrap();
+        // 3 of 8 grams in common, warped: (64 - 25) / 64
+        assert_eq!(ngram.search("SPA", None)[0].1, 39.0 / 64.0);
+
+        let ngram = NGramBuilder::default()
+            .n(2)
+            .pad_len(0)
+            .fill(vec!["ham"])
+            .finish()
+            .unwrap();
+        assert_eq!(ngram.split("ham"), ["ha", "am"]);
+        // "am" of "ha", "am" and "ms"
+        assert_eq!(ngram.search("ams", None)[0].1, 1.0 / 3.0);
+
+        let ngram = NGramBuilder::default()
+            .pad_char('#')
+            .pad_len(1)
+            .finish()
+            .unwrap();
+        assert_eq!(ngram.splititem(&"ab"), ["#ab", "ab#"]);
+
+        let builder = || NGramBuilder::<String>::default();
+        assert_eq!(builder().n(0).finish().unwrap_err(), Error::InvalidArity(0));
+        assert_eq!(
+            builder().n(2).pad_len(2).finish().unwrap_err(),
+            Error::InvalidPadLength {
+                pad_len: 2,
+                arity: 2
+            }
+        );
+        assert_eq!(
+            builder().warp(0.5).finish().unwrap_err(),
+            Error::InvalidWarp(0.5)
+        );
+    }
+
+    #[test]
+    fn compat_set_operations() {
+        let mut ngram = NGramBuilder::with_key(|item: &(u32, &str)| item.1.to_lowercase())
+            .threshold(0.3)
+            .finish()
+            .unwrap();
+        assert!(ngram.add((0, "Spam")));
+        assert!(ngram.add((1, "SPAM")));
+        assert!(!ngram.add((0, "Spam")));
+        assert!(ngram.add((2, "Ham")));
+        assert_eq!(ngram.len(), 3);
+        assert!(ngram.contains(&(1, "SPAM")));
+        assert!(!ngram.contains(&(1, "Spam")));
+
+        // Items with the same key are both found, in the order added
+        assert_eq!(
+            owned(ngram.searchitem(&(9, "sPAm"), None)),
+            [((0, "Spam"), 1.0), ((1, "SPAM"), 1.0)]
+        );
+        assert_eq!(ngram.finditem(&(9, "ham"), None), Some(&(2, "Ham")));
+        assert_eq!(ngram.find("HAM", None), None);
+
+        assert!(ngram.remove(&(0, "Spam")));
+        assert!(!ngram.remove(&(0, "Spam")));
+        assert_eq!(ngram.find("spam", None), Some(&(1, "SPAM")));
+        ngram.discard(&(1, "SPAM"));
+        ngram.discard(&(1, "SPAM"));
+        assert_eq!(ngram.find("spam", None), None);
+        assert_eq!(ngram.iter().collect::<Vec<_>>(), [&(2, "Ham")]);
+        assert_eq!(ngram.len(), 1);
+    }
+}
@@ -0,0 +1,419 @@
+#![deny(missing_docs)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::corpus::{shard_of, Corpus, CorpusBuilder};
+use crate::scoring::ShardedIdf;
+use crate::search::{fan_out, rank};
+use crate::{Error, KeyTransformer, Ngram, Scoring, SearchOptions, SearchResult, ShardedCorpus};
+
+/// Holds a corpus that can be searched and modified from many threads at
+/// once, through a shared reference.
+///
+/// Entries are partitioned by the hash of their key across a number of
+/// shards, each an independent index behind its own lock. Adding or removing
+/// a text only locks the shard its key belongs to, and an n-gram search only
+/// ever holds a single shard's read lock, so searches are never held up for
+/// longer than it takes to update a single shard, and updates of different
+/// shards don't contend at all.
+///
+/// A search visits the shards one at a time, so it sees every update that
+/// completed before it started, but may or may not see updates that happen
+/// while it's running. Searches with weighted `Scoring` are the exception:
+/// they hold every shard's read lock at once, to weigh grams by their
+/// document frequencies over the whole corpus.
+/// ```rust
+/// # use std::sync::Arc;
+/// # use std::thread;
+/// # use ngrammatic::CorpusBuilder;
+/// # fn main() {
+/// let corpus = Arc::new(
+///     CorpusBuilder::default()
+///         .fill(vec!["tomato", "potato"])
+///         .finish_concurrent(4),
+/// );
+/// let writer = {
+///     let corpus = Arc::clone(&corpus);
+///     thread::spawn(move || corpus.add_text("tornado"))
+/// };
+/// let results = corpus.search("tomacco", 0.40, 10);
+/// assert_eq!(results[0].text, "tomato");
+/// writer.join().unwrap();
+/// assert_eq!(corpus.len(), 3);
+/// # }
+/// ```
+pub struct ConcurrentCorpus<KT>
+where
+    KT: KeyTransformer,
+{
+    /// An empty corpus with the shards' settings, used to prepare queries
+    template: Corpus<KT>,
+    shards: Vec<RwLock<Corpus<KT>>>,
+    next_order: AtomicUsize,
+}
+
+impl<KT> std::fmt::Debug for ConcurrentCorpus<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Debug format for a `ConcurrentCorpus`. Only shows the number of
+    /// shards, as showing their contents would mean locking them all.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ConcurrentCorpus")
+            .field("shards", &self.shards.len())
+            .finish()
+    }
+}
+
+impl<KT> ConcurrentCorpus<KT>
+where
+    KT: KeyTransformer + Send + Sync,
+{
+    /// Partition the entries of an existing `corpus` across `shards` (at
+    /// least one) shards, keeping their insertion order, frequencies and
+    /// payloads.
+    pub fn from_corpus(corpus: Corpus<KT>, shards: usize) -> Self
+    where
+        KT: Clone,
+    {
+        ShardedCorpus::from_corpus(corpus, shards).into()
+    }
+
+    /// Lock the shard the already transformed `key` belongs to for reading.
+    fn read(&self, key: &str) -> RwLockReadGuard<'_, Corpus<KT>> {
+        self.shards[shard_of(key, self.shards.len())]
+            .read()
+            .expect("corpus shard lock poisoned")
+    }
+
+    /// Lock the shard the already transformed `key` belongs to for writing.
+    fn write(&self, key: &str) -> RwLockWriteGuard<'_, Corpus<KT>> {
+        self.shards[shard_of(key, self.shards.len())]
+            .write()
+            .expect("corpus shard lock poisoned")
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ConcurrentCorpus`.
+    pub fn add_text(&self, text: &str) {
+        // Can't fail, as keys without grams are allowed
+        let _ = self.add_key(self.template.transform_key(text), true);
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ConcurrentCorpus`, unless it's too short to produce any grams.
+    pub fn try_add_text(&self, text: &str) -> Result<(), Error> {
+        self.add_key(self.template.transform_key(text), false)
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ConcurrentCorpus`, along with a `payload` that can be looked up with
+    /// `payload`.
+    pub fn add_text_with_payload(&self, text: &str, payload: &str) {
+        let key = self.template.transform_key(text);
+        let mut shard = self.write(&key);
+        // Can't fail, as keys without grams are allowed
+        let _ = shard.add_key(key, true, || Some(self.take_order()));
+        shard.set_payload(text, payload.to_string());
+    }
+
+    fn add_key(&self, key: String, allow_empty: bool) -> Result<(), Error> {
+        self.write(&key)
+            .add_key(key, allow_empty, || Some(self.take_order()))
+    }
+
+    /// Number a new entry, after all of those added before it.
+    fn take_order(&self) -> usize {
+        self.next_order.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Remove the entry for the supplied `text`, along with its payload.
+    /// Returns whether there was such an entry.
+    pub fn remove_text(&self, text: &str) -> bool {
+        let key = self.template.transform_key(text);
+        self.write(&key).remove_key(&key)
+    }
+
+    /// Determines whether an exact match exists for the supplied `text` in
+    /// the `ConcurrentCorpus`, after processing it with the key transformer.
+    pub fn key(&self, text: &str) -> Option<String> {
+        self.read(&self.template.transform_key(text)).key(text)
+    }
+
+    /// Look up the payload of the entry for `text`, if it was added with
+    /// one.
+    pub fn payload(&self, text: &str) -> Option<String> {
+        self.read(&self.template.transform_key(text))
+            .payload(text)
+            .map(str::to_string)
+    }
+
+    /// The number of entries in the corpus.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().expect("corpus shard lock poisoned").len())
+            .sum()
+    }
+
+    /// If the corpus is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of shards the entries are partitioned across.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Choose the stop grams of each shard again, from the current document
+    /// frequencies of its grams, like `Corpus::update_stop_grams`. Locks one
+    /// shard at a time.
+    pub fn update_stop_grams(&self) {
+        for shard in &self.shards {
+            shard
+                .write()
+                .expect("corpus shard lock poisoned")
+                .update_stop_grams();
+        }
+    }
+
+    /// Perform a fuzzy search of the `ConcurrentCorpus` for `Ngrams` above
+    /// some `threshold` of similarity to the supplied `text`. Returns up to
+    /// `limit` results, sorted by highest similarity to lowest.
+    pub fn search(&self, text: &str, threshold: f32, limit: usize) -> Vec<SearchResult> {
+        let options = SearchOptions::new().threshold(threshold).limit(limit);
+        self.search_unchecked(text, &options)
+    }
+
+    /// Perform a fuzzy search of the `ConcurrentCorpus` for `Ngrams` similar
+    /// to the supplied `text`, as configured by the `options`, just like
+    /// `Corpus::search_with`. A parallel search visits the shards in
+    /// parallel.
+    pub fn search_with(
+        &self,
+        text: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>, Error> {
+        options.validate()?;
+        Ok(self.search_unchecked(text, options))
+    }
+
+    fn search_unchecked(&self, text: &str, options: &SearchOptions) -> Vec<SearchResult> {
+        let item = self
+            .template
+            .build_ngram(&self.template.transform_key(text));
+        if options.scoring != Scoring::Ngram {
+            return self.search_weighted(&item, options);
+        }
+        let results = fan_out(&self.shards, options, |shard| {
+            let shard = shard.read().expect("corpus shard lock poisoned");
+            let idf = |gram: &str| shard.inverse_document_frequency(gram);
+            shard.ranked_candidates(&item, options, &idf)
+        });
+        rank(&item.text, results, options)
+    }
+
+    /// Perform a search with weighted `Scoring`, which weighs grams by their
+    /// document frequencies over every shard, so holds all their read locks
+    /// at once. Writers only ever hold a single shard's lock, so taking them
+    /// all can't deadlock.
+    fn search_weighted(&self, item: &Ngram, options: &SearchOptions) -> Vec<SearchResult> {
+        let shards: Vec<RwLockReadGuard<'_, Corpus<KT>>> = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().expect("corpus shard lock poisoned"))
+            .collect();
+        let idf = ShardedIdf::new(
+            shards.iter().map(|shard| &**shard).collect(),
+            item,
+            options.scoring,
+        );
+        let idf = |gram: &str| idf.weight(gram);
+        let results = fan_out(&shards, options, |shard| {
+            shard.ranked_candidates(item, options, &idf)
+        });
+        rank(&item.text, results, options)
+    }
+}
+
+impl<KT> CorpusBuilder<KT>
+where
+    KT: KeyTransformer + Clone + Send + Sync,
+{
+    /// Yield a `ConcurrentCorpus` instance with all the properties set with
+    /// this builder, partitioned into the given number of `shards` (at least
+    /// one). More shards mean less contention between threads, but more work
+    /// per search.
+    pub fn finish_concurrent(self, shards: usize) -> ConcurrentCorpus<KT> {
+        self.finish_sharded(shards).into()
+    }
+}
+
+impl<KT> From<ShardedCorpus<KT>> for ConcurrentCorpus<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Put each shard of a `ShardedCorpus` behind its own lock.
+    fn from(sharded: ShardedCorpus<KT>) -> Self {
+        ConcurrentCorpus {
+            template: sharded.template,
+            shards: sharded.shards.into_iter().map(RwLock::new).collect(),
+            next_order: AtomicUsize::new(sharded.next_order),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    use crate::{Pad, TieBreak};
+
+    #[test]
+    fn concurrent_corpus_matches_corpus() {
+        let words = [
+            "tomato", "potato", "tornado", "Tomato", "carrot", "tomatoes",
+        ];
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(words)
+            .finish();
+        let concurrent = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(words)
+            .finish_concurrent(3);
+        assert_eq!(concurrent.len(), corpus.len());
+        for tie_break in [
+            TieBreak::Text,
+            TieBreak::InsertionOrder,
+            TieBreak::Frequency,
+        ] {
+            let options = SearchOptions::new().tie_break(tie_break).offset(1).limit(3);
+            assert_eq!(
+                concurrent.search_with("tomacco", &options),
+                corpus.search_with("tomacco", &options)
+            );
+        }
+    }
+
+    #[test]
+    fn concurrent_corpus_from_corpus() {
+        let mut corpus = CorpusBuilder::default()
+            .fill(["tomato", "potato", "tornado", "carrot"])
+            .finish();
+        corpus.add_text_with_payload("tomatoes", "red");
+        corpus.add_text("tomato");
+        let expected = corpus.search_with(
+            "tomacco",
+            &SearchOptions::new().tie_break(TieBreak::Frequency),
+        );
+        let concurrent = ConcurrentCorpus::from_corpus(corpus, 3);
+        assert_eq!(concurrent.shard_count(), 3);
+        assert_eq!(concurrent.len(), 5);
+        assert_eq!(concurrent.payload("tomatoes"), Some("red".to_string()));
+        assert_eq!(
+            concurrent.search_with(
+                "tomacco",
+                &SearchOptions::new().tie_break(TieBreak::Frequency)
+            ),
+            expected
+        );
+        concurrent.add_text("tomatillo");
+        assert_eq!(concurrent.len(), 6);
+    }
+
+    #[test]
+    fn concurrent_corpus_add_remove() {
+        let corpus = CorpusBuilder::default().finish_concurrent(2);
+        assert!(corpus.is_empty());
+        corpus.add_text_with_payload("tomato", "red");
+        corpus.add_text("potato");
+        assert_eq!(corpus.payload("tomato"), Some("red".to_string()));
+        assert_eq!(corpus.key("potato"), Some("potato".to_string()));
+        assert!(corpus.remove_text("tomato"));
+        assert!(!corpus.remove_text("tomato"));
+        assert_eq!(corpus.payload("tomato"), None);
+        assert!(corpus.search("tomato", 0.9, 10).is_empty());
+        assert_eq!(corpus.len(), 1);
+    }
+
+    #[test]
+    fn concurrent_corpus_numbers_inserted_entries() {
+        let corpus = CorpusBuilder::default()
+            .arity(3)
+            .pad_full(Pad::None)
+            .finish_concurrent(2);
+        corpus.add_text("tomato");
+        // Neither failed nor repeated additions take a position
+        assert!(corpus.try_add_text("to").is_err());
+        corpus.add_text("tomato");
+        assert_eq!(corpus.next_order.load(Ordering::Relaxed), 1);
+        corpus.add_text_with_payload("potato", "brown");
+        assert_eq!(corpus.next_order.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn concurrent_corpus_stress() {
+        const WRITERS: usize = 4;
+        const READERS: usize = 4;
+        const WORDS: usize = 200;
+        let word = |writer: usize, i: usize| format!("w{}x{}", writer, i);
+        let corpus = Arc::new(CorpusBuilder::default().finish_concurrent(8));
+
+        let writers: Vec<_> = (0..WRITERS)
+            .map(|writer| {
+                let corpus = Arc::clone(&corpus);
+                thread::spawn(move || {
+                    for i in 0..WORDS {
+                        corpus.add_text(&word(writer, i));
+                        // Remove every other word shortly after adding it
+                        if i % 2 == 1 {
+                            assert!(corpus.remove_text(&word(writer, i - 1)));
+                        }
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..READERS)
+            .map(|reader| {
+                let corpus = Arc::clone(&corpus);
+                thread::spawn(move || {
+                    for i in 0..WORDS {
+                        let query = word(reader % WRITERS, i);
+                        let results = corpus.search(&query, 0.0, 20);
+                        assert!(results.len() <= 20);
+                        for pair in results.windows(2) {
+                            assert!(pair[0] >= pair[1]);
+                        }
+                        for result in &results {
+                            assert!(result.text.starts_with('w'));
+                            assert!((0.0..=1.0).contains(&result.similarity));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(corpus.len(), WRITERS * WORDS / 2);
+        for writer in 0..WRITERS {
+            for i in 0..WORDS {
+                let found = corpus
+                    .search(&word(writer, i), 1.0, 1)
+                    .first()
+                    .map(|result| result.text.clone());
+                if i % 2 == 1 {
+                    assert_eq!(found, Some(word(writer, i)));
+                } else {
+                    assert_eq!(found, None);
+                }
+            }
+        }
+    }
+}
@@ -2,24 +2,35 @@
 
 use std::collections::{HashMap, HashSet};
 use std::f32;
+use std::io::{BufRead, Write};
+use std::mem::size_of;
 
 use string_interner::{DefaultBackend, DefaultSymbol, StringInterner};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "rayon")]
+use smol_str::SmolStr;
 
-use crate::ngram::{Ngram, NgramBuilder};
+use crate::ngram::{check_settings, resolve_arities, Ngram, NgramBuilder, SkipGrams};
+use crate::persist::{read_corpus, write_corpus, Settings};
+use crate::postings::PostingList;
+use crate::prune::StopGrams;
+use crate::reader::{read_delimited, read_texts, DelimitedOptions, ReadOptions};
+use crate::scoring::{inverse_document_frequency, Idf};
+use crate::search::{rank, EntryStats, Ranked};
+use crate::stats::{
+    hash_map_bytes, ngram_bytes, string_map_bytes, top_grams, CorpusStats, HeapStats, PostingStats,
+    TOP_GRAMS,
+};
 use crate::{
-    IdentityKeyTransformer, KeyTransformer, LinkedKeyTransformer, LowerKeyTransformer, Pad,
-    SearchResult,
+    Error, IdentityKeyTransformer, KeyTransformer, LinkedKeyTransformer, LowerKeyTransformer, Pad,
+    Rerank, SearchOptions, SearchResult,
 };
 
 // Import traits for rayon parallelization
 #[cfg(feature = "rayon")]
-use rayon::{
-    iter::IntoParallelIterator, iter::IntoParallelRefIterator, iter::ParallelIterator,
-    slice::ParallelSliceMut,
-};
+use rayon::{iter::IntoParallelIterator, iter::IntoParallelRefIterator, iter::ParallelIterator};
 
 /// Holds a corpus of words and their ngrams, allowing fuzzy matches of
 /// candidate strings against known strings in the corpus.
@@ -29,11 +40,19 @@ where
     KT: KeyTransformer,
 {
     arity: usize,
+    arities: Vec<(usize, f32)>,
+    position_tolerance: Option<usize>,
+    skip_grams: SkipGrams,
+    stop_grams: StopGrams,
     pad_left: Pad,
     pad_right: Pad,
     strings: StringInterner<DefaultBackend>,
     ngrams: HashMap<DefaultSymbol, Ngram>,
-    gram_to_words: HashMap<DefaultSymbol, Vec<DefaultSymbol>>,
+    gram_to_words: HashMap<DefaultSymbol, PostingList>,
+    stop_gram_frequencies: HashMap<DefaultSymbol, usize>,
+    entries: HashMap<DefaultSymbol, EntryStats>,
+    next_order: usize,
+    payloads: HashMap<DefaultSymbol, String>,
     key_transformer: KT,
 }
 
@@ -47,6 +66,10 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         writeln!(f, "Corpus {{")?;
         writeln!(f, "  arity: {:?},", self.arity)?;
+        writeln!(f, "  arities: {:?},", self.arities)?;
+        writeln!(f, "  position_tolerance: {:?},", self.position_tolerance)?;
+        writeln!(f, "  skip_grams: {:?},", self.skip_grams)?;
+        writeln!(f, "  stop_grams: {:?},", self.stop_grams)?;
         writeln!(f, "  pad_left: {:?},", self.pad_left)?;
         writeln!(f, "  pad_right: {:?},", self.pad_right)?;
         writeln!(f, "  ngrams: {:?},", self.ngrams)?;
@@ -74,16 +97,41 @@ where
     /// # }
     /// ```
     #[allow(dead_code)]
-    #[allow(clippy::unwrap_or_default)]
     pub fn add_ngram(&mut self, ngram: Ngram) {
+        self.insert_ngram(ngram, None);
+    }
+
+    /// Add the supplied `ngram` to the `Corpus`, as the entry added in
+    /// position `order` when breaking ties by insertion order, or after all
+    /// the existing entries if `None`.
+    pub(crate) fn insert_ngram(&mut self, ngram: Ngram, order: Option<usize>) {
         let word_sym = self.strings.get_or_intern(ngram.text.as_str());
         self.ngrams.insert(word_sym, ngram.clone());
+        let order = order.unwrap_or(self.next_order);
+        self.next_order = self.next_order.max(order + 1);
+        self.entries
+            .entry(word_sym)
+            .or_insert(EntryStats {
+                order,
+                frequency: 0,
+            })
+            .frequency += 1;
         for gram_str in ngram.grams.keys() {
             let gram_sym = self.strings.get_or_intern(gram_str.as_str());
+            if let Some(frequency) = self.stop_gram_frequencies.get_mut(&gram_sym) {
+                *frequency += 1;
+                let stored = self
+                    .gram_to_words
+                    .get(&gram_sym)
+                    .map_or(0, PostingList::len);
+                if self.stop_grams.prune_postings && stored >= self.stop_grams.cap {
+                    continue;
+                }
+            }
             self.gram_to_words
                 .entry(gram_sym)
-                .or_insert_with(Vec::new)
-                .push(word_sym);
+                .or_default()
+                .insert(word_sym);
         }
     }
 
@@ -104,17 +152,255 @@ where
     /// ```
     #[allow(dead_code)]
     pub fn add_text(&mut self, text: &str) {
-        let arity = self.arity;
-        let pad_left = self.pad_left.clone();
-        let pad_right = self.pad_right.clone();
         let new_key = self.key_transformer.transform(text);
-        self.add_ngram(
-            NgramBuilder::new(&new_key)
-                .arity(arity)
-                .pad_left(pad_left)
-                .pad_right(pad_right)
-                .finish(),
-        );
+        // Can't fail, as keys without grams are allowed
+        let _ = self.add_key(new_key, true, || None);
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `Corpus`, along with a `payload` that can be looked up with `payload`.
+    /// Adding the same text again replaces its payload.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let mut corpus = CorpusBuilder::default().case_insensitive().finish();
+    /// corpus.add_text_with_payload("Tomato", "Q20638126");
+    /// let results = corpus.search("tomatoes", 0.40, 10);
+    /// assert_eq!(corpus.payload(&results[0].text), Some("Q20638126"));
+    /// # }
+    /// ```
+    pub fn add_text_with_payload(&mut self, text: &str, payload: &str) {
+        self.add_text(text);
+        self.set_payload(text, payload.to_string());
+    }
+
+    /// Attach a `payload` to the entry for `text`.
+    pub(crate) fn set_payload(&mut self, text: &str, payload: String) {
+        let key = self.key_transformer.transform(text);
+        let sym = self.strings.get_or_intern(key);
+        self.payloads.insert(sym, payload);
+    }
+
+    /// Look up the payload of the entry for `text`, after processing it with
+    /// the `Corpus`'s `key_trans` function, if it was added with one.
+    pub fn payload(&self, text: &str) -> Option<&str> {
+        let key = self.key_transformer.transform(text);
+        self.strings
+            .get(key.as_str())
+            .and_then(|sym| self.payloads.get(&sym))
+            .map(String::as_str)
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `Corpus`, unless it's too short to produce any grams.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, Error, Pad};
+    /// # fn main() {
+    /// let mut corpus = CorpusBuilder::default().arity(3).pad_full(Pad::None).finish();
+    /// assert!(corpus.try_add_text("tomato").is_ok());
+    /// assert_eq!(
+    ///     corpus.try_add_text("to"),
+    ///     Err(Error::EmptyGrams { text: "to".to_string() })
+    /// );
+    /// assert!(corpus.key("to").is_none());
+    /// # }
+    /// ```
+    pub fn try_add_text(&mut self, text: &str) -> Result<(), Error> {
+        let new_key = self.key_transformer.transform(text);
+        self.add_key(new_key, false, || None)
+    }
+
+    /// Add an entry for the already transformed `key`, generating its `Ngram`
+    /// unless the key was added before. Unless `allow_empty` is set, fails
+    /// instead if the key produces no grams. New entries take the position
+    /// returned by `order` when breaking ties by insertion order, if any,
+    /// which is only called when a new entry is actually inserted.
+    pub(crate) fn add_key(
+        &mut self,
+        key: String,
+        allow_empty: bool,
+        order: impl FnOnce() -> Option<usize>,
+    ) -> Result<(), Error> {
+        let existing = self
+            .strings
+            .get(key.as_str())
+            .filter(|sym| self.ngrams.contains_key(sym));
+        match existing {
+            Some(sym) => {
+                if !allow_empty && self.ngrams[&sym].is_empty() {
+                    return Err(Error::EmptyGrams { text: key });
+                }
+                if let Some(stats) = self.entries.get_mut(&sym) {
+                    stats.frequency += 1;
+                }
+            }
+            None => {
+                let ngram = self.build_ngram(&key);
+                if !allow_empty && ngram.is_empty() {
+                    return Err(Error::EmptyGrams { text: key });
+                }
+                self.insert_ngram(ngram, order());
+            }
+        }
+        Ok(())
+    }
+
+    /// Add entries for the already transformed `keys`, in order, as
+    /// `add_key` does, but build the posting lists of their grams in bulk
+    /// once they're all added rather than one entry at a time. Only for a
+    /// corpus being built, whose stop grams haven't been chosen yet. Stops at
+    /// the first key that fails to be added.
+    pub(crate) fn add_keys<It>(&mut self, keys: It, allow_empty: bool) -> Result<(), Error>
+    where
+        It: IntoIterator<Item = String>,
+    {
+        debug_assert!(self.stop_gram_frequencies.is_empty());
+        let mut postings: HashMap<DefaultSymbol, Vec<DefaultSymbol>> = HashMap::new();
+        let mut result = Ok(());
+        for key in keys {
+            let existing = self
+                .strings
+                .get(key.as_str())
+                .filter(|sym| self.ngrams.contains_key(sym));
+            if let Some(sym) = existing {
+                if !allow_empty && self.ngrams[&sym].is_empty() {
+                    result = Err(Error::EmptyGrams { text: key });
+                    break;
+                }
+                if let Some(stats) = self.entries.get_mut(&sym) {
+                    stats.frequency += 1;
+                }
+                continue;
+            }
+            let ngram = self.build_ngram(&key);
+            if !allow_empty && ngram.is_empty() {
+                result = Err(Error::EmptyGrams { text: key });
+                break;
+            }
+            let word_sym = self.strings.get_or_intern(key.as_str());
+            for gram_str in ngram.grams.keys() {
+                let gram_sym = self.strings.get_or_intern(gram_str.as_str());
+                postings.entry(gram_sym).or_default().push(word_sym);
+            }
+            self.ngrams.insert(word_sym, ngram);
+            self.entries.insert(
+                word_sym,
+                EntryStats {
+                    order: self.next_order,
+                    frequency: 1,
+                },
+            );
+            self.next_order += 1;
+        }
+        for (gram_sym, word_syms) in postings {
+            self.gram_to_words
+                .entry(gram_sym)
+                .or_default()
+                .extend(word_syms);
+        }
+        result
+    }
+
+    /// The keys of the interned `texts`, in order.
+    fn transform_texts(&self, texts: &[DefaultSymbol]) -> Vec<String> {
+        texts
+            .iter()
+            .filter_map(|sym| self.strings.resolve(*sym))
+            .map(|text| self.key_transformer.transform(text))
+            .collect()
+    }
+
+    /// Attach the `payloads` of the interned `texts` to their entries, in the
+    /// order the texts were filled in.
+    fn set_payloads(
+        &mut self,
+        texts: &[DefaultSymbol],
+        mut payloads: HashMap<DefaultSymbol, String>,
+    ) {
+        for sym in texts {
+            if let Some(payload) = payloads.remove(sym) {
+                if let Some(owned) = self.strings.resolve(*sym).map(str::to_owned) {
+                    self.set_payload(&owned, payload);
+                }
+            }
+        }
+    }
+
+    /// Generate an `Ngram` for an already transformed `key`, with the
+    /// `Corpus`'s arity and padding settings.
+    pub(crate) fn build_ngram(&self, key: &str) -> Ngram {
+        let mut builder = NgramBuilder::new(key)
+            .arity(self.arity)
+            .pad_left(self.pad_left.clone())
+            .pad_right(self.pad_right.clone())
+            .skip_grams(self.skip_grams);
+        if let Some(max_distance) = self.position_tolerance {
+            builder = builder.positional(max_distance);
+        }
+        if self.arities.is_empty() {
+            builder.finish()
+        } else {
+            builder
+                .weighted_arities(self.arities.iter().copied())
+                .finish()
+        }
+    }
+
+    /// Remove the entry for the supplied `text`, after processing it with the
+    /// `Corpus`'s `key_trans` function, along with its payload. Returns
+    /// whether there was such an entry.
+    ///
+    /// The strings of removed entries and their grams stay interned, so
+    /// their memory isn't reclaimed.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let mut corpus = CorpusBuilder::default()
+    ///     .fill(vec!["tomato", "potato"])
+    ///     .finish();
+    /// assert!(corpus.remove_text("tomato"));
+    /// assert!(!corpus.remove_text("tomato"));
+    /// assert_eq!(corpus.search("tomacco", 0.40, 10).len(), 0);
+    /// assert_eq!(corpus.len(), 1);
+    /// # }
+    /// ```
+    pub fn remove_text(&mut self, text: &str) -> bool {
+        let key = self.key_transformer.transform(text);
+        self.remove_key(&key)
+    }
+
+    /// Remove the entry for the already transformed `key`. Returns whether
+    /// there was such an entry.
+    pub(crate) fn remove_key(&mut self, key: &str) -> bool {
+        let word_sym = match self.strings.get(key) {
+            Some(word_sym) => word_sym,
+            None => return false,
+        };
+        let ngram = match self.ngrams.remove(&word_sym) {
+            Some(ngram) => ngram,
+            None => return false,
+        };
+        self.entries.remove(&word_sym);
+        self.payloads.remove(&word_sym);
+        for gram_str in ngram.grams.keys() {
+            if let Some(gram_sym) = self.strings.get(gram_str.as_str()) {
+                if let Some(frequency) = self.stop_gram_frequencies.get_mut(&gram_sym) {
+                    *frequency = frequency.saturating_sub(1);
+                }
+                if let Some(word_syms) = self.gram_to_words.get_mut(&gram_sym) {
+                    word_syms.remove(word_sym);
+                    if word_syms.is_empty() {
+                        self.gram_to_words.remove(&gram_sym);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// The number of entries in the corpus.
+    pub fn len(&self) -> usize {
+        self.ngrams.len()
     }
 
     /// If the corpus is empty.
@@ -123,6 +409,276 @@ where
         self.ngrams.is_empty()
     }
 
+    /// Summarize the size of the `Corpus` index and the shape of its grams:
+    /// the distribution of posting list lengths, the most frequent grams,
+    /// and the estimated heap memory used by each structure.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill(vec!["tomato", "potato", "carrot"])
+    ///     .finish();
+    /// let stats = corpus.stats();
+    /// assert_eq!(stats.entries, 3);
+    /// assert_eq!(stats.postings.max, 2);
+    /// assert_eq!(stats.top_grams[0], ("at".to_string(), 2));
+    /// println!("{}", stats);
+    /// # }
+    /// ```
+    pub fn stats(&self) -> CorpusStats {
+        let lengths: Vec<usize> = self.gram_to_words.values().map(PostingList::len).collect();
+        let gram_count: usize = self.ngrams.values().map(Ngram::count_grams).sum();
+        let mean_grams_per_entry = if self.ngrams.is_empty() {
+            0.0
+        } else {
+            gram_count as f32 / self.ngrams.len() as f32
+        };
+        let string_bytes: usize = self.strings.iter().map(|(_, text)| text.len()).sum();
+        let heap = HeapStats {
+            strings: string_bytes
+                + self.strings.len() * (size_of::<usize>() + size_of::<DefaultSymbol>() + 1),
+            ngrams: hash_map_bytes::<DefaultSymbol, Ngram>(self.ngrams.capacity())
+                + self.ngrams.values().map(ngram_bytes).sum::<usize>(),
+            postings: hash_map_bytes::<DefaultSymbol, PostingList>(self.gram_to_words.capacity())
+                + self
+                    .gram_to_words
+                    .values()
+                    .map(PostingList::heap_bytes)
+                    .sum::<usize>()
+                + hash_map_bytes::<DefaultSymbol, usize>(self.stop_gram_frequencies.capacity()),
+            entries: hash_map_bytes::<DefaultSymbol, EntryStats>(self.entries.capacity()),
+            payloads: string_map_bytes(&self.payloads),
+        };
+        CorpusStats {
+            entries: self.ngrams.len(),
+            distinct_grams: self.gram_frequencies().count(),
+            stop_grams: self.stop_gram_frequencies.len(),
+            postings: PostingStats::from_lengths(lengths),
+            top_grams: top_grams(self.gram_frequencies(), TOP_GRAMS),
+            mean_grams_per_entry,
+            heap,
+        }
+    }
+
+    /// Iterate over the distinct grams of the entries in the `Corpus`, each
+    /// with its document frequency, the number of entries it occurs in. The
+    /// order is unspecified.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default().fill(vec!["tomato", "potato"]).finish();
+    /// let mut common: Vec<&str> = corpus
+    ///     .gram_frequencies()
+    ///     .filter(|&(_, frequency)| frequency == corpus.len())
+    ///     .map(|(gram, _)| gram)
+    ///     .collect();
+    /// common.sort();
+    /// assert_eq!(common, ["at", "o ", "to"]);
+    /// # }
+    /// ```
+    pub fn gram_frequencies(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        // Stop grams may have no postings stored, but their frequency is kept
+        self.gram_to_words
+            .keys()
+            .filter(move |gram_sym| !self.stop_gram_frequencies.contains_key(gram_sym))
+            .chain(self.stop_gram_frequencies.keys())
+            .filter_map(move |gram_sym| {
+                let frequency = self.gram_frequency(*gram_sym);
+                match self.strings.resolve(*gram_sym) {
+                    Some(gram) if frequency > 0 => Some((gram, frequency)),
+                    _ => None,
+                }
+            })
+    }
+
+    /// The document frequency of the `gram`: the number of entries in the
+    /// `Corpus` it occurs in.
+    pub fn document_frequency(&self, gram: &str) -> usize {
+        self.strings
+            .get(gram)
+            .map_or(0, |gram_sym| self.gram_frequency(gram_sym))
+    }
+
+    /// The document frequency of the interned gram `gram_sym`.
+    fn gram_frequency(&self, gram_sym: DefaultSymbol) -> usize {
+        match self.stop_gram_frequencies.get(&gram_sym) {
+            Some(frequency) => *frequency,
+            None => self
+                .gram_to_words
+                .get(&gram_sym)
+                .map_or(0, PostingList::len),
+        }
+    }
+
+    /// The number of stop grams of the `Corpus`, as chosen by its
+    /// `StopGrams` settings.
+    pub fn stop_gram_count(&self) -> usize {
+        self.stop_gram_frequencies.len()
+    }
+
+    /// Choose the stop grams of the `Corpus` again, as its `StopGrams`
+    /// settings direct, from the current document frequencies of its grams.
+    /// Stop grams are only chosen when the `Corpus` is finished or loaded,
+    /// so this is worth calling after adding or removing many entries.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, StopGrams};
+    /// # fn main() {
+    /// let mut corpus = CorpusBuilder::default()
+    ///     .stop_grams(StopGrams::new().most_frequent(1))
+    ///     .finish();
+    /// corpus.add_text("tomato");
+    /// corpus.add_text("potato");
+    /// assert_eq!(corpus.stop_gram_count(), 0);
+    /// corpus.update_stop_grams();
+    /// assert_eq!(corpus.stop_gram_count(), 1);
+    /// # }
+    /// ```
+    pub fn update_stop_grams(&mut self) {
+        let settings = self.stop_grams;
+        if settings == StopGrams::default() && self.stop_gram_frequencies.is_empty() {
+            return;
+        }
+        let frequencies: HashMap<DefaultSymbol, usize> = self
+            .gram_to_words
+            .keys()
+            .chain(self.stop_gram_frequencies.keys())
+            .map(|gram_sym| (*gram_sym, self.gram_frequency(*gram_sym)))
+            .filter(|(_, frequency)| *frequency > 0)
+            .collect();
+        let stop_grams = settings.choose(&frequencies, self.ngrams.len());
+
+        // Former stop grams may have had their postings pruned, which have
+        // to be gathered from the entries again
+        let restored: HashSet<DefaultSymbol> = self
+            .stop_gram_frequencies
+            .iter()
+            .filter(|(gram_sym, frequency)| {
+                let stored = self.gram_to_words.get(gram_sym).map_or(0, PostingList::len);
+                !stop_grams.contains(gram_sym) && stored < **frequency
+            })
+            .map(|(gram_sym, _)| *gram_sym)
+            .collect();
+        if !restored.is_empty() {
+            let mut postings: HashMap<DefaultSymbol, Vec<DefaultSymbol>> = HashMap::new();
+            for (word_sym, ngram) in &self.ngrams {
+                for gram_str in ngram.grams.keys() {
+                    let gram_sym = match self.strings.get(gram_str.as_str()) {
+                        Some(gram_sym) if restored.contains(&gram_sym) => gram_sym,
+                        _ => continue,
+                    };
+                    postings.entry(gram_sym).or_default().push(*word_sym);
+                }
+            }
+            for gram_sym in &restored {
+                self.gram_to_words.remove(gram_sym);
+            }
+            for (gram_sym, word_syms) in postings {
+                self.gram_to_words
+                    .insert(gram_sym, word_syms.into_iter().collect());
+            }
+        }
+
+        if settings.prune_postings {
+            for gram_sym in &stop_grams {
+                if let Some(word_syms) = self.gram_to_words.get_mut(gram_sym) {
+                    word_syms.truncate(settings.cap);
+                    word_syms.shrink_to_fit();
+                    if word_syms.is_empty() {
+                        self.gram_to_words.remove(gram_sym);
+                    }
+                }
+            }
+        }
+        self.stop_gram_frequencies = stop_grams
+            .into_iter()
+            .map(|gram_sym| (gram_sym, frequencies[&gram_sym]))
+            .collect();
+    }
+
+    /// The inverse document frequency of the `gram`, which weighs it when
+    /// searching with weighted `Scoring`: the fewer entries of the `Corpus`
+    /// it occurs in, the larger it is. Always positive.
+    pub fn inverse_document_frequency(&self, gram: &str) -> f32 {
+        inverse_document_frequency(self.document_frequency(gram), self.ngrams.len())
+    }
+
+    /// Process the supplied `text` with the `Corpus`'s `key_trans` function.
+    pub(crate) fn transform_key(&self, text: &str) -> String {
+        self.key_transformer.transform(text)
+    }
+
+    /// Move the entries of the `Corpus` to `shards` (at least one) new
+    /// corpora with the same settings, each taking the entries whose keys
+    /// `shard_of` assigns to it, keeping their insertion order, frequencies
+    /// and payloads. Returns the number after the last entry's position
+    /// alongside them.
+    pub(crate) fn into_shards(mut self, shards: usize) -> (Vec<Corpus<KT>>, usize)
+    where
+        KT: Clone,
+    {
+        let shards = shards.max(1);
+        let mut corpora: Vec<Corpus<KT>> = (0..shards).map(|_| self.empty_clone()).collect();
+        let mut entries: Vec<(DefaultSymbol, EntryStats)> = self.entries.drain().collect();
+        entries.sort_by_key(|(_, stats)| stats.order);
+        for (sym, stats) in entries {
+            let ngram = match self.ngrams.remove(&sym) {
+                Some(ngram) => ngram,
+                None => continue,
+            };
+            let corpus = &mut corpora[shard_of(&ngram.text, shards)];
+            let key = ngram.text.clone();
+            corpus.insert_ngram(ngram, Some(stats.order));
+            let shard_sym = corpus.strings.get_or_intern(key);
+            corpus.entries.insert(shard_sym, stats);
+            if let Some(payload) = self.payloads.remove(&sym) {
+                corpus.payloads.insert(shard_sym, payload);
+            }
+        }
+        for corpus in corpora.iter_mut() {
+            corpus.update_stop_grams();
+        }
+        (corpora, self.next_order)
+    }
+
+    /// Create a new, empty `Corpus` with the same settings as this one.
+    pub(crate) fn empty_clone(&self) -> Corpus<KT>
+    where
+        KT: Clone,
+    {
+        self.empty_with_key_transformer(self.key_transformer.clone())
+    }
+
+    /// Iterate over the (already transformed) keys of all entries in the
+    /// `Corpus`.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &str> {
+        self.ngrams.values().map(|ngram| ngram.text.as_str())
+    }
+
+    /// Create a new, empty `Corpus` with the same settings as this one, but
+    /// processing its keys with `key_transformer` instead.
+    pub(crate) fn empty_with_key_transformer<KT2>(&self, key_transformer: KT2) -> Corpus<KT2>
+    where
+        KT2: KeyTransformer,
+    {
+        Corpus {
+            arity: self.arity,
+            arities: self.arities.clone(),
+            position_tolerance: self.position_tolerance,
+            skip_grams: self.skip_grams,
+            stop_grams: self.stop_grams,
+            pad_left: self.pad_left.clone(),
+            pad_right: self.pad_right.clone(),
+            strings: StringInterner::default(),
+            ngrams: HashMap::new(),
+            gram_to_words: HashMap::new(),
+            stop_gram_frequencies: HashMap::new(),
+            entries: HashMap::new(),
+            next_order: 0,
+            payloads: HashMap::new(),
+            key_transformer,
+        }
+    }
+
     /// Determines whether an exact match exists for the supplied `text` in the
     /// `Corpus` index, after processing it with the `Corpus`'s `key_trans`
     /// function.
@@ -135,6 +691,127 @@ where
             .map(|_| text.to_string())
     }
 
+    /// Save the settings and entries of the `Corpus`, including their
+    /// frequencies and payloads, to the `writer`, in a line based text
+    /// format that `Corpus::load` reads back. The key transformer isn't
+    /// saved, but the keys are saved already transformed, along with whether
+    /// it lowercases them.
+    /// ```rust
+    /// # use ngrammatic::{Corpus, CorpusBuilder, IdentityKeyTransformer};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let corpus = CorpusBuilder::default()
+    ///     .arity(3)
+    ///     .fill(vec!["tomato", "potato"])
+    ///     .finish();
+    /// let mut saved = Vec::new();
+    /// corpus.save(&mut saved)?;
+    /// let loaded = Corpus::load(&saved[..], IdentityKeyTransformer)?;
+    /// assert_eq!(
+    ///     loaded.search("tomacco", 0.0, 10),
+    ///     corpus.search("tomacco", 0.0, 10)
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let settings = Settings {
+            arity: self.arity,
+            arities: self.arities.clone(),
+            position_tolerance: self.position_tolerance,
+            skip_grams: self.skip_grams,
+            stop_grams: self.stop_grams,
+            pad_left: self.pad_left.clone(),
+            pad_right: self.pad_right.clone(),
+            lowercase: self.key_transformer.lowercases(),
+        };
+        let mut entries: Vec<(&EntryStats, DefaultSymbol)> = self
+            .entries
+            .iter()
+            .map(|(sym, stats)| (stats, *sym))
+            .collect();
+        entries.sort_by_key(|(stats, _)| stats.order);
+        let entries = entries.into_iter().filter_map(|(stats, sym)| {
+            let key = self.strings.resolve(sym)?;
+            let payload = self.payloads.get(&sym).map(String::as_str);
+            Some((key, stats.frequency, payload))
+        });
+        write_corpus(writer, &settings, entries)
+    }
+
+    /// Load a `Corpus` saved with `Corpus::save` from the `reader`, with the
+    /// same `key_transformer` it was built with. Fails if reading fails, or
+    /// with `Error::Format` if the input isn't a saved corpus.
+    pub fn load<R: BufRead>(reader: R, key_transformer: KT) -> Result<Corpus<KT>, Error> {
+        Self::load_with(reader, |_| key_transformer)
+    }
+
+    /// Load a `Corpus` saved with `Corpus::save` from the `reader`, with the
+    /// key transformer that `key_transformer` returns given whether the
+    /// saved keys were lowercased. Fails like `Corpus::load`.
+    /// ```rust
+    /// # use ngrammatic::{Corpus, CorpusBuilder, IdentityKeyTransformer, KeyTransformer};
+    /// # use ngrammatic::LowerKeyTransformer;
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let case_insensitive = true;
+    /// let corpus = CorpusBuilder::default()
+    ///     .link_key_transformer(case_insensitive.then(|| LowerKeyTransformer))
+    ///     .fill(vec!["Tomato", "Potato"])
+    ///     .finish();
+    /// let mut saved = Vec::new();
+    /// corpus.save(&mut saved)?;
+    /// let loaded = Corpus::load_with(&saved[..], |lowercase| {
+    ///     IdentityKeyTransformer.link(lowercase.then(|| LowerKeyTransformer))
+    /// })?;
+    /// assert_eq!(loaded.search("TOMATO", 0.9, 1)[0].text, "tomato");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_with<R, F>(reader: R, key_transformer: F) -> Result<Corpus<KT>, Error>
+    where
+        R: BufRead,
+        F: FnOnce(bool) -> KT,
+    {
+        let (settings, saved) = read_corpus(reader)?;
+        check_settings(
+            settings.arity,
+            &settings.arities,
+            &settings.pad_left,
+            &settings.pad_right,
+        )?;
+        let (arity, arities) = resolve_arities(settings.arity, &settings.arities);
+        let mut corpus = Corpus {
+            arity,
+            arities,
+            position_tolerance: settings.position_tolerance,
+            skip_grams: settings.skip_grams,
+            stop_grams: settings.stop_grams,
+            pad_left: settings.pad_left,
+            pad_right: settings.pad_right,
+            strings: StringInterner::default(),
+            ngrams: HashMap::new(),
+            gram_to_words: HashMap::new(),
+            stop_gram_frequencies: HashMap::new(),
+            entries: HashMap::new(),
+            next_order: 0,
+            payloads: HashMap::new(),
+            key_transformer: key_transformer(settings.lowercase),
+        };
+        for entry in saved {
+            let order = corpus.next_order;
+            // Can't fail, as keys without grams are allowed
+            let _ = corpus.add_key(entry.key.clone(), true, || Some(order));
+            let sym = corpus.strings.get_or_intern(entry.key);
+            if let Some(stats) = corpus.entries.get_mut(&sym) {
+                stats.frequency = entry.frequency.max(1);
+            }
+            if let Some(payload) = entry.payload {
+                corpus.payloads.insert(sym, payload);
+            }
+        }
+        corpus.update_stop_grams();
+        Ok(corpus)
+    }
+
     /// Perform a fuzzy search of the `Corpus` for `Ngrams` above some
     /// `threshold` of similarity to the supplied `text`.  Returns up to `limit`
     /// results, sorted by highest similarity to lowest.
@@ -202,28 +879,44 @@ where
         threshold: f32,
         limit: usize,
     ) -> Vec<SearchResult> {
-        let item = NgramBuilder::new(&self.key_transformer.transform(text))
-            .arity(self.arity)
-            .pad_left(self.pad_left.clone())
-            .pad_right(self.pad_right.clone())
-            .finish();
-        let ngrams_to_consider: HashSet<&Ngram> = item
-            .grams
-            .keys()
-            .filter_map(|gram_str| self.strings.get(gram_str.as_str()))
-            .filter_map(|gram_sym| self.gram_to_words.get(&gram_sym))
-            // Fetch ngrams from raw words
-            .flat_map(|word_syms| word_syms.iter().filter_map(|ws| self.ngrams.get(ws)))
-            .collect();
-        let mut results: Vec<SearchResult> = ngrams_to_consider
-            .iter()
-            .filter_map(|n| item.matches_with_warp(n, warp, threshold))
-            .collect();
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit);
+        self.search_unchecked(text, &options)
+    }
 
-        // Sort highest similarity to lowest
-        results.sort_by(|a, b| b.partial_cmp(a).unwrap());
-        results.truncate(limit);
-        results
+    /// Perform a fuzzy search of the `Corpus` for `Ngrams` with a custom `warp`,
+    /// re-ranking the best n-gram candidates with an edit metric. The
+    /// `similarity` of each result blends its n-gram and edit similarities as
+    /// configured by `rerank`, and results above some `threshold` of that
+    /// blended similarity are returned, up to `limit` of them, sorted by
+    /// highest similarity to lowest.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, EditMetric, Rerank};
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default().fill(vec!["abce", "abcdxyz"]).finish();
+    /// assert_eq!(corpus.search("abcd", 0.40, 10)[0].text, "abcdxyz");
+    /// let rerank = Rerank::new(EditMetric::Levenshtein);
+    /// let results = corpus.search_with_rerank("abcd", 2.0, 0.40, 10, &rerank);
+    /// assert_eq!(results[0].text, "abce");
+    /// assert_eq!(results[0].edit_similarity, Some(0.75));
+    /// # }
+    /// ```
+    pub fn search_with_rerank(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+        rerank: &Rerank,
+    ) -> Vec<SearchResult> {
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit)
+            .rerank(*rerank);
+        self.search_unchecked(text, &options)
     }
 
     /// Perform a parallelized fuzzy search of the `Corpus` for `Ngrams` with a custom
@@ -251,30 +944,219 @@ where
         threshold: f32,
         limit: usize,
     ) -> Vec<SearchResult> {
-        let item = NgramBuilder::new(&self.key_transformer.transform(text))
-            .arity(self.arity)
-            .pad_left(self.pad_left.clone())
-            .pad_right(self.pad_right.clone())
-            .finish();
-        let ngrams_to_consider: HashSet<&Ngram> = item
-            .grams
-            .keys()
-            .collect::<Vec<_>>()
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit)
+            .parallel(true);
+        self.search_unchecked(text, &options)
+    }
+
+    /// Perform a fuzzy search of the `Corpus` for `Ngrams` similar to the
+    /// supplied `text`, as configured by the `options`. Returns the results
+    /// sorted by highest similarity to lowest, with ties broken as set by
+    /// `SearchOptions::tie_break`.
+    ///
+    /// Fails with `Error::InvalidThreshold` if the threshold isn't within
+    /// 0.0 to 1.0, or with `Error::InvalidWarp` if the warp isn't within 1.0
+    /// to 3.0.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, SearchOptions};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill(vec!["tomato", "tomatoes", "potato"])
+    ///     .finish();
+    /// let options = SearchOptions::new().threshold(0.40).limit(1);
+    /// let first_page = corpus.search_with("tomacco", &options)?;
+    /// let second_page = corpus.search_with("tomacco", &options.clone().offset(1))?;
+    /// assert_eq!(first_page.len(), 1);
+    /// assert_eq!(second_page.len(), 1);
+    /// assert_ne!(first_page[0].text, second_page[0].text);
+    /// assert!(corpus.search_with("tomacco", &options.warp(f32::NAN)).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn search_with(
+        &self,
+        text: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>, Error> {
+        options.validate()?;
+        Ok(self.search_unchecked(text, options))
+    }
+
+    /// Perform a search as configured by the `options`, without checking
+    /// their validity. Out of range warps are clamped, and out of range
+    /// thresholds either admit or reject every candidate.
+    fn search_unchecked(&self, text: &str, options: &SearchOptions) -> Vec<SearchResult> {
+        let item = self.build_ngram(&self.key_transformer.transform(text));
+        let idf = |gram: &str| self.inverse_document_frequency(gram);
+        let results = self.ranked_candidates(&item, options, &idf);
+        rank(&item.text, results, options)
+    }
+
+    /// Score the entries that are candidate matches for the query `item`, as
+    /// configured by the `options`, keeping only as many of the best of them
+    /// as the final results can draw from. Weighted scorings weigh grams by
+    /// their `idf`, which for a shard is that of the whole corpus.
+    pub(crate) fn ranked_candidates(
+        &self,
+        item: &Ngram,
+        options: &SearchOptions,
+        idf: &Idf,
+    ) -> Vec<Ranked> {
+        // When re-ranking, the threshold applies to the blended similarity
+        let ngram_threshold = match options.rerank {
+            Some(_) => 0.0,
+            None => options.threshold,
+        };
+
+        #[cfg(feature = "rayon")]
+        let mut results = if options.parallel {
+            self.candidate_results_par(item, options, ngram_threshold, idf)
+        } else {
+            self.candidate_results(item, options, ngram_threshold, idf)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let mut results = self.candidate_results(item, options, ngram_threshold, idf);
+
+        let keep = match options.rerank {
+            Some(rerank) => rerank.candidates,
+            None => options.offset.saturating_add(options.limit),
+        };
+        if results.len() > keep {
+            results.select_nth_unstable_by(keep, |a, b| options.tie_break.compare(a, b));
+            results.truncate(keep);
+        }
+        results
+    }
+
+    /// Score every `Ngram` in the `Corpus` that shares a gram with the query
+    /// `item` and passes the `options` filter, keeping those at or above the
+    /// `threshold`.
+    fn candidate_results(
+        &self,
+        item: &Ngram,
+        options: &SearchOptions,
+        threshold: f32,
+        idf: &Idf,
+    ) -> Vec<Ranked> {
+        let (postings, pruned) = self.candidate_postings(item);
+        let words_to_consider: HashSet<DefaultSymbol> = postings
+            .iter()
+            .flat_map(|(word_syms, kept)| word_syms.iter().take(*kept))
+            .collect();
+        words_to_consider
+            .iter()
+            .filter_map(|word_sym| {
+                self.candidate_result(item, *word_sym, options, threshold, pruned, idf)
+            })
+            .collect()
+    }
+
+    /// Parallelized version of `candidate_results`.
+    #[cfg(feature = "rayon")]
+    fn candidate_results_par(
+        &self,
+        item: &Ngram,
+        options: &SearchOptions,
+        threshold: f32,
+        idf: &Idf,
+    ) -> Vec<Ranked> {
+        let (postings, pruned) = self.candidate_postings(item);
+        let words_to_consider: HashSet<DefaultSymbol> = postings
             .par_iter()
-            .filter_map(|gram_str| self.strings.get(gram_str.as_str()))
-            .filter_map(|gram_sym| self.gram_to_words.get(&gram_sym))
-            .flat_map_iter(|word_syms| word_syms.iter().filter_map(|ws| self.ngrams.get(ws)))
+            .flat_map_iter(|(word_syms, kept)| word_syms.iter().take(*kept))
             .collect();
-        let mut results: Vec<SearchResult> = ngrams_to_consider
+        words_to_consider
             .into_par_iter()
-            .filter_map(|n| item.matches_with_warp(n, warp, threshold))
-            .collect();
+            .filter_map(|word_sym| {
+                self.candidate_result(item, word_sym, options, threshold, pruned, idf)
+            })
+            .collect()
+    }
 
-        // Sort highest similarity to lowest
-        results.par_sort_by(|a, b| b.partial_cmp(a).unwrap());
-        results.truncate(limit);
-        results
+    /// The posting lists to gather the candidate matches of the query `item`
+    /// from, each with how many of its entries to gather, and whether some
+    /// entries having its stop grams were left out. Only `cap` of the
+    /// entries having each stop gram are gathered, unless every gram of the
+    /// query is a stop gram.
+    fn candidate_postings(&self, item: &Ngram) -> (Vec<(&PostingList, usize)>, bool) {
+        let mut postings = Vec::new();
+        let mut stopped = Vec::new();
+        let mut only_stop_grams = true;
+        for gram_str in item.grams.keys() {
+            let gram_sym = match self.strings.get(gram_str.as_str()) {
+                Some(gram_sym) => gram_sym,
+                None => continue,
+            };
+            let word_syms = self.gram_to_words.get(&gram_sym);
+            match self.stop_gram_frequencies.get(&gram_sym) {
+                Some(frequency) => stopped.push((word_syms, *frequency)),
+                None => {
+                    only_stop_grams = false;
+                    if let Some(word_syms) = word_syms {
+                        postings.push((word_syms, word_syms.len()));
+                    }
+                }
+            }
+        }
+        let cap = if only_stop_grams {
+            usize::MAX
+        } else {
+            self.stop_grams.cap
+        };
+        let mut pruned = false;
+        for (word_syms, frequency) in stopped {
+            let stored = word_syms.map_or(0, PostingList::len);
+            let kept = stored.min(cap);
+            pruned |= kept < frequency;
+            if let Some(word_syms) = word_syms {
+                postings.push((word_syms, kept));
+            }
+        }
+        (postings, pruned)
     }
+
+    /// Score the entry `word_sym` against the query `item`, if it passes the
+    /// `options` filter and is at or above the `threshold`. The result is
+    /// marked as `pruned` if it was found while pruning stop grams.
+    fn candidate_result(
+        &self,
+        item: &Ngram,
+        word_sym: DefaultSymbol,
+        options: &SearchOptions,
+        threshold: f32,
+        pruned: bool,
+        idf: &Idf,
+    ) -> Option<Ranked> {
+        let ngram = self.ngrams.get(&word_sym)?;
+        let stats = self.entries.get(&word_sym)?;
+        if !options.admits(&ngram.text) {
+            return None;
+        }
+        let similarity = options.scoring.similarity(item, ngram, options.warp, idf);
+        if similarity >= threshold {
+            let mut result = SearchResult::new(ngram.text.to_string(), similarity);
+            result.pruned = pruned;
+            Some((result, *stats))
+        } else {
+            None
+        }
+    }
+}
+
+/// Assign the already transformed `key` to one of `shards` corpora, by its
+/// 64 bit FNV-1a hash. Unlike the hashers of the standard library, FNV-1a is
+/// fully specified, so the assignment is the same on every run and with
+/// every toolchain, and serialized sharded corpora stay valid.
+pub(crate) fn shard_of(key: &str, shards: usize) -> usize {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    (hash % shards as u64) as usize
 }
 
 /// Build an Ngram Corpus, one setting at a time.
@@ -286,11 +1168,18 @@ where
     KT: KeyTransformer,
 {
     arity: usize,
+    arities: Vec<(usize, f32)>,
+    position_tolerance: Option<usize>,
+    skip_grams: SkipGrams,
+    stop_grams: StopGrams,
     pad_left: Pad,
     pad_right: Pad,
     strings: StringInterner<DefaultBackend>,
     texts: Vec<DefaultSymbol>,
+    payloads: HashMap<DefaultSymbol, String>,
     key_transformer: KT,
+    #[cfg(feature = "rayon")]
+    parallel: bool,
 }
 
 impl<KT> std::fmt::Debug for CorpusBuilder<KT>
@@ -303,6 +1192,10 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         writeln!(f, "CorpusBuilder {{")?;
         writeln!(f, "  arity: {:?},", self.arity)?;
+        writeln!(f, "  arities: {:?},", self.arities)?;
+        writeln!(f, "  position_tolerance: {:?},", self.position_tolerance)?;
+        writeln!(f, "  skip_grams: {:?},", self.skip_grams)?;
+        writeln!(f, "  stop_grams: {:?},", self.stop_grams)?;
         writeln!(f, "  pad_left: {:?},", self.pad_left)?;
         writeln!(f, "  pad_right: {:?},", self.pad_right)?;
         writeln!(f, "  texts: {:?},", self.texts)?;
@@ -330,11 +1223,18 @@ impl Default for CorpusBuilder {
     fn default() -> Self {
         CorpusBuilder {
             arity: 2,
+            arities: Vec::new(),
+            position_tolerance: None,
+            skip_grams: SkipGrams::None,
+            stop_grams: StopGrams::default(),
             pad_left: Pad::Auto,
             pad_right: Pad::Auto,
             texts: Vec::new(),
+            payloads: HashMap::new(),
             strings: StringInterner::default(),
             key_transformer: IdentityKeyTransformer,
+            #[cfg(feature = "rayon")]
+            parallel: false,
         }
     }
 }
@@ -364,7 +1264,84 @@ where
 
     /// Set `arity` (the _n_ in _ngram_) to use for the resulting `Corpus`.
     pub fn arity(mut self, arity: usize) -> Self {
-        self.arity = arity.max(1);
+        self.arity = arity;
+        self.arities.clear();
+        self
+    }
+
+    /// Index grams of every size in `arities` (e.g. a range such as `1..=3`,
+    /// or a set such as `[2, 4]`) in the resulting `Corpus`, with their
+    /// similarities weighted equally.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .arities(2..=3)
+    ///     .fill(vec!["pie", "tomato"])
+    ///     .finish();
+    /// let results = corpus.search("tomacco", 0.40, 10);
+    /// assert_eq!(results[0].text, "tomato");
+    /// # }
+    /// ```
+    pub fn arities<It>(self, arities: It) -> Self
+    where
+        It: IntoIterator<Item = usize>,
+    {
+        self.weighted_arities(arities.into_iter().map(|arity| (arity, 1.0)))
+    }
+
+    /// Index grams of every size in `arities` in the resulting `Corpus`, each
+    /// paired with the weight its similarity carries in search results.
+    pub fn weighted_arities<It>(mut self, arities: It) -> Self
+    where
+        It: IntoIterator<Item = (usize, f32)>,
+    {
+        self.arities = arities.into_iter().collect();
+        self
+    }
+
+    /// Record the offset of every gram in the resulting `Corpus`, so that a
+    /// gram of the query and a gram of an entry only count as shared if their
+    /// offsets are at most `max_distance` characters apart.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, Pad};
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .arity(1)
+    ///     .pad_full(Pad::None)
+    ///     .positional(0)
+    ///     .fill(vec!["silent"])
+    ///     .finish();
+    /// assert!(corpus.search("listen", 0.5, 10).is_empty());
+    /// # }
+    /// ```
+    pub fn positional(mut self, max_distance: usize) -> Self {
+        self.position_tolerance = Some(max_distance);
+        self
+    }
+
+    /// Set which skip-grams to index in the resulting `Corpus`, alongside or
+    /// instead of the contiguous grams. Skip-grams make searches more robust
+    /// to transposed characters.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, SkipGrams};
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .skip_grams(SkipGrams::Alongside(1))
+    ///     .fill(vec!["the", "ten"])
+    ///     .finish();
+    /// assert_eq!(corpus.search("teh", 0.40, 10)[0].text, "the");
+    /// # }
+    /// ```
+    pub fn skip_grams(mut self, skip_grams: SkipGrams) -> Self {
+        self.skip_grams = skip_grams;
+        self
+    }
+
+    /// Set how stop grams, which occur in too many entries to be worth
+    /// gathering candidates from, are chosen and pruned. See `StopGrams`.
+    pub fn stop_grams(mut self, stop_grams: StopGrams) -> Self {
+        self.stop_grams = stop_grams;
         self
     }
 
@@ -384,7 +1361,8 @@ where
     }
 
     /// Provide an iterator that will be parallelized that yields strings to
-    /// be added to the `Corpus`.
+    /// be added to the `Corpus`. A builder filled this way also generates the
+    /// `Ngram`s in parallel when it's finished, as with `finish_par`.
     #[cfg(feature = "rayon")]
     pub fn fill_par<FillIt>(mut self, iterable: FillIt) -> Self
     where
@@ -394,9 +1372,82 @@ where
         let tmp: Vec<String> = iterable.into_par_iter().map(<_>::into).collect();
         self.texts
             .extend(tmp.into_iter().map(|s| self.strings.get_or_intern(s)));
+        self.parallel = true;
         self
     }
 
+    /// Read texts line by line from the `reader`, as configured by the
+    /// `options`, to be added to the `Corpus`. Fails if reading fails.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, ReadOptions, Splitter};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let input = "# vegetables\ntomato potato\n\ncarrot\n";
+    /// let options = ReadOptions::new()
+    ///     .splitter(Splitter::Whitespace)
+    ///     .comment_prefix("#");
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill_from_reader(input.as_bytes(), &options)?
+    ///     .finish();
+    /// assert_eq!(corpus.search("tomacco", 0.40, 10)[0].text, "tomato");
+    /// assert!(corpus.key("vegetables").is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fill_from_reader<R>(mut self, reader: R, options: &ReadOptions) -> Result<Self, Error>
+    where
+        R: BufRead,
+    {
+        let mut texts = Vec::new();
+        let strings = &mut self.strings;
+        read_texts(reader, options, |text| texts.push(strings.get_or_intern(text)))?;
+        self.texts.extend(texts);
+        Ok(self)
+    }
+
+    /// Read texts, and optionally their payloads, from a column of
+    /// delimited data such as CSV or TSV in the `reader`, as configured by
+    /// the `options`, to be added to the `Corpus`. The payload of each text
+    /// can be looked up with `Corpus::payload`, and a text given more than
+    /// once keeps its last payload. Fails if reading fails, or if the data
+    /// isn't well formed.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, DelimitedOptions};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let input = "id,name\nQ20638126,tomato\nQ10998,potato\n";
+    /// let options = DelimitedOptions::csv()
+    ///     .column("name")
+    ///     .payload_column("id");
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill_from_delimited(input.as_bytes(), &options)?
+    ///     .finish();
+    /// let results = corpus.search("tomacco", 0.40, 10);
+    /// assert_eq!(corpus.payload(&results[0].text), Some("Q20638126"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fill_from_delimited<R>(
+        mut self,
+        reader: R,
+        options: &DelimitedOptions,
+    ) -> Result<Self, Error>
+    where
+        R: BufRead,
+    {
+        let mut texts = Vec::new();
+        let mut payloads = Vec::new();
+        let strings = &mut self.strings;
+        read_delimited(reader, options, |text, payload| {
+            let sym = strings.get_or_intern(text);
+            texts.push(sym);
+            if let Some(payload) = payload {
+                payloads.push((sym, payload.to_string()));
+            }
+        })?;
+        self.texts.extend(texts);
+        self.payloads.extend(payloads);
+        Ok(self)
+    }
+
     /// A key transformation function, supplied as a boxed Fn that takes a
     /// &str and returns a String, applied to all strings that will be added
     /// to the `Corpus`. Searches on the `Corpus` will be similarly
@@ -424,11 +1475,18 @@ where
     {
         CorpusBuilder {
             arity: self.arity,
+            arities: self.arities,
+            position_tolerance: self.position_tolerance,
+            skip_grams: self.skip_grams,
+            stop_grams: self.stop_grams,
             pad_left: self.pad_left,
             pad_right: self.pad_right,
             texts: self.texts,
+            payloads: self.payloads,
             strings: self.strings,
             key_transformer: self.key_transformer.link(key_trans),
+            #[cfg(feature = "rayon")]
+            parallel: self.parallel,
         }
     }
 
@@ -452,21 +1510,175 @@ where
     }
 
     /// Yield a `Corpus` instance with all the properties set with this builder.
+    /// An arity of 0 is treated as 1, and texts that produce no grams are
+    /// added without any. If any texts were filled in with `fill_par`, this
+    /// is `finish_par`.
     pub fn finish(self) -> Corpus<KT> {
-        let mut corpus = Corpus {
-            arity: self.arity,
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            return self.finish_par();
+        }
+        let (mut corpus, texts, payloads) = self.into_empty();
+        // Can't fail, as keys without grams are allowed
+        let _ = corpus.add_keys(corpus.transform_texts(&texts), true);
+        corpus.set_payloads(&texts, payloads);
+        corpus.update_stop_grams();
+        corpus
+    }
+
+    /// Yield a `Corpus` instance with all the properties set with this
+    /// builder, or an error if any of them is invalid, or if any of the texts
+    /// filled in is too short to produce any grams. Use `Corpus::try_add_text`
+    /// to skip such texts instead.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, Error, Pad};
+    /// # fn main() {
+    /// let result = CorpusBuilder::default().arity(0).try_finish();
+    /// assert_eq!(result.unwrap_err(), Error::InvalidArity(0));
+    /// let result = CorpusBuilder::default()
+    ///     .arity(3)
+    ///     .pad_full(Pad::None)
+    ///     .fill(vec!["tomato", "to"])
+    ///     .try_finish();
+    /// assert_eq!(result.unwrap_err(), Error::EmptyGrams { text: "to".to_string() });
+    /// # }
+    /// ```
+    pub fn try_finish(self) -> Result<Corpus<KT>, Error> {
+        check_settings(self.arity, &self.arities, &self.pad_left, &self.pad_right)?;
+        let (mut corpus, texts, payloads) = self.into_empty();
+        corpus.add_keys(corpus.transform_texts(&texts), false)?;
+        corpus.set_payloads(&texts, payloads);
+        corpus.update_stop_grams();
+        Ok(corpus)
+    }
+
+    /// Parallelized version of `finish`, which generates the `Ngram`s of all
+    /// the texts in parallel, and then merges the grams each thread collected
+    /// into the `Corpus`'s index. Yields the same `Corpus` as `finish`.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill_par(vec!["tomato", "potato", "carrot"])
+    ///     .finish_par();
+    /// assert_eq!(corpus.search_par("tomacco", 0.40, 10)[0].text, "tomato");
+    /// # }
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn finish_par(self) -> Corpus<KT> {
+        let (mut corpus, texts, payloads) = self.into_empty();
+        let keys: Vec<String> = texts
+            .par_iter()
+            .filter_map(|sym| corpus.strings.resolve(*sym))
+            .map(|text| corpus.key_transformer.transform(text))
+            .collect();
+
+        // Interning has to happen serially, and settles the order of entries
+        let mut new_keys: Vec<(DefaultSymbol, String)> = Vec::new();
+        for key in keys {
+            let sym = corpus.strings.get_or_intern(key.as_str());
+            let order = corpus.next_order;
+            let stats = corpus.entries.entry(sym).or_insert(EntryStats {
+                order,
+                frequency: 0,
+            });
+            if stats.frequency == 0 {
+                corpus.next_order += 1;
+                new_keys.push((sym, key));
+            }
+            stats.frequency += 1;
+        }
+
+        let ngrams: Vec<(DefaultSymbol, Ngram)> = new_keys
+            .into_par_iter()
+            .map(|(sym, key)| (sym, corpus.build_ngram(&key)))
+            .collect();
+        let postings: HashMap<SmolStr, Vec<DefaultSymbol>> = ngrams
+            .par_iter()
+            .fold(HashMap::new, |mut postings, (word_sym, ngram)| {
+                for gram_str in ngram.grams.keys() {
+                    postings
+                        .entry(gram_str.clone())
+                        .or_insert_with(Vec::new)
+                        .push(*word_sym);
+                }
+                postings
+            })
+            .reduce(HashMap::new, |a, b| {
+                let (mut larger, smaller) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+                for (gram_str, word_syms) in smaller {
+                    larger.entry(gram_str).or_default().extend(word_syms);
+                }
+                larger
+            });
+        for (gram_str, word_syms) in postings {
+            let gram_sym = corpus.strings.get_or_intern(gram_str.as_str());
+            corpus
+                .gram_to_words
+                .entry(gram_sym)
+                .or_default()
+                .extend(word_syms);
+        }
+        corpus.ngrams.extend(ngrams);
+        corpus.set_payloads(&texts, payloads);
+        corpus.update_stop_grams();
+        corpus
+    }
+
+    /// Split this builder into an empty `Corpus` with its settings, holding
+    /// no interned strings, and the texts to add to it in order, each with
+    /// its payload, if any.
+    pub(crate) fn into_entries(self) -> (Corpus<KT>, Vec<(String, Option<String>)>)
+    where
+        KT: Clone,
+    {
+        let (template, texts, mut payloads) = self.into_empty();
+        let entries = texts
+            .into_iter()
+            .filter_map(|sym| {
+                let text = template.strings.resolve(sym)?;
+                Some((text.to_string(), payloads.remove(&sym)))
+            })
+            .collect();
+        let empty = template.empty_with_key_transformer(template.key_transformer.clone());
+        (empty, entries)
+    }
+
+    /// Split this builder into an empty `Corpus` with its settings, and the
+    /// texts to add to it, with their payloads.
+    pub(crate) fn into_empty(
+        self,
+    ) -> (
+        Corpus<KT>,
+        Vec<DefaultSymbol>,
+        HashMap<DefaultSymbol, String>,
+    ) {
+        let (arity, arities) = resolve_arities(self.arity, &self.arities);
+        let corpus = Corpus {
+            arity,
+            arities,
+            position_tolerance: self.position_tolerance,
+            skip_grams: self.skip_grams,
+            stop_grams: self.stop_grams,
             ngrams: HashMap::new(),
             gram_to_words: HashMap::new(),
+            stop_gram_frequencies: HashMap::new(),
+            entries: HashMap::new(),
+            next_order: 0,
+            payloads: HashMap::new(),
             strings: self.strings,
             pad_left: self.pad_left,
             pad_right: self.pad_right,
             key_transformer: self.key_transformer,
         };
-        for sym in self.texts {
-            if let Some(owned) = corpus.strings.resolve(sym).map(str::to_owned) {
-                corpus.add_text(&owned);
-            }
-        }
+        (corpus, self.texts, self.payloads)
+    }
+
+    /// An empty `Corpus` with the settings of this builder, leaving out any
+    /// texts filled into it.
+    pub(crate) fn into_settings(self) -> Corpus<KT> {
+        let (mut corpus, _, _) = self.into_empty();
+        corpus.strings = StringInterner::default();
         corpus
     }
 }
@@ -474,6 +1686,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{DelimitedOptions, EditMetric, ReadOptions, TieBreak};
 
     #[test]
     fn corpus_add_text_before_setting_arity() {
@@ -502,6 +1715,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn corpus_add_multiple() {
         let corpus = CorpusBuilder::default()
             .arity(2)
@@ -552,6 +1766,43 @@ mod tests {
         assert_eq!(corpus.search("b", 0.5, 10).len(), 2);
     }
 
+    #[test]
+    fn corpus_finish_matches_add_text() {
+        let words = vec!["Word9", "word1", "WORD9", "Tomato", "tomato", "Potato"];
+        let finished = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(words.clone())
+            .finish();
+        let mut added = CorpusBuilder::default().case_insensitive().finish();
+        for word in words {
+            added.add_text(word);
+        }
+        // Symbols are interned in a different order, so compare by text
+        let postings = |corpus: &Corpus<_>| {
+            let mut postings: Vec<(String, Vec<String>)> = corpus
+                .gram_to_words
+                .iter()
+                .map(|(gram_sym, word_syms)| {
+                    let mut texts: Vec<String> = word_syms
+                        .iter()
+                        .map(|sym| corpus.strings.resolve(sym).unwrap().to_string())
+                        .collect();
+                    texts.sort();
+                    let gram = corpus.strings.resolve(*gram_sym).unwrap();
+                    (gram.to_string(), texts)
+                })
+                .collect();
+            postings.sort();
+            postings
+        };
+        assert_eq!(postings(&finished), postings(&added));
+        let options = SearchOptions::new().tie_break(TieBreak::Frequency);
+        assert_eq!(
+            finished.search_with("word", &options),
+            added.search_with("word", &options)
+        );
+    }
+
     #[test]
     fn corpus_search_emoji() {
         let corpus = CorpusBuilder::default()
@@ -586,6 +1837,388 @@ mod tests {
         assert!(corpus.search("", 0., 10).is_empty());
     }
 
+    #[test]
+    fn corpus_search_mixed_arity() {
+        let words = vec!["ab", "abcdefgh", "abcdefxy"];
+        let corpus = CorpusBuilder::default().arities(1..=3).fill(&words).finish();
+        let results = corpus.search("abcdefgx", 0.3, 10);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[2].text, "ab");
+
+        // weighting unigrams heavily favours texts sharing many characters
+        let corpus = CorpusBuilder::default()
+            .weighted_arities(vec![(1, 10.0), (3, 1.0)])
+            .fill(&words)
+            .finish();
+        let results = corpus.search("hgfedcba", 0.0, 10);
+        assert_eq!(results[0].text, "abcdefgh");
+        assert!(results[0].similarity > 0.8);
+    }
+
+    #[test]
+    fn corpus_search_positional() {
+        let words = vec!["silent", "listen", "enlist"];
+        let corpus = CorpusBuilder::default()
+            .arity(1)
+            .pad_full(Pad::None)
+            .fill(&words)
+            .finish();
+        assert_eq!(corpus.search("listen", 0.99, 10).len(), 3);
+
+        let corpus = CorpusBuilder::default()
+            .arity(1)
+            .pad_full(Pad::None)
+            .positional(1)
+            .fill(&words)
+            .finish();
+        let results = corpus.search("listen", 0.6, 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].text, "listen");
+    }
+
+    #[test]
+    fn corpus_search_skip_grams_transpositions() {
+        let words = vec!["the", "ten", "tea", "receive", "recipe", "weird", "wired"];
+        let typos = [("teh", "the"), ("recieve", "receive"), ("wierd", "weird")];
+        // A typo is recalled if its word is among the best matches, however
+        // equally similar matches happen to be ordered
+        let recall = |corpus: &Corpus<IdentityKeyTransformer>| {
+            typos
+                .iter()
+                .filter(|(typo, word)| {
+                    let results = corpus.search(typo, 0.5, 10);
+                    results.iter().any(|result| {
+                        result.text == *word && result.similarity == results[0].similarity
+                    })
+                })
+                .count()
+        };
+
+        let plain = CorpusBuilder::default().fill(&words).finish();
+        let skipping = CorpusBuilder::default()
+            .skip_grams(SkipGrams::Alongside(1))
+            .fill(&words)
+            .finish();
+        assert!(recall(&plain) < typos.len());
+        assert_eq!(recall(&skipping), typos.len());
+    }
+
+    #[test]
+    fn corpus_search_with_rerank() {
+        let corpus = CorpusBuilder::default()
+            .fill(vec!["receive", "recipe", "reserve", "deceive"])
+            .finish();
+        let rerank = Rerank::new(EditMetric::DamerauLevenshtein).edit_weight(1.0);
+        let results = corpus.search_with_rerank("recieve", 2.0, 0.0, 10, &rerank);
+        assert_eq!(results[0].text, "receive");
+        for result in &results {
+            assert_eq!(result.edit_similarity, Some(result.similarity));
+        }
+        assert!((results[0].similarity - 6.0 / 7.0).abs() < 0.0001);
+
+        // Only the best candidates are re-ranked
+        let rerank = rerank.candidates(2);
+        assert_eq!(corpus.search_with_rerank("recieve", 2.0, 0.0, 10, &rerank).len(), 2);
+        // The threshold applies to the blended similarity
+        assert!(corpus
+            .search_with_rerank("recieve", 2.0, 0.9, 10, &rerank)
+            .is_empty());
+    }
+
+    #[test]
+    fn corpus_search_with_options() {
+        let corpus = CorpusBuilder::default()
+            .arity(1)
+            .pad_full(Pad::None)
+            .fill(vec!["abc", "abd", "abe", "xyz"])
+            .finish();
+        let options = SearchOptions::new()
+            .threshold(0.5)
+            .tie_break(TieBreak::Text);
+        let texts = |results: Result<Vec<SearchResult>, Error>| {
+            results.unwrap().into_iter().map(|r| r.text).collect::<Vec<_>>()
+        };
+        assert_eq!(texts(corpus.search_with("ab", &options)), vec!["abc", "abd", "abe"]);
+        assert_eq!(
+            texts(corpus.search_with("ab", &options.clone().offset(1).limit(1))),
+            vec!["abd"]
+        );
+        assert!(texts(corpus.search_with("ab", &options.clone().offset(3))).is_empty());
+        assert_eq!(
+            texts(corpus.search_with("ab", &options.filter(|text| text.ends_with('e')))),
+            vec!["abe"]
+        );
+    }
+
+    #[test]
+    fn corpus_search_tie_breaks() {
+        // Anagrams share all their unigrams, and so tie
+        let corpus = CorpusBuilder::default()
+            .arity(1)
+            .pad_full(Pad::None)
+            .fill(vec!["bca", "cab", "abc", "cab", "cab", "abc"])
+            .finish();
+        let texts = |tie_break: TieBreak| {
+            let options = SearchOptions::new().tie_break(tie_break);
+            corpus
+                .search_with("abc", &options)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(TieBreak::default()), vec!["abc", "bca", "cab"]);
+        assert_eq!(texts(TieBreak::InsertionOrder), vec!["bca", "cab", "abc"]);
+        assert_eq!(texts(TieBreak::Frequency), vec!["cab", "abc", "bca"]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn corpus_search_with_options_parallel() {
+        let words: Vec<String> = (0..500).map(|i| format!("tomato{}", i % 97)).collect();
+        let corpus = CorpusBuilder::default().fill(&words).finish();
+        for tie_break in [TieBreak::Text, TieBreak::InsertionOrder, TieBreak::Frequency] {
+            let options = SearchOptions::new().limit(50).tie_break(tie_break);
+            let texts = |options: &SearchOptions| {
+                corpus
+                    .search_with("tomacco", options)
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| (r.text, r.similarity))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(texts(&options), texts(&options.clone().parallel(true)));
+        }
+    }
+
+    #[test]
+    fn corpus_search_with_invalid_options() {
+        let corpus = CorpusBuilder::default().fill(vec!["tomato"]).finish();
+        let search = |options: SearchOptions| corpus.search_with("tomacco", &options);
+        for threshold in [-0.1, 1.1, f32::NAN] {
+            assert!(matches!(
+                search(SearchOptions::new().threshold(threshold)),
+                Err(Error::InvalidThreshold(_))
+            ));
+        }
+        for warp in [0.5, 3.5, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                search(SearchOptions::new().warp(warp)),
+                Err(Error::InvalidWarp(_))
+            ));
+        }
+        assert!(search(SearchOptions::new().threshold(1.0).warp(3.0)).is_ok());
+        // The legacy searches don't validate, but never panic either
+        assert!(corpus.search_with_warp("tomacco", f32::NAN, 0.0, 10).is_empty());
+    }
+
+    #[test]
+    fn corpus_try_finish() {
+        let corpus = CorpusBuilder::default()
+            .fill(vec!["tomato", "potato"])
+            .try_finish()
+            .unwrap();
+        assert_eq!(corpus.search("tomato", 0.9, 10)[0].text, "tomato");
+        let result = CorpusBuilder::default()
+            .pad_right(Pad::Pad(String::new()))
+            .try_finish();
+        assert_eq!(result.unwrap_err(), Error::InvalidPadding);
+    }
+
+    #[test]
+    fn corpus_try_add_text() {
+        let mut corpus = CorpusBuilder::default()
+            .arity(3)
+            .pad_full(Pad::None)
+            .case_insensitive()
+            .finish();
+        let rows = ["Tomato", "TO", "Potato", ""];
+        let skipped: Vec<Error> = rows
+            .iter()
+            .filter_map(|row| corpus.try_add_text(row).err())
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                Error::EmptyGrams {
+                    text: "to".to_string()
+                },
+                Error::EmptyGrams {
+                    text: String::new()
+                },
+            ]
+        );
+        assert!(corpus.key("tomato").is_some());
+        assert!(corpus.key("to").is_none());
+    }
+
+    #[test]
+    fn corpus_fill_from_delimited_payloads() {
+        let input = "name\tid\nTomato\t1\npotato\t2\ntomato\t3\n\t4\n";
+        let options = DelimitedOptions::tsv().payload_column("id");
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill_from_delimited(input.as_bytes(), &options)
+            .unwrap()
+            .finish();
+        assert_eq!(corpus.payload("TOMATO"), Some("3"));
+        assert_eq!(corpus.payload("potato"), Some("2"));
+        assert_eq!(corpus.payload("carrot"), None);
+        let missing = CorpusBuilder::default()
+            .fill_from_delimited(input.as_bytes(), &options.column("title"));
+        assert!(matches!(missing, Err(Error::Format { line: 1, .. })));
+    }
+
+    #[test]
+    fn corpus_fill_from_reader_io_error() {
+        let input: &[u8] = b"tomato\n\xff\n";
+        let result = CorpusBuilder::default().fill_from_reader(input, &ReadOptions::new());
+        assert!(matches!(result, Err(Error::Io { .. })));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn corpus_finish_par() {
+        let words: Vec<String> = (0..2000)
+            .map(|i| format!("Word{}", i % 1500))
+            .chain(vec!["TOMATO".to_string(), "tomato".to_string()])
+            .collect();
+        let payloads = DelimitedOptions::csv().payload_column(1);
+        let build = || {
+            CorpusBuilder::default()
+                .arities(1..=2)
+                .case_insensitive()
+                .fill(&words)
+                .fill_from_delimited("text,id\ntomato,7\n".as_bytes(), &payloads)
+                .unwrap()
+        };
+        // Symbols are interned in a different order, so compare by text
+        let by_text = |corpus: &Corpus<_>| {
+            let mut entries: Vec<(String, Ngram, EntryStats)> = corpus
+                .ngrams
+                .iter()
+                .map(|(sym, ngram)| (ngram.text.to_string(), ngram.clone(), corpus.entries[sym]))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        };
+        let serial = build().finish();
+        let parallel = build().finish_par();
+        assert_eq!(by_text(&serial), by_text(&parallel));
+        assert_eq!(parallel.payload("Tomato"), Some("7"));
+        let filled_par = build().fill_par(vec!["Word0"]);
+        assert!(filled_par.parallel);
+        let filled = build().fill(vec!["Word0"]);
+        assert_eq!(by_text(&filled_par.finish()), by_text(&filled.finish()));
+        for query in ["word12", "tomacco", "wrd999"] {
+            for tie_break in [TieBreak::InsertionOrder, TieBreak::Frequency] {
+                let options = SearchOptions::new().limit(100).tie_break(tie_break);
+                assert_eq!(
+                    serial.search_with(query, &options),
+                    parallel.search_with(query, &options)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn corpus_save_load() {
+        let mut corpus = CorpusBuilder::default()
+            .weighted_arities(vec![(2, 0.5), (3, 1.0)])
+            .positional(3)
+            .pad_left(Pad::Pad("^".to_string()))
+            .case_insensitive()
+            .fill(vec!["tomato", "Tomato", "potato", "carrot"])
+            .finish();
+        corpus.add_text_with_payload("Tomatoes", "plural\tred");
+        corpus.remove_text("carrot");
+        let mut saved = Vec::new();
+        corpus.save(&mut saved).unwrap();
+        let loaded = Corpus::load(
+            &saved[..],
+            IdentityKeyTransformer.link(LowerKeyTransformer),
+        )
+        .unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.payload("TOMATOES"), Some("plural\tred"));
+        for tie_break in [TieBreak::InsertionOrder, TieBreak::Frequency] {
+            let options = SearchOptions::new().tie_break(tie_break);
+            assert_eq!(
+                loaded.search_with("Tomacco", &options),
+                corpus.search_with("Tomacco", &options)
+            );
+        }
+        let mut resaved = Vec::new();
+        loaded.save(&mut resaved).unwrap();
+        assert_eq!(resaved, saved);
+        let truncated = Corpus::load(&saved[..20], IdentityKeyTransformer);
+        assert!(matches!(truncated, Err(Error::Format { .. })));
+
+        // Whether keys were lowercased is saved, and chooses the key
+        // transformer of corpora loaded with load_with
+        let load_with = |saved: &[u8]| {
+            Corpus::load_with(saved, |lowercase| {
+                IdentityKeyTransformer.link(lowercase.then(|| LowerKeyTransformer))
+            })
+        };
+        assert!(load_with(&saved[..]).unwrap().key("TOMATO").is_some());
+        let sensitive = CorpusBuilder::default().fill(vec!["Tomato"]).finish();
+        let mut saved = Vec::new();
+        sensitive.save(&mut saved).unwrap();
+        assert!(load_with(&saved[..]).unwrap().key("tomato").is_none());
+    }
+
+    #[test]
+    fn corpus_remove_text() {
+        let mut corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(vec!["tomato", "tomatoes", "potato"])
+            .finish();
+        corpus.add_text_with_payload("Tornado", "wind");
+        assert!(corpus.remove_text("TOMATO"));
+        assert!(corpus.remove_text("tornado"));
+        assert!(!corpus.remove_text("carrot"));
+        assert_eq!(corpus.len(), 2);
+        assert_eq!(corpus.payload("tornado"), None);
+        let results = corpus.search("tomato", 0.0, 10);
+        let texts: Vec<&str> = results.iter().map(|r| r.text.as_str()).collect();
+        assert_eq!(texts, vec!["tomatoes", "potato"]);
+        // Removed texts can be added again
+        corpus.add_text("tomato");
+        assert_eq!(corpus.search("tomato", 1.0, 10)[0].text, "tomato");
+    }
+
+    #[test]
+    fn shard_assignment_is_stable() {
+        // FNV-1a hashes of "" and "a" are 0xcbf29ce484222325 and
+        // 0xaf63dc4c8601ec8c
+        assert_eq!(shard_of("", 8), 5);
+        assert_eq!(shard_of("a", 8), 4);
+        assert_eq!(shard_of("tomato", 8), 3);
+        assert_eq!(shard_of("potato", 8), 2);
+        assert_eq!(shard_of("carrot", 3), 2);
+        assert_eq!(shard_of("tomato", 1), 0);
+    }
+
+    #[test]
+    fn search_result_total_order() {
+        let result = |text: &str, similarity| SearchResult::new(text.to_string(), similarity);
+        assert_ne!(result("abc", 0.5), result("abd", 0.5));
+        assert_eq!(result("abc", 0.5), result("abc", 0.5));
+        let mut results = [
+            result("b", 0.5),
+            result("c", f32::NAN),
+            result("a", 0.5),
+            result("d", 1.0),
+        ];
+        results.sort_by(|a, b| b.cmp(a));
+        let texts: Vec<&str> = results.iter().map(|r| r.text.as_str()).collect();
+        // NaN similarities sort last
+        assert_eq!(texts, vec!["d", "a", "b", "c"]);
+        assert!(result("c", f32::NAN) < result("z", f32::NEG_INFINITY));
+        assert!(result("c", f32::NAN) < result("c", 0.0));
+    }
+
     #[test]
     fn accept_iterator_of_strings() {
         let provider = Vec::<String>::new().into_iter();
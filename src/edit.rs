@@ -0,0 +1,237 @@
+#![deny(missing_docs)]
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A character-based string similarity measure, used to re-rank the best
+/// n-gram candidates of a search.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EditMetric {
+    /// Levenshtein distance (insertions, deletions and substitutions),
+    /// normalized by the length of the longer string.
+    Levenshtein,
+    /// Damerau-Levenshtein distance, in its optimal string alignment form,
+    /// which also counts a transposition of two adjacent characters as a
+    /// single edit. Normalized by the length of the longer string.
+    DamerauLevenshtein,
+    /// Jaro-Winkler similarity, with the standard prefix scale of 0.1 for
+    /// common prefixes of up to four characters.
+    JaroWinkler,
+}
+
+impl EditMetric {
+    /// Calculate the similarity of `a` and `b` under this metric, in the
+    /// range of 0.0 to 1.0.
+    /// ```rust
+    /// # use ngrammatic::EditMetric;
+    /// # fn main() {
+    /// assert_eq!(EditMetric::Levenshtein.similarity("ca", "ac"), 0.0);
+    /// assert_eq!(EditMetric::DamerauLevenshtein.similarity("ca", "ac"), 0.5);
+    /// # }
+    /// ```
+    pub fn similarity(&self, a: &str, b: &str) -> f32 {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        match *self {
+            EditMetric::Levenshtein => normalize(levenshtein(&a, &b, false), &a, &b),
+            EditMetric::DamerauLevenshtein => normalize(levenshtein(&a, &b, true), &a, &b),
+            EditMetric::JaroWinkler => jaro_winkler(&a, &b),
+        }
+    }
+}
+
+/// Turn an edit `distance` between `a` and `b` into a similarity.
+fn normalize(distance: usize, a: &[char], b: &[char]) -> f32 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        1.0
+    } else {
+        1.0 - distance as f32 / longest as f32
+    }
+}
+
+/// Edit distance between `a` and `b`, optionally counting transpositions of
+/// adjacent characters as a single edit (optimal string alignment).
+fn levenshtein(a: &[char], b: &[char], transpositions: bool) -> usize {
+    // Three rolling rows of the distance matrix: two rows back (only needed
+    // for transpositions), the previous row, and the current one.
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if transpositions && i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Jaro-Winkler similarity of `a` and `b`.
+fn jaro_winkler(a: &[char], b: &[char]) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches: Vec<char> = Vec::with_capacity(a.len());
+    for (i, &c) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        for j in start..end {
+            if !b_matched[j] && b[j] == c {
+                b_matched[j] = true;
+                a_matches.push(c);
+                break;
+            }
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.0;
+    }
+    let b_matches = b
+        .iter()
+        .zip(&b_matched)
+        .filter(|(_, matched)| **matched)
+        .map(|(c, _)| *c);
+    let transpositions = a_matches
+        .iter()
+        .zip(b_matches)
+        .filter(|(x, y)| **x != *y)
+        .count()
+        / 2;
+    let m = a_matches.len() as f32;
+    let jaro = (m / a.len() as f32 + m / b.len() as f32 + (m - transpositions as f32) / m) / 3.0;
+    let prefix = a
+        .iter()
+        .zip(b)
+        .take(4)
+        .take_while(|(x, y)| x == y)
+        .count();
+    jaro + prefix as f32 * 0.1 * (1.0 - jaro)
+}
+
+/// Settings for re-ranking the best n-gram candidates of a search with an
+/// `EditMetric`.
+///
+/// The `candidates` best n-gram matches are re-scored, and their final
+/// similarity is a blend of the n-gram similarity and the edit similarity,
+/// with the edit similarity carrying `edit_weight`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Rerank {
+    /// The edit metric used to re-score candidates
+    pub(crate) metric: EditMetric,
+    /// How many of the best n-gram candidates are re-scored
+    pub(crate) candidates: usize,
+    /// The weight of the edit similarity in the blended similarity, in the
+    /// range of 0.0 to 1.0
+    pub(crate) edit_weight: f32,
+}
+
+impl Rerank {
+    /// Re-rank the 50 best n-gram candidates with the given `metric`,
+    /// weighting the n-gram and edit similarities equally.
+    pub fn new(metric: EditMetric) -> Self {
+        Rerank {
+            metric,
+            candidates: 50,
+            edit_weight: 0.5,
+        }
+    }
+
+    /// Set how many of the best n-gram candidates are re-scored.
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    /// Set the weight of the edit similarity in the blended similarity
+    /// (clamped to the range 0.0 to 1.0).
+    pub fn edit_weight(mut self, edit_weight: f32) -> Self {
+        self.edit_weight = edit_weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Blend an n-gram similarity with an edit similarity.
+    pub(crate) fn blend(&self, ngram_similarity: f32, edit_similarity: f32) -> f32 {
+        // Deserialized settings skip the clamp of `edit_weight`
+        let edit_weight = self.edit_weight.clamp(0.0, 1.0);
+        ngram_similarity * (1.0 - edit_weight) + edit_similarity * edit_weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[test]
+    fn levenshtein_distances() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        let distance = |a: &str, b: &str| levenshtein(&chars(a), &chars(b), false);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("flaw", "lawn"), 2);
+        assert_eq!(distance("ca", "ac"), 2);
+    }
+
+    #[test]
+    fn damerau_levenshtein_distances() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        let distance = |a: &str, b: &str| levenshtein(&chars(a), &chars(b), true);
+        assert_eq!(distance("ca", "ac"), 1);
+        assert_eq!(distance("teh", "the"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        // optimal string alignment never edits a substring twice
+        assert_eq!(distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn edit_similarities() {
+        let levenshtein = EditMetric::Levenshtein;
+        assert!(approx_eq(levenshtein.similarity("kitten", "sitting"), 4.0 / 7.0));
+        assert!(approx_eq(levenshtein.similarity("", ""), 1.0));
+        assert!(approx_eq(levenshtein.similarity("\u{1f346}", "\u{1f60f}"), 0.0));
+    }
+
+    #[test]
+    fn jaro_winkler_similarities() {
+        let jaro_winkler = EditMetric::JaroWinkler;
+        assert!(approx_eq(jaro_winkler.similarity("MARTHA", "MARHTA"), 0.9611));
+        assert!(approx_eq(jaro_winkler.similarity("DWAYNE", "DUANE"), 0.84));
+        assert!(approx_eq(jaro_winkler.similarity("DIXON", "DICKSONX"), 0.8133));
+        assert!(approx_eq(jaro_winkler.similarity("abc", "xyz"), 0.0));
+        assert!(approx_eq(jaro_winkler.similarity("abc", "abc"), 1.0));
+    }
+
+    #[test]
+    fn rerank_blend() {
+        let rerank = Rerank::new(EditMetric::Levenshtein);
+        assert!(approx_eq(rerank.blend(0.2, 0.8), 0.5));
+        assert!(approx_eq(rerank.edit_weight(5.0).blend(0.2, 0.8), 0.8));
+        // Settings that skipped the clamp, as deserialized ones can, still
+        // blend within range
+        let unclamped = Rerank {
+            edit_weight: 5.0,
+            ..rerank
+        };
+        assert!(approx_eq(unclamped.blend(0.2, 0.8), 0.8));
+    }
+}
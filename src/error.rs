@@ -0,0 +1,89 @@
+#![deny(missing_docs)]
+
+use std::fmt;
+use std::io;
+
+/// The ways in which ngrammatic operations can fail.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    /// A similarity threshold was outside the range of 0.0 to 1.0, or NaN.
+    InvalidThreshold(f32),
+    /// A warp factor was outside the range of 1.0 to 3.0, or not finite.
+    InvalidWarp(f32),
+    /// An arity (gram size) was 0.
+    InvalidArity(usize),
+    /// The weight of a gram size was negative or not finite, or all gram
+    /// sizes were weighted 0.0.
+    InvalidWeight(f32),
+    /// A `Pad::Pad` padding was empty. Use `Pad::None` for no padding.
+    InvalidPadding,
+    /// A padding length was not less than the arity of the grams it pads.
+    InvalidPadLength {
+        /// The number of padding characters
+        pad_len: usize,
+        /// The arity of the grams
+        arity: usize,
+    },
+    /// A text was too short to produce any grams with the configured arity
+    /// and padding.
+    EmptyGrams {
+        /// The text, after key transformation
+        text: String,
+    },
+    /// Reading input failed.
+    Io {
+        /// The kind of I/O error
+        kind: io::ErrorKind,
+        /// A description of the I/O error
+        message: String,
+    },
+    /// Input was not in the expected format.
+    Format {
+        /// The line of input the problem was found on, counting from 1
+        line: usize,
+        /// A description of the problem
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidThreshold(threshold) => write!(
+                f,
+                "invalid threshold {}: must be in the range 0.0 to 1.0",
+                threshold
+            ),
+            Error::InvalidWarp(warp) => {
+                write!(f, "invalid warp {}: must be in the range 1.0 to 3.0", warp)
+            }
+            Error::InvalidArity(arity) => write!(f, "invalid arity {}: must be at least 1", arity),
+            Error::InvalidWeight(weight) => write!(
+                f,
+                "invalid arity weight {}: must be finite and non-negative, and not all 0.0",
+                weight
+            ),
+            Error::InvalidPadding => write!(f, "invalid padding: is empty"),
+            Error::InvalidPadLength { pad_len, arity } => write!(
+                f,
+                "invalid padding length {}: must be less than the arity {}",
+                pad_len, arity
+            ),
+            Error::EmptyGrams { text } => write!(f, "text {:?} produces no grams", text),
+            Error::Io { message, .. } => write!(f, "I/O error: {}", message),
+            Error::Format { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+#![deny(missing_docs)]
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Corpus, Error, KeyTransformer, Ngram, Rerank, Scoring, SearchOptions};
+
+/// How the similarity of a candidate text to a query was computed, from the
+/// grams they share. Returned by `Ngram::explain` and `Corpus::explain`.
+///
+/// Gram lists are ordered by gram, and count repeated grams as many times as
+/// they occur. For positional ngrams, occurrences of a gram too far apart
+/// count as found on only one side.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Explanation {
+    /// The query text, after key transformation
+    pub query: String,
+    /// The candidate text, after key transformation
+    pub candidate: String,
+    /// The query text, with the padding used for generating its grams
+    pub query_padded: String,
+    /// The candidate text, with the padding used for generating its grams
+    pub candidate_padded: String,
+    /// The grams of both texts, with the number of occurrences they share
+    pub shared: Vec<(String, usize)>,
+    /// The grams of the query not shared with the candidate, with the
+    /// number of their occurrences
+    pub query_only: Vec<(String, usize)>,
+    /// The grams of the candidate not shared with the query, with the
+    /// number of their occurrences
+    pub candidate_only: Vec<(String, usize)>,
+    /// The number of shared gram occurrences
+    pub samegram_count: usize,
+    /// The number of gram occurrences of both texts, counting shared ones
+    /// once
+    pub allgram_count: usize,
+    /// When grams of several sizes were generated, the similarity of each
+    /// size, whose weighted mean is the n-gram similarity. Empty otherwise.
+    pub arities: Vec<ArityExplanation>,
+    /// The warp factor applied to the n-gram similarity
+    pub warp: f32,
+    /// How the similarity was computed from the grams of the texts. With
+    /// weighted scoring, the `warp` isn't applied.
+    pub scoring: Scoring,
+    /// The similarity of the grams of the texts, as computed by the
+    /// `scoring`
+    pub ngram_similarity: f32,
+    /// When re-ranking with an edit metric, how the edit similarity was
+    /// blended with the n-gram similarity
+    pub rerank: Option<Rerank>,
+    /// When re-ranking with an edit metric, the edit similarity of the texts
+    pub edit_similarity: Option<f32>,
+    /// The final similarity, as a search would score the candidate
+    pub similarity: f32,
+}
+
+/// The part of an `Explanation` for grams of a single size, when grams of
+/// several sizes were generated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ArityExplanation {
+    /// The size of the grams
+    pub arity: usize,
+    /// The weight of this size's similarity
+    pub weight: f32,
+    /// The number of shared gram occurrences of this size
+    pub samegram_count: usize,
+    /// The number of gram occurrences of this size of both texts, counting
+    /// shared ones once
+    pub allgram_count: usize,
+    /// The similarity of the grams of this size, as computed by the
+    /// `scoring`
+    pub similarity: f32,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:?} vs {:?}: similarity {:.4}",
+            self.query, self.candidate, self.similarity
+        )?;
+        writeln!(
+            f,
+            "  padded: {:?} vs {:?}",
+            self.query_padded, self.candidate_padded
+        )?;
+        write!(f, "  metric: ")?;
+        match self.scoring {
+            Scoring::Ngram => write!(f, "n-gram similarity with warp {}", self.warp)?,
+            Scoring::WeightedJaccard => write!(f, "IDF weighted Jaccard similarity")?,
+            Scoring::WeightedCosine => write!(f, "IDF weighted cosine similarity")?,
+        }
+        if let Some(rerank) = self.rerank {
+            write!(
+                f,
+                ", blended with {:?} edit similarity weighted {}",
+                rerank.metric, rerank.edit_weight
+            )?;
+        }
+        writeln!(f)?;
+        write_grams(f, "shared", &self.shared)?;
+        write_grams(f, "query only", &self.query_only)?;
+        write_grams(f, "candidate only", &self.candidate_only)?;
+        writeln!(
+            f,
+            "  n-gram similarity: {:.4} ({} same of {} grams)",
+            self.ngram_similarity, self.samegram_count, self.allgram_count
+        )?;
+        for size in &self.arities {
+            writeln!(
+                f,
+                "    arity {} weighted {}: {:.4} ({} same of {} grams)",
+                size.arity, size.weight, size.similarity, size.samegram_count, size.allgram_count
+            )?;
+        }
+        if let Some(edit_similarity) = self.edit_similarity {
+            writeln!(f, "  edit similarity: {:.4}", edit_similarity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a line listing the `grams` with their counts, after a `label`.
+fn write_grams(f: &mut fmt::Formatter, label: &str, grams: &[(String, usize)]) -> fmt::Result {
+    write!(f, "  {}:", label)?;
+    if grams.is_empty() {
+        write!(f, " none")?;
+    }
+    for (gram, count) in grams {
+        write!(f, " {:?}", gram)?;
+        if *count > 1 {
+            write!(f, "x{}", count)?;
+        }
+    }
+    writeln!(f)
+}
+
+impl Ngram {
+    /// Explain the similarity of the `other` `Ngram` to this one, with a
+    /// warp of 2.0, as `matches` would compute it.
+    /// ```rust
+    /// # use ngrammatic::NgramBuilder;
+    /// # fn main() {
+    /// let a = NgramBuilder::new("tomacco").finish();
+    /// let b = NgramBuilder::new("tomato").finish();
+    /// let explanation = a.explain(&b);
+    /// assert_eq!(explanation.similarity, a.similarity_to(&b, 2.0));
+    /// assert_eq!(explanation.samegram_count, 5);
+    /// assert_eq!(explanation.shared[0], (" t".to_string(), 1));
+    /// println!("{}", explanation);
+    /// # }
+    /// ```
+    pub fn explain(&self, other: &Ngram) -> Explanation {
+        self.explain_with_warp(other, 2.0)
+    }
+
+    /// Explain the similarity of the `other` `Ngram` to this one, with the
+    /// given `warp` factor (clamped to the range 1.0 to 3.0).
+    pub fn explain_with_warp(&self, other: &Ngram, warp: f32) -> Explanation {
+        let mut shared = Vec::new();
+        let mut query_only = Vec::new();
+        for (gram, &count) in &self.grams {
+            let same = self.count_shared(other, gram);
+            if same > 0 {
+                shared.push((gram.to_string(), same));
+            }
+            if count > same {
+                query_only.push((gram.to_string(), count - same));
+            }
+        }
+        let mut candidate_only = Vec::new();
+        for (gram, &count) in &other.grams {
+            let same = other.count_shared(self, gram);
+            if count > same {
+                candidate_only.push((gram.to_string(), count - same));
+            }
+        }
+        for grams in [&mut shared, &mut query_only, &mut candidate_only] {
+            grams.sort();
+        }
+
+        let counts = self.count_sized_grams(other);
+        let arities = self
+            .arities
+            .iter()
+            .zip(counts)
+            .map(|(&(arity, weight), counts)| {
+                let (samegram_count, allgram_count) = counts;
+                ArityExplanation {
+                    arity,
+                    weight,
+                    samegram_count,
+                    allgram_count,
+                    similarity: Ngram::similarity(samegram_count, allgram_count, warp),
+                }
+            })
+            .collect();
+        let similarity = self.similarity_to(other, warp);
+        Explanation {
+            query: self.text.to_string(),
+            candidate: other.text.to_string(),
+            query_padded: self.text_padded.to_string(),
+            candidate_padded: other.text_padded.to_string(),
+            samegram_count: self.count_samegrams(other),
+            allgram_count: self.count_allgrams(other),
+            shared,
+            query_only,
+            candidate_only,
+            arities,
+            warp: warp.clamp(1.0, 3.0),
+            scoring: Scoring::Ngram,
+            ngram_similarity: similarity,
+            rerank: None,
+            edit_similarity: None,
+            similarity,
+        }
+    }
+}
+
+impl<KT> Corpus<KT>
+where
+    KT: KeyTransformer + Sync,
+{
+    /// Explain the similarity of the `candidate` text to the `query`, as
+    /// `search` would score it. The `candidate` doesn't need to be in the
+    /// `Corpus`, and both texts are key transformed.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .case_insensitive()
+    ///     .fill(vec!["Tomato", "potato"])
+    ///     .finish();
+    /// let result = &corpus.search("tomacco", 0.25, 10)[0];
+    /// let explanation = corpus.explain("tomacco", &result.text);
+    /// assert_eq!(explanation.similarity, result.similarity);
+    /// assert_eq!(explanation.candidate_padded, " tomato ");
+    /// assert!(explanation.to_string().contains("shared: \" t\" \"ma\" \"o \" \"om\" \"to\""));
+    /// # }
+    /// ```
+    pub fn explain(&self, query: &str, candidate: &str) -> Explanation {
+        self.explain_unchecked(query, candidate, &SearchOptions::new())
+    }
+
+    /// Explain the similarity of the `candidate` text to the `query`, as
+    /// `search_with` would score it with the given `options`, including
+    /// their warp, scoring and any re-ranking. Fails if the options are invalid.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, EditMetric, Rerank, SearchOptions};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let corpus = CorpusBuilder::default().fill(vec!["abce", "abcdxyz"]).finish();
+    /// let options = SearchOptions::new().rerank(Rerank::new(EditMetric::Levenshtein));
+    /// let explanation = corpus.explain_with("abcd", "abce", &options)?;
+    /// assert_eq!(explanation.edit_similarity, Some(0.75));
+    /// assert_eq!(explanation.similarity, corpus.search_with("abcd", &options)?[0].similarity);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn explain_with(
+        &self,
+        query: &str,
+        candidate: &str,
+        options: &SearchOptions,
+    ) -> Result<Explanation, Error> {
+        options.validate()?;
+        Ok(self.explain_unchecked(query, candidate, options))
+    }
+
+    fn explain_unchecked(
+        &self,
+        query: &str,
+        candidate: &str,
+        options: &SearchOptions,
+    ) -> Explanation {
+        let query = self.build_ngram(&self.transform_key(query));
+        let candidate = self.build_ngram(&self.transform_key(candidate));
+        let mut explanation = query.explain_with_warp(&candidate, options.warp);
+        if options.scoring != Scoring::Ngram {
+            let idf = |gram: &str| self.inverse_document_frequency(gram);
+            for size in explanation.arities.iter_mut() {
+                size.similarity =
+                    options
+                        .scoring
+                        .weighted_similarity(&query, &candidate, Some(size.arity), &idf);
+            }
+            explanation.ngram_similarity =
+                options
+                    .scoring
+                    .similarity(&query, &candidate, options.warp, idf);
+            explanation.similarity = explanation.ngram_similarity;
+            explanation.scoring = options.scoring;
+        }
+        if let Some(rerank) = options.rerank {
+            let edit_similarity = rerank.metric.similarity(&query.text, &candidate.text);
+            explanation.similarity = rerank.blend(explanation.ngram_similarity, edit_similarity);
+            explanation.edit_similarity = Some(edit_similarity);
+            explanation.rerank = Some(rerank);
+        }
+        explanation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CorpusBuilder, NgramBuilder, Pad};
+
+    #[test]
+    fn explain_counts() {
+        let a = NgramBuilder::new("banana")
+            .arity(2)
+            .pad_full(Pad::None)
+            .finish();
+        let b = NgramBuilder::new("bandana")
+            .arity(2)
+            .pad_full(Pad::None)
+            .finish();
+        let explanation = a.explain_with_warp(&b, 1.0);
+        let grams = |grams: &[(&str, usize)]| {
+            grams
+                .iter()
+                .map(|&(gram, count)| (gram.to_string(), count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            explanation.shared,
+            grams(&[("an", 2), ("ba", 1), ("na", 1)])
+        );
+        assert_eq!(explanation.query_only, grams(&[("na", 1)]));
+        assert_eq!(explanation.candidate_only, grams(&[("da", 1), ("nd", 1)]));
+        assert_eq!(explanation.samegram_count, 4);
+        assert_eq!(explanation.allgram_count, 7);
+        assert_eq!(explanation.similarity, 4.0 / 7.0);
+        assert_eq!(explanation.similarity, a.similarity_to(&b, 1.0));
+        assert!(explanation.arities.is_empty());
+        assert_eq!(
+            explanation.to_string(),
+            "\"banana\" vs \"bandana\": similarity 0.5714\n  \
+             padded: \"banana\" vs \"bandana\"\n  \
+             metric: n-gram similarity with warp 1\n  \
+             shared: \"an\"x2 \"ba\" \"na\"\n  \
+             query only: \"na\"\n  \
+             candidate only: \"da\" \"nd\"\n  \
+             n-gram similarity: 0.5714 (4 same of 7 grams)\n"
+        );
+    }
+
+    #[test]
+    fn explain_matches_search() {
+        let corpus = CorpusBuilder::default()
+            .arities(1..=3)
+            .positional(2)
+            .fill(vec!["tomato", "potato", "tornado", "tomatoes"])
+            .finish();
+        let options = SearchOptions::new().warp(1.5);
+        for result in corpus.search_with("tomacco", &options).unwrap() {
+            let explanation = corpus
+                .explain_with("tomacco", &result.text, &options)
+                .unwrap();
+            assert_eq!(explanation.similarity, result.similarity);
+            assert_eq!(explanation.arities.len(), 3);
+            let weighted: f32 = explanation.arities.iter().map(|size| size.similarity).sum();
+            assert!((weighted / 3.0 - explanation.similarity).abs() < 1e-6);
+        }
+        assert_eq!(
+            corpus.explain_with("tomacco", "tomato", &SearchOptions::new().warp(5.0)),
+            Err(Error::InvalidWarp(5.0))
+        );
+        let options = options.scoring(Scoring::WeightedCosine);
+        let result = &corpus.search_with("tomacco", &options).unwrap()[0];
+        let explanation = corpus
+            .explain_with("tomacco", &result.text, &options)
+            .unwrap();
+        assert_eq!(explanation.similarity, result.similarity);
+        assert!(explanation
+            .to_string()
+            .contains("metric: IDF weighted cosine similarity\n"));
+
+        let unrelated = corpus.explain("xyz", "tomato");
+        assert!(unrelated.shared.is_empty());
+        assert_eq!(unrelated.similarity, 0.0);
+        assert!(unrelated.to_string().contains("shared: none"));
+    }
+}
@@ -1,41 +1,119 @@
 #![doc = include_str!("../README.md")]
 #![deny(missing_docs)]
 
+use std::cell::RefCell;
 use std::cmp::Ordering;
 
+use crate::search::compare_similarity;
+
+pub mod compat;
+mod compare;
+mod concurrent;
 mod corpus;
+mod edit;
+mod error;
+mod explain;
 mod ngram;
+mod persist;
+mod phonetic;
+mod postings;
+mod prune;
+mod reader;
+mod record;
+mod scan;
+mod scoring;
+mod search;
+#[cfg(feature = "server")]
+mod server;
+mod sharded;
+mod stats;
 
+pub use crate::compare::{compare, CompareConfig, CompareMetric, Comparer};
+pub use crate::concurrent::ConcurrentCorpus;
 pub use crate::corpus::{Corpus, CorpusBuilder};
-pub use crate::ngram::{Ngram, NgramBuilder};
+pub use crate::edit::{EditMetric, Rerank};
+pub use crate::error::Error;
+pub use crate::explain::{ArityExplanation, Explanation};
+pub use crate::ngram::{Ngram, NgramBuilder, SkipGrams};
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use crate::postings::{PostingIter, PostingList};
+pub use crate::phonetic::{
+    MetaphoneKeyTransformer, NysiisKeyTransformer, PhoneticCorpus, SoundexKeyTransformer,
+};
+pub use crate::prune::StopGrams;
+pub use crate::reader::{Column, DelimitedOptions, ReadOptions, Splitter};
+pub use crate::record::{RecordCorpus, RecordCorpusBuilder, RecordSearchResult};
+pub use crate::scan::{ScanMatch, ScanOptions, Scanner};
+pub use crate::scoring::Scoring;
+pub use crate::search::{SearchOptions, TieBreak};
+#[cfg(feature = "server")]
+pub use crate::server::{SearchServer, ShutdownHandle};
+pub use crate::sharded::ShardedCorpus;
+pub use crate::stats::{CorpusStats, HeapStats, PostingStats};
 
 /// Holds a fuzzy match search result string, and its associated similarity
 /// to the query text.
+///
+/// Search results are ordered by their similarity, with a NaN similarity
+/// below every other, and results with equal similarity by their text, with
+/// the lexicographically earliest text being the greatest. Sorting results in
+/// descending order therefore puts the most similar first, and breaks ties
+/// alphabetically. The component scores don't take part in comparisons.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub struct SearchResult {
     /// The text of a fuzzy match
     pub text: String,
     /// A similarity value indicating how closely the other term matched
     pub similarity: f32,
+    /// The n-gram similarity of the literal text, before it was blended with
+    /// any other score. Equal to `similarity` for plain n-gram searches.
+    pub ngram_similarity: f32,
+    /// The n-gram similarity of the phonetic codes, for searches of a
+    /// `PhoneticCorpus`.
+    pub phonetic_similarity: Option<f32>,
+    /// The edit similarity of the text, for searches re-ranked with an
+    /// `EditMetric`.
+    pub edit_similarity: Option<f32>,
+    /// Whether some candidates of the search were left out by pruning stop
+    /// grams. See `StopGrams`.
+    pub pruned: bool,
+}
+
+impl Ord for SearchResult {
+    fn cmp(&self, other: &SearchResult) -> Ordering {
+        compare_similarity(self.similarity, other.similarity)
+            .then_with(|| other.text.cmp(&self.text))
+    }
 }
 
 impl PartialOrd for SearchResult {
     fn partial_cmp(&self, other: &SearchResult) -> Option<Ordering> {
-        self.similarity.partial_cmp(&other.similarity)
+        Some(self.cmp(other))
     }
 }
 
 impl PartialEq for SearchResult {
     fn eq(&self, other: &SearchResult) -> bool {
-        self.similarity == other.similarity
+        self.cmp(other) == Ordering::Equal
     }
 }
 
+impl Eq for SearchResult {}
+
 impl SearchResult {
     /// Trivial constructor used internally to build search results
     pub(crate) fn new(text: String, similarity: f32) -> Self {
-        SearchResult { text, similarity }
+        SearchResult {
+            text,
+            similarity,
+            ngram_similarity: similarity,
+            phonetic_similarity: None,
+            edit_similarity: None,
+            pruned: false,
+        }
     }
 }
 
@@ -101,6 +179,22 @@ pub trait KeyTransformer {
     /// * `key` - The key to transform.
     fn transform(&self, key: &str) -> String;
 
+    /// Transform a key, appending the result to `out`. Key transformers can
+    /// implement this to avoid allocating a `String` for each key.
+    ///
+    /// # Arguments
+    /// * `key` - The key to transform.
+    /// * `out` - The string to append the transformed key to.
+    fn transform_into(&self, key: &str, out: &mut String) {
+        out.push_str(&self.transform(key));
+    }
+
+    /// Whether this key transformer lowercases keys. Saved corpora record
+    /// it, so that `Corpus::load_with` can pick a key transformer to match.
+    fn lowercases(&self) -> bool {
+        false
+    }
+
     /// Link this key transformer to another.
     ///
     /// # Arguments
@@ -130,6 +224,10 @@ impl KeyTransformer for IdentityKeyTransformer {
     fn transform(&self, key: &str) -> String {
         key.to_string()
     }
+
+    fn transform_into(&self, key: &str, out: &mut String) {
+        out.push_str(key);
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
@@ -141,6 +239,45 @@ impl KeyTransformer for LowerKeyTransformer {
     fn transform(&self, key: &str) -> String {
         key.to_lowercase()
     }
+
+    fn transform_into(&self, key: &str, out: &mut String) {
+        // Lowercasing all of a non-ASCII string handles context sensitive
+        // mappings, like a final sigma
+        if key.is_ascii() {
+            out.extend(key.chars().map(|c| c.to_ascii_lowercase()));
+        } else {
+            out.push_str(&key.to_lowercase());
+        }
+    }
+
+    fn lowercases(&self) -> bool {
+        true
+    }
+}
+
+/// An optional key transformer leaves keys unmodified when `None`, so whether
+/// keys are transformed can be chosen at run time.
+impl<KT> KeyTransformer for Option<KT>
+where
+    KT: KeyTransformer,
+{
+    fn transform(&self, key: &str) -> String {
+        match self {
+            Some(key_trans) => key_trans.transform(key),
+            None => key.to_string(),
+        }
+    }
+
+    fn transform_into(&self, key: &str, out: &mut String) {
+        match self {
+            Some(key_trans) => key_trans.transform_into(key, out),
+            None => out.push_str(key),
+        }
+    }
+
+    fn lowercases(&self) -> bool {
+        self.as_ref().map_or(false, KeyTransformer::lowercases)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
@@ -170,4 +307,23 @@ where
     fn transform(&self, key: &str) -> String {
         self.dst.transform(&self.src.transform(key))
     }
+
+    fn transform_into(&self, key: &str, out: &mut String) {
+        // The intermediate key goes in a buffer reused by every linked key
+        // transformer on this thread, with one buffer per level of nesting
+        thread_local! {
+            static BUFFERS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        }
+        let mut buffer = BUFFERS
+            .with(|buffers| buffers.borrow_mut().pop())
+            .unwrap_or_default();
+        self.src.transform_into(key, &mut buffer);
+        self.dst.transform_into(&buffer, out);
+        buffer.clear();
+        BUFFERS.with(|buffers| buffers.borrow_mut().push(buffer));
+    }
+
+    fn lowercases(&self) -> bool {
+        self.src.lowercases() || self.dst.lowercases()
+    }
 }
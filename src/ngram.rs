@@ -7,7 +7,45 @@ use smol_str::SmolStr;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{Pad, SearchResult};
+use crate::{Error, Pad, SearchResult};
+
+/// Determines which gapped grams ("skip-grams") are generated for an `Ngram`.
+/// A skip-gram of arity _n_ is made of _n_ characters of the padded text, in
+/// order, with up to `max_skip` characters skipped between them in total, so
+/// that "t_e" from "the" yields the bigram "te". Skip-grams share keys with
+/// contiguous grams, which is what makes a transposition like "teh" for "the"
+/// keep most of its grams. Arity 1 grams have no gaps, and are unaffected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SkipGrams {
+    /// Only contiguous grams are generated.
+    None,
+    /// Skip-grams with up to the given number of skipped characters are
+    /// generated alongside the contiguous grams.
+    Alongside(usize),
+    /// Skip-grams with between one and the given number of skipped characters
+    /// are generated, instead of the contiguous grams.
+    Only(usize),
+}
+
+impl Default for SkipGrams {
+    /// Default is `None`, generating only contiguous grams.
+    fn default() -> Self {
+        SkipGrams::None
+    }
+}
+
+impl SkipGrams {
+    /// The smallest and largest number of characters skipped by the generated
+    /// grams.
+    fn skip_range(&self) -> (usize, usize) {
+        match *self {
+            SkipGrams::None => (0, 0),
+            SkipGrams::Alongside(max_skip) => (0, max_skip),
+            SkipGrams::Only(max_skip) => (1, max_skip),
+        }
+    }
+}
 
 /// Stores a "word", with all its n-grams. The "arity" member determines the
 /// value of "n" used in generating the n-grams.
@@ -24,11 +62,27 @@ pub struct Ngram {
     /// A collection of all generated ngrams for the text, with a
     /// count of how many times that ngram appears in the text
     pub grams: HashMap<SmolStr, usize>,
+    /// When grams of several sizes were generated, each of those sizes with
+    /// the weight its similarity carries, ordered by size. In that case
+    /// `arity` is the largest size, and `text_padded` holds the padding used
+    /// for it. Empty when only grams of size `arity` were generated.
+    pub arities: Vec<(usize, f32)>,
+    /// For positional ngrams, the maximum distance between the offsets of two
+    /// occurrences of a gram for them to count as shared. `None` when the
+    /// position of grams is ignored.
+    pub position_tolerance: Option<usize>,
+    /// For positional ngrams, the character offsets within `text_padded` at
+    /// which each gram occurs, in ascending order. Empty otherwise.
+    pub positions: HashMap<SmolStr, Vec<usize>>,
+    /// The skip-grams generated for the text.
+    pub skip_grams: SkipGrams,
 }
 
 impl PartialEq for Ngram {
     fn eq(&self, other: &Self) -> bool {
-        self.text_padded == other.text_padded && self.arity == other.arity
+        self.text_padded == other.text_padded
+            && self.arity == other.arity
+            && self.sizes().eq(other.sizes())
     }
 }
 impl Eq for Ngram {}
@@ -37,6 +91,9 @@ impl Hash for Ngram {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.text_padded.hash(state);
         self.arity.hash(state);
+        for size in self.sizes() {
+            size.hash(state);
+        }
     }
 }
 
@@ -69,11 +126,40 @@ impl Ngram {
     /// 100.0);
     /// # }
     /// ```
+    ///
+    /// When grams of several sizes were generated, this is the weighted mean
+    /// of the similarities computed separately for each size.
     pub fn similarity_to(&self, other: &Ngram, warp: f32) -> f32 {
         let warp = warp.clamp(1.0, 3.0);
-        let samegram_count = self.count_samegrams(other);
-        let allgram_count = self.count_allgrams(other);
-        Ngram::similarity(samegram_count, allgram_count, warp)
+        if self.arities.is_empty() {
+            let samegram_count = self.count_samegrams(other);
+            let allgram_count = self.count_allgrams(other);
+            return Ngram::similarity(samegram_count, allgram_count, warp);
+        }
+        let mut weighted = 0.0;
+        let mut total_weight = 0.0;
+        let counts = self.count_sized_grams(other);
+        for (&(_, weight), &(samegram_count, allgram_count)) in self.arities.iter().zip(&counts) {
+            weighted += weight * Ngram::similarity(samegram_count, allgram_count, warp);
+            total_weight += weight;
+        }
+        if total_weight > 0.0 {
+            weighted / total_weight
+        } else {
+            0.0
+        }
+    }
+
+    /// Iterate over the sizes of the grams generated for the text.
+    fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        let single = if self.arities.is_empty() {
+            Some(self.arity)
+        } else {
+            None
+        };
+        single
+            .into_iter()
+            .chain(self.arities.iter().map(|(arity, _)| *arity))
     }
 
     /// Determines if this `Ngram` matches a given `other` `Ngram`, for a given
@@ -135,6 +221,9 @@ impl Ngram {
     /// Returns the total number of unique grams between this
     /// `Ngram` and the `other` `Ngram`.
     pub(crate) fn count_allgrams(&self, other: &Ngram) -> usize {
+        if !self.arities.is_empty() || self.skip_grams != SkipGrams::None {
+            return self.count_grams() + other.count_grams() - self.count_samegrams(other);
+        }
         // This is a shortcut that counts all grams between both ngrams
         // Then subtracts out one instance of the grams that are in common
         let self_length = self.text_padded.chars().count();
@@ -151,13 +240,65 @@ impl Ngram {
     pub(crate) fn count_samegrams(&self, other: &Ngram) -> usize {
         let mut sames: usize = 0;
         for key in self.grams.keys() {
-            let selfcount = self.count_gram(key.as_ref());
-            let othercount = other.count_gram(key.as_ref());
-            sames += selfcount.min(othercount);
+            sames += self.count_shared(other, key);
         }
         sames
     }
 
+    /// Returns how many occurrences of `gram` are shared between this `Ngram`
+    /// and the `other` `Ngram`. For positional ngrams, occurrences are paired
+    /// up only when their offsets are within the `position_tolerance`.
+    pub(crate) fn count_shared(&self, other: &Ngram, gram: &str) -> usize {
+        let tolerance = match self.position_tolerance {
+            Some(tolerance) if !other.positions.is_empty() => tolerance,
+            _ => return self.count_gram(gram).min(other.count_gram(gram)),
+        };
+        let (ours, theirs) = match (self.positions.get(gram), other.positions.get(gram)) {
+            (Some(ours), Some(theirs)) => (ours, theirs),
+            _ => return 0,
+        };
+        // Both offset lists are sorted, so greedily pairing the earliest
+        // compatible occurrences yields the largest number of pairs.
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+        while i < ours.len() && j < theirs.len() {
+            if ours[i].abs_diff(theirs[j]) <= tolerance {
+                shared += 1;
+                i += 1;
+                j += 1;
+            } else if ours[i] < theirs[j] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        shared
+    }
+
+    /// Returns the count of common grams and the total number of unique
+    /// grams between this `Ngram` and the `other` `Ngram`, for each of the
+    /// `arities` of this `Ngram`, in the same order. The grams of both are
+    /// grouped by their size in a single pass.
+    pub(crate) fn count_sized_grams(&self, other: &Ngram) -> Vec<(usize, usize)> {
+        let index_of = |gram: &str| {
+            let size = gram.chars().count();
+            self.arities.iter().position(|(arity, _)| *arity == size)
+        };
+        let mut counts = vec![(0, 0); self.arities.len()];
+        for (gram, count) in &self.grams {
+            if let Some(index) = index_of(gram) {
+                let shared = self.count_shared(other, gram);
+                counts[index].0 += shared;
+                counts[index].1 += count - shared;
+            }
+        }
+        for (gram, count) in &other.grams {
+            if let Some(index) = index_of(gram) {
+                counts[index].1 += count;
+            }
+        }
+        counts
+    }
+
     /// Return the number of times a particular `gram` appears in the `Ngram`
     /// text.
     /// ```rust
@@ -203,18 +344,145 @@ impl Ngram {
     /// Private method that initializes an `Ngram` by calculating all of its
     /// grams.
     fn init(&mut self) {
-        if self.arity > self.text_padded.len() {
+        let text_padded = self.text_padded.clone();
+        self.add_grams(&text_padded, self.arity, 0);
+    }
+
+    /// Private method that counts all grams of size `arity` in the
+    /// `text_padded` supplied, which starts `offset` characters into the
+    /// `Ngram`'s own `text_padded`.
+    fn add_grams(&mut self, text_padded: &str, arity: usize, offset: usize) {
+        if arity > text_padded.len() {
+            return;
+        }
+        let chars_padded: Vec<char> = text_padded.chars().collect();
+        let (min_skip, max_skip) = match arity {
+            1 => (0, 0),
+            _ => self.skip_grams.skip_range(),
+        };
+        let mut tmp = String::with_capacity(arity);
+        if min_skip == 0 {
+            for (position, window) in chars_padded.windows(arity).enumerate() {
+                tmp.clear();
+                tmp.extend(window.iter());
+                self.add_gram(&tmp, offset + position);
+            }
+        }
+        if max_skip > 0 {
+            let mut picked = Vec::with_capacity(arity);
+            for start in 0..chars_padded.len() {
+                picked.clear();
+                picked.push(start);
+                self.add_skip_grams(&chars_padded, arity, max_skip, &mut picked, offset, &mut tmp);
+            }
+        }
+    }
+
+    /// Private method that recursively extends the character indices
+    /// `picked` so far into skip-grams of size `arity`, counting each one
+    /// that skips at least one and at most `max_skip` characters.
+    fn add_skip_grams(
+        &mut self,
+        chars: &[char],
+        arity: usize,
+        max_skip: usize,
+        picked: &mut Vec<usize>,
+        offset: usize,
+        tmp: &mut String,
+    ) {
+        let first = picked[0];
+        let last = picked[picked.len() - 1];
+        let skipped = last - first + 1 - picked.len();
+        if picked.len() == arity {
+            if skipped > 0 {
+                tmp.clear();
+                tmp.extend(picked.iter().map(|&i| chars[i]));
+                self.add_gram(tmp, offset + first);
+            }
             return;
         }
-        let chars_padded: Vec<char> = self.text_padded.chars().collect();
-        let mut tmp = String::with_capacity(self.arity);
-        for window in chars_padded.windows(self.arity) {
-            tmp.clear();
-            tmp.extend(window.iter());
-            let count = self.grams.entry(SmolStr::new(&tmp)).or_insert(0);
-            *count += 1;
+        for next in (last + 1)..chars.len().min(last + 2 + max_skip - skipped) {
+            picked.push(next);
+            self.add_skip_grams(chars, arity, max_skip, picked, offset, tmp);
+            picked.pop();
+        }
+    }
+
+    /// Private method that counts one occurrence of `gram`, found at
+    /// `position` within `text_padded`.
+    fn add_gram(&mut self, gram: &str, position: usize) {
+        let gram = SmolStr::new(gram);
+        if self.position_tolerance.is_some() {
+            self.positions
+                .entry(gram.clone())
+                .or_default()
+                .push(position);
+        }
+        let count = self.grams.entry(gram).or_insert(0);
+        *count += 1;
+    }
+}
+
+/// Resolve a requested `arity` and set of weighted gram sizes into the arity
+/// and the sizes an `Ngram` is built with. Sizes are clamped to at least 1,
+/// negative weights to 0.0, duplicates keep the last weight given, and the
+/// sizes are ordered. When several sizes remain, the arity is the largest of
+/// them; otherwise the sizes are empty.
+pub(crate) fn resolve_arities(
+    arity: usize,
+    arities: &[(usize, f32)],
+) -> (usize, Vec<(usize, f32)>) {
+    let mut normalized: Vec<(usize, f32)> = Vec::new();
+    for &(arity, weight) in arities {
+        let arity = arity.max(1);
+        let weight = weight.max(0.0);
+        match normalized.iter_mut().find(|(a, _)| *a == arity) {
+            Some(existing) => existing.1 = weight,
+            None => normalized.push((arity, weight)),
+        }
+    }
+    normalized.sort_by_key(|(arity, _)| *arity);
+    let arity = match normalized.last() {
+        Some((largest, _)) => *largest,
+        None => arity.max(1),
+    };
+    if normalized.len() <= 1 {
+        normalized.clear();
+    }
+    (arity, normalized)
+}
+
+/// Check a requested `arity`, set of weighted gram sizes, and padding, as
+/// given to a builder, reporting the first setting that would otherwise be
+/// silently corrected.
+pub(crate) fn check_settings(
+    arity: usize,
+    arities: &[(usize, f32)],
+    pad_left: &Pad,
+    pad_right: &Pad,
+) -> Result<(), Error> {
+    if arities.is_empty() && arity == 0 {
+        return Err(Error::InvalidArity(arity));
+    }
+    for &(arity, weight) in arities {
+        if arity == 0 {
+            return Err(Error::InvalidArity(arity));
+        }
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(Error::InvalidWeight(weight));
         }
     }
+    if !arities.is_empty() && arities.iter().all(|(_, weight)| *weight == 0.0) {
+        return Err(Error::InvalidWeight(0.0));
+    }
+    for pad in [pad_left, pad_right] {
+        if let Pad::Pad(padding) = pad {
+            if padding.is_empty() {
+                return Err(Error::InvalidPadding);
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Build an `Ngram`, one setting at a time.
@@ -225,6 +493,9 @@ impl Ngram {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NgramBuilder {
     arity: usize,
+    arities: Vec<(usize, f32)>,
+    position_tolerance: Option<usize>,
+    skip_grams: SkipGrams,
     pad_left: Pad,
     pad_right: Pad,
     text: String,
@@ -245,6 +516,9 @@ impl NgramBuilder {
     pub fn new(text: &str) -> Self {
         NgramBuilder {
             arity: 2,
+            arities: Vec::new(),
+            position_tolerance: None,
+            skip_grams: SkipGrams::None,
             pad_left: Pad::Auto,
             pad_right: Pad::Auto,
             text: text.to_string(),
@@ -314,11 +588,80 @@ impl NgramBuilder {
     /// # }
     /// ```
     pub fn arity(mut self, arity: usize) -> Self {
-        self.arity = arity.max(1);
+        self.arity = arity;
+        self.arities.clear();
+        self
+    }
+
+    /// Generate grams of every size in `arities` (e.g. a range such as
+    /// `1..=3`, or a set such as `[2, 4]`) for the resulting `Ngram`, with
+    /// their similarities weighted equally.
+    /// ```rust
+    /// # use ngrammatic::NgramBuilder;
+    /// # fn main() {
+    /// let a = NgramBuilder::new("tomato").arities(2..=3).finish();
+    /// assert!(a.contains("to"));
+    /// assert!(a.contains("tom"));
+    /// # }
+    /// ```
+    pub fn arities<It>(self, arities: It) -> Self
+    where
+        It: IntoIterator<Item = usize>,
+    {
+        self.weighted_arities(arities.into_iter().map(|arity| (arity, 1.0)))
+    }
+
+    /// Generate grams of every size in `arities` for the resulting `Ngram`,
+    /// each paired with the weight its similarity carries when comparing
+    /// `Ngram`s.
+    /// ```rust
+    /// # use ngrammatic::NgramBuilder;
+    /// # fn main() {
+    /// let a = NgramBuilder::new("tomato").weighted_arities(vec![(2, 1.0), (3, 2.0)]).finish();
+    /// assert_eq!(a.arities, vec![(2, 1.0), (3, 2.0)]);
+    /// # }
+    /// ```
+    pub fn weighted_arities<It>(mut self, arities: It) -> Self
+    where
+        It: IntoIterator<Item = (usize, f32)>,
+    {
+        self.arities = arities.into_iter().collect();
+        self
+    }
+
+    /// Record the offset of every gram in the resulting `Ngram`, so that when
+    /// comparing it to other positional `Ngram`s, two occurrences of a gram
+    /// only count as shared if their offsets are at most `max_distance`
+    /// characters apart.
+    /// ```rust
+    /// # use ngrammatic::{NgramBuilder, Pad};
+    /// # fn main() {
+    /// let a = NgramBuilder::new("listen").arity(1).pad_full(Pad::None).positional(1).finish();
+    /// let b = NgramBuilder::new("silent").arity(1).pad_full(Pad::None).positional(1).finish();
+    /// assert!(a.similarity_to(&b, 2.0) < 1.0);
+    /// # }
+    /// ```
+    pub fn positional(mut self, max_distance: usize) -> Self {
+        self.position_tolerance = Some(max_distance);
+        self
+    }
+
+    /// Set which skip-grams to generate for the resulting `Ngram`.
+    /// ```rust
+    /// # use ngrammatic::{NgramBuilder, SkipGrams};
+    /// # fn main() {
+    /// let a = NgramBuilder::new("the").arity(2).skip_grams(SkipGrams::Alongside(1)).finish();
+    /// assert!(a.contains("th"));
+    /// assert!(a.contains("te"));
+    /// # }
+    /// ```
+    pub fn skip_grams(mut self, skip_grams: SkipGrams) -> Self {
+        self.skip_grams = skip_grams;
         self
     }
 
     /// Yield an `Ngram` instance with all the properties set with this builder.
+    /// An arity of 0 is treated as 1.
     /// ```rust
     /// # use ngrammatic::NgramBuilder;
     /// # fn main() {
@@ -329,15 +672,62 @@ impl NgramBuilder {
     /// # }
     /// ```
     pub fn finish(self) -> Ngram {
+        let (arity, arities) = resolve_arities(self.arity, &self.arities);
         let mut ngram = Ngram {
-            arity: self.arity,
+            arity,
             text: SmolStr::new(&self.text),
-            text_padded: SmolStr::new(Pad::pad_text(&self.text, self.pad_left, self.pad_right, self.arity - 1)),
+            text_padded: SmolStr::new(Pad::pad_text(
+                &self.text,
+                self.pad_left.clone(),
+                self.pad_right.clone(),
+                arity - 1,
+            )),
             grams: HashMap::new(),
+            arities: Vec::new(),
+            position_tolerance: self.position_tolerance,
+            positions: HashMap::new(),
+            skip_grams: self.skip_grams,
         };
         ngram.init();
+        let largest_pad = self.pad_left.to_string(arity - 1).chars().count();
+        for &(size, _) in arities.iter().filter(|(size, _)| *size != arity) {
+            let text_padded =
+                Pad::pad_text(&self.text, self.pad_left.clone(), self.pad_right.clone(), size - 1);
+            // Keep offsets relative to the padding of the largest arity
+            let offset = largest_pad - self.pad_left.to_string(size - 1).chars().count();
+            ngram.add_grams(&text_padded, size, offset);
+        }
+        ngram.arities = arities;
         ngram
     }
+
+    /// Yield an `Ngram` instance with all the properties set with this
+    /// builder, or an error if any of them is invalid, or if the text is too
+    /// short to produce any grams.
+    /// ```rust
+    /// # use ngrammatic::{Error, NgramBuilder, Pad};
+    /// # fn main() {
+    /// assert!(NgramBuilder::new("tomato").arity(3).try_finish().is_ok());
+    /// assert_eq!(
+    ///     NgramBuilder::new("tomato").arity(0).try_finish(),
+    ///     Err(Error::InvalidArity(0))
+    /// );
+    /// assert_eq!(
+    ///     NgramBuilder::new("to").arity(3).pad_full(Pad::None).try_finish(),
+    ///     Err(Error::EmptyGrams { text: "to".to_string() })
+    /// );
+    /// # }
+    /// ```
+    pub fn try_finish(self) -> Result<Ngram, Error> {
+        check_settings(self.arity, &self.arities, &self.pad_left, &self.pad_right)?;
+        let ngram = self.finish();
+        if ngram.is_empty() {
+            return Err(Error::EmptyGrams {
+                text: ngram.text.to_string(),
+            });
+        }
+        Ok(ngram)
+    }
 }
 
 #[cfg(test)]
@@ -455,6 +845,129 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn mixed_arity_grams() {
+        let ngram = NgramBuilder::new("ab").arities(1..=3).finish();
+        assert_eq!(ngram.arity, 3);
+        assert_eq!(ngram.text_padded, "  ab  ");
+        for gram in ["a", "b", " a", "ab", "b ", "  a", " ab", "ab ", "b  "] {
+            assert!(ngram.contains(gram), "missing {:?}", gram);
+        }
+        assert!(!ngram.contains(" "));
+        assert_eq!(ngram.count_grams(), 2 + 3 + 4);
+        let mut other = NgramBuilder::new("ab").arity(2).finish();
+        let counts = ngram.count_sized_grams(&other);
+        assert_eq!(counts, vec![(0, 2), (3, 3), (0, 4)]);
+        // Grams changed after building are taken into account
+        other.grams.remove(" a");
+        let counts = ngram.count_sized_grams(&other);
+        assert_eq!(counts, vec![(0, 2), (2, 3), (0, 4)]);
+    }
+
+    #[test]
+    fn mixed_arity_single_size() {
+        let ngram = NgramBuilder::new("ab").arities(vec![3, 3]).finish();
+        assert_eq!(ngram, NgramBuilder::new("ab").arity(3).finish());
+        assert!(ngram.arities.is_empty());
+    }
+
+    #[test]
+    fn mixed_arity_similarity() {
+        let a = NgramBuilder::new("ab").arities(1..=2).pad_full(Pad::None).finish();
+        let b = NgramBuilder::new("ba").arities(1..=2).pad_full(Pad::None).finish();
+        // unigrams all match, bigrams don't
+        assert!(float_approx_eq(a.similarity_to(&b, 1.0), 0.5, None));
+
+        let a = NgramBuilder::new("ab")
+            .weighted_arities(vec![(1, 3.0), (2, 1.0)])
+            .pad_full(Pad::None)
+            .finish();
+        assert!(float_approx_eq(a.similarity_to(&b, 1.0), 0.75, None));
+    }
+
+    #[test]
+    fn positional_offsets() {
+        let ngram = NgramBuilder::new("abab").arity(2).positional(0).finish();
+        assert_eq!(ngram.positions.get("ab"), Some(&vec![1, 3]));
+        assert_eq!(ngram.positions.get(" a"), Some(&vec![0]));
+
+        let ngram = NgramBuilder::new("ab").arities(1..=2).positional(0).finish();
+        assert_eq!(ngram.positions.get("a"), Some(&vec![1]));
+        assert_eq!(ngram.positions.get(" a"), Some(&vec![0]));
+    }
+
+    #[test]
+    fn positional_similarity() {
+        let build = |text: &str, tolerance: Option<usize>| {
+            let builder = NgramBuilder::new(text).arity(1).pad_full(Pad::None);
+            match tolerance {
+                Some(tolerance) => builder.positional(tolerance).finish(),
+                None => builder.finish(),
+            }
+        };
+        let similarity = |tolerance| {
+            build("listen", tolerance).similarity_to(&build("silent", tolerance), 1.0)
+        };
+        assert!(float_approx_eq(similarity(None), 1.0, None));
+        // only the "i" lines up exactly
+        assert!(float_approx_eq(similarity(Some(0)), 1.0 / 11.0, None));
+        // every letter is within two characters of its counterpart
+        assert!(float_approx_eq(similarity(Some(2)), 1.0, None));
+    }
+
+    #[test]
+    fn skip_grams_alongside() {
+        let ngram = NgramBuilder::new("abcd")
+            .arity(2)
+            .pad_full(Pad::None)
+            .skip_grams(SkipGrams::Alongside(1))
+            .finish();
+        for gram in ["ab", "bc", "cd", "ac", "bd"] {
+            assert!(ngram.contains(gram), "missing {:?}", gram);
+        }
+        assert!(!ngram.contains("ad"));
+        assert_eq!(ngram.count_grams(), 5);
+    }
+
+    #[test]
+    fn skip_grams_only() {
+        let ngram = NgramBuilder::new("abcde")
+            .arity(3)
+            .pad_full(Pad::None)
+            .skip_grams(SkipGrams::Only(2))
+            .positional(0)
+            .finish();
+        let mut grams: Vec<&str> = ngram.grams.keys().map(|g| g.as_str()).collect();
+        grams.sort();
+        assert_eq!(grams, vec!["abd", "abe", "acd", "ace", "ade", "bce", "bde"]);
+        assert_eq!(ngram.positions.get("bce"), Some(&vec![1]));
+    }
+
+    #[test]
+    fn skip_grams_unigrams_unaffected() {
+        let plain = NgramBuilder::new("abc").arity(1).finish();
+        let skipped = NgramBuilder::new("abc")
+            .arity(1)
+            .skip_grams(SkipGrams::Only(2))
+            .finish();
+        assert_eq!(plain.grams, skipped.grams);
+    }
+
+    #[test]
+    fn skip_grams_transposition() {
+        let build = |text: &str, skip_grams| {
+            NgramBuilder::new(text).skip_grams(skip_grams).finish()
+        };
+        let similarity = |a: &str, b: &str, skip_grams| {
+            build(a, skip_grams).similarity_to(&build(b, skip_grams), 2.0)
+        };
+        let skip_grams = SkipGrams::None;
+        assert!(similarity("teh", "the", skip_grams) < similarity("teh", "ten", skip_grams));
+        let skip_grams = SkipGrams::Alongside(1);
+        assert!(similarity("teh", "the", skip_grams) > similarity("teh", "ten", skip_grams));
+        assert!(float_approx_eq(similarity("teh", "the", skip_grams), 65.0 / 81.0, None));
+    }
+
     #[test]
     fn similarity_completelydifferent() {
         let ngram0 = NgramBuilder::new("ab").arity(2).finish();
@@ -465,4 +978,42 @@ mod tests {
             None,
         ));
     }
+
+    #[test]
+    fn try_finish_invalid_settings() {
+        let builder = || NgramBuilder::new("tomato");
+        assert_eq!(builder().arity(0).try_finish(), Err(Error::InvalidArity(0)));
+        assert_eq!(
+            builder().arities(vec![0, 2]).try_finish(),
+            Err(Error::InvalidArity(0))
+        );
+        assert_eq!(
+            builder().weighted_arities(vec![(1, -1.0), (2, 1.0)]).try_finish(),
+            Err(Error::InvalidWeight(-1.0))
+        );
+        assert_eq!(
+            builder().weighted_arities(vec![(1, 0.0), (2, 0.0)]).try_finish(),
+            Err(Error::InvalidWeight(0.0))
+        );
+        assert_eq!(
+            builder().pad_left(Pad::Pad(String::new())).try_finish(),
+            Err(Error::InvalidPadding)
+        );
+        // Settings corrected later are valid
+        assert!(builder().arity(0).arities(1..=2).try_finish().is_ok());
+        // finish() still silently corrects them
+        assert_eq!(builder().arity(0).finish().arity, 1);
+    }
+
+    #[test]
+    fn try_finish_empty_grams() {
+        let result = NgramBuilder::new("ab").arity(3).pad_full(Pad::None).try_finish();
+        assert_eq!(
+            result,
+            Err(Error::EmptyGrams {
+                text: "ab".to_string()
+            })
+        );
+        assert!(NgramBuilder::new("ab").arity(3).try_finish().is_ok());
+    }
 }
@@ -0,0 +1,365 @@
+#![deny(missing_docs)]
+
+use std::io::{BufRead, Write};
+
+use crate::ngram::SkipGrams;
+use crate::{Error, Pad, StopGrams};
+
+/// The first line of every saved corpus, identifying the format and its
+/// version.
+const MAGIC: &str = "ngrammatic-corpus 1";
+
+/// The settings of a saved corpus, which determine how its grams are
+/// generated.
+#[derive(Debug, Clone)]
+pub(crate) struct Settings {
+    pub(crate) arity: usize,
+    pub(crate) arities: Vec<(usize, f32)>,
+    pub(crate) position_tolerance: Option<usize>,
+    pub(crate) skip_grams: SkipGrams,
+    pub(crate) stop_grams: StopGrams,
+    pub(crate) pad_left: Pad,
+    pub(crate) pad_right: Pad,
+    /// Whether the keys were lowercased
+    pub(crate) lowercase: bool,
+}
+
+/// An entry of a saved corpus.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SavedEntry {
+    /// The already transformed key of the entry
+    pub(crate) key: String,
+    /// The number of times the entry was added
+    pub(crate) frequency: usize,
+    pub(crate) payload: Option<String>,
+}
+
+/// Write a corpus with the given `settings` and `entries`, in insertion
+/// order, to the `writer`.
+///
+/// The format is line based text: a line identifying the format, a line per
+/// setting, an `entries` line, and then a line per entry, holding its
+/// frequency, key, and optionally its payload, separated by tabs. Lines
+/// starting with `#` are comments.
+pub(crate) fn write_corpus<'a, W, It>(
+    mut writer: W,
+    settings: &Settings,
+    entries: It,
+) -> Result<(), Error>
+where
+    W: Write,
+    It: IntoIterator<Item = (&'a str, usize, Option<&'a str>)>,
+{
+    writeln!(writer, "{}", MAGIC)?;
+    writeln!(writer, "arity {}", settings.arity)?;
+    if !settings.arities.is_empty() {
+        let arities: Vec<String> = settings
+            .arities
+            .iter()
+            .map(|(arity, weight)| format!("{}={}", arity, weight))
+            .collect();
+        writeln!(writer, "arities {}", arities.join(" "))?;
+    }
+    if let Some(max_distance) = settings.position_tolerance {
+        writeln!(writer, "positional {}", max_distance)?;
+    }
+    match settings.skip_grams {
+        SkipGrams::None => {}
+        SkipGrams::Alongside(skip) => writeln!(writer, "skip_grams alongside {}", skip)?,
+        SkipGrams::Only(skip) => writeln!(writer, "skip_grams only {}", skip)?,
+    }
+    if settings.stop_grams != StopGrams::default() {
+        writeln!(writer, "stop_grams {}", format_stop_grams(&settings.stop_grams))?;
+    }
+    writeln!(writer, "pad_left {}", format_pad(&settings.pad_left))?;
+    writeln!(writer, "pad_right {}", format_pad(&settings.pad_right))?;
+    if settings.lowercase {
+        writeln!(writer, "lowercase")?;
+    }
+    writeln!(writer, "entries")?;
+    for (key, frequency, payload) in entries {
+        write!(writer, "{}\t{}", frequency, escape(key))?;
+        if let Some(payload) = payload {
+            write!(writer, "\t{}", escape(payload))?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the settings and entries of a corpus written by `write_corpus` from
+/// the `reader`.
+pub(crate) fn read_corpus<R>(mut reader: R) -> Result<(Settings, Vec<SavedEntry>), Error>
+where
+    R: BufRead,
+{
+    let mut settings = Settings {
+        arity: 2,
+        arities: Vec::new(),
+        position_tolerance: None,
+        skip_grams: SkipGrams::None,
+        stop_grams: StopGrams::default(),
+        pad_left: Pad::Auto,
+        pad_right: Pad::Auto,
+        lowercase: false,
+    };
+    let mut entries = Vec::new();
+    let mut in_entries = false;
+    let mut seen_magic = false;
+    let mut line = String::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        line_number += 1;
+        let content = line.trim_end_matches(&['\r', '\n'][..]);
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let format_error = |message: String| Error::Format {
+            line: line_number,
+            message,
+        };
+        if !seen_magic {
+            if content != MAGIC {
+                return Err(format_error("not a saved ngrammatic corpus".to_string()));
+            }
+            seen_magic = true;
+        } else if in_entries {
+            entries.push(parse_entry(content).map_err(format_error)?);
+        } else if content == "entries" {
+            in_entries = true;
+        } else {
+            parse_setting(content, &mut settings).map_err(format_error)?;
+        }
+    }
+    if !in_entries {
+        return Err(Error::Format {
+            line: line_number,
+            message: "missing entries".to_string(),
+        });
+    }
+    Ok((settings, entries))
+}
+
+/// Apply a single `name value` setting line to the `settings`.
+fn parse_setting(line: &str, settings: &mut Settings) -> Result<(), String> {
+    let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+    match name {
+        "arity" => settings.arity = parse_number(value)?,
+        "arities" => {
+            settings.arities = value
+                .split_whitespace()
+                .map(|pair| {
+                    let (arity, weight) = pair
+                        .split_once('=')
+                        .ok_or_else(|| format!("invalid arity weight {:?}", pair))?;
+                    let weight = weight
+                        .parse()
+                        .map_err(|_| format!("invalid arity weight {:?}", pair))?;
+                    Ok((parse_number(arity)?, weight))
+                })
+                .collect::<Result<_, String>>()?
+        }
+        "positional" => settings.position_tolerance = Some(parse_number(value)?),
+        "skip_grams" => {
+            settings.skip_grams = match value.split_once(' ') {
+                Some(("alongside", skip)) => SkipGrams::Alongside(parse_number(skip)?),
+                Some(("only", skip)) => SkipGrams::Only(parse_number(skip)?),
+                _ => return Err(format!("invalid skip grams {:?}", value)),
+            }
+        }
+        "stop_grams" => settings.stop_grams = parse_stop_grams(value)?,
+        "pad_left" => settings.pad_left = parse_pad(value)?,
+        "pad_right" => settings.pad_right = parse_pad(value)?,
+        "lowercase" if value.is_empty() => settings.lowercase = true,
+        _ => return Err(format!("unknown setting {:?}", name)),
+    }
+    Ok(())
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number {:?}", value))
+}
+
+/// Format `StopGrams` settings as space separated `name=value` pairs, and
+/// a `prune_postings` flag.
+fn format_stop_grams(stop_grams: &StopGrams) -> String {
+    let mut fields = Vec::new();
+    if let Some(ratio) = stop_grams.max_document_ratio {
+        fields.push(format!("max_document_ratio={}", ratio));
+    }
+    if let Some(count) = stop_grams.most_frequent {
+        fields.push(format!("most_frequent={}", count));
+    }
+    fields.push(format!("cap={}", stop_grams.cap));
+    if stop_grams.prune_postings {
+        fields.push("prune_postings".to_string());
+    }
+    fields.join(" ")
+}
+
+fn parse_stop_grams(value: &str) -> Result<StopGrams, String> {
+    let mut stop_grams = StopGrams::new();
+    for field in value.split_whitespace() {
+        match field.split_once('=') {
+            None if field == "prune_postings" => stop_grams.prune_postings = true,
+            Some(("max_document_ratio", ratio)) => {
+                let ratio = ratio
+                    .parse()
+                    .map_err(|_| format!("invalid document ratio {:?}", ratio))?;
+                stop_grams = stop_grams.max_document_ratio(ratio);
+            }
+            Some(("most_frequent", count)) => stop_grams.most_frequent = Some(parse_number(count)?),
+            Some(("cap", cap)) => stop_grams.cap = parse_number(cap)?,
+            _ => return Err(format!("invalid stop grams {:?}", field)),
+        }
+    }
+    Ok(stop_grams)
+}
+
+fn format_pad(pad: &Pad) -> String {
+    match pad {
+        Pad::None => "none".to_string(),
+        Pad::Auto => "auto".to_string(),
+        Pad::Pad(pad) => format!("pad {}", escape(pad)),
+    }
+}
+
+fn parse_pad(value: &str) -> Result<Pad, String> {
+    match value.split_once(' ') {
+        None if value == "none" => Ok(Pad::None),
+        None if value == "auto" => Ok(Pad::Auto),
+        Some(("pad", pad)) => Ok(Pad::Pad(unescape(pad)?)),
+        _ => Err(format!("invalid padding {:?}", value)),
+    }
+}
+
+/// Parse a `frequency \t key [\t payload]` entry line.
+fn parse_entry(line: &str) -> Result<SavedEntry, String> {
+    let mut fields = line.split('\t');
+    let frequency = parse_number(fields.next().unwrap_or(""))?;
+    let key = unescape(fields.next().ok_or("missing key")?)?;
+    let payload = fields.next().map(unescape).transpose()?;
+    if fields.next().is_some() {
+        return Err("too many fields".to_string());
+    }
+    Ok(SavedEntry {
+        key,
+        frequency,
+        payload,
+    })
+}
+
+/// Escape the characters that separate fields and lines with backslashes.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse `escape`.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            other => return Err(format!("invalid escape {:?}", other)),
+        }
+    }
+    Ok(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read_corpus() {
+        let settings = Settings {
+            arity: 3,
+            arities: vec![(2, 0.5), (3, 1.0)],
+            position_tolerance: Some(4),
+            skip_grams: SkipGrams::Alongside(1),
+            stop_grams: StopGrams::new()
+                .max_document_ratio(0.25)
+                .cap(10)
+                .prune_postings(true),
+            pad_left: Pad::Pad("\t\\ ".to_string()),
+            pad_right: Pad::None,
+            lowercase: true,
+        };
+        let entries = vec![
+            ("tomato", 2, Some("red\nround")),
+            ("", 1, None),
+            ("po\ttato", 1, Some("")),
+        ];
+        let mut saved = Vec::new();
+        write_corpus(&mut saved, &settings, entries.clone()).unwrap();
+        let (read, read_entries) = read_corpus(&saved[..]).unwrap();
+        assert_eq!(read.arity, 3);
+        assert_eq!(read.arities, settings.arities);
+        assert_eq!(read.position_tolerance, Some(4));
+        assert_eq!(read.skip_grams, SkipGrams::Alongside(1));
+        assert_eq!(read.stop_grams, settings.stop_grams);
+        assert!(matches!(read.pad_left, Pad::Pad(ref pad) if pad == "\t\\ "));
+        assert!(matches!(read.pad_right, Pad::None));
+        assert!(read.lowercase);
+        let expected: Vec<SavedEntry> = entries
+            .into_iter()
+            .map(|(key, frequency, payload)| SavedEntry {
+                key: key.to_string(),
+                frequency,
+                payload: payload.map(str::to_string),
+            })
+            .collect();
+        assert_eq!(read_entries, expected);
+    }
+
+    #[test]
+    fn read_corpus_errors() {
+        let read = |input: &str| read_corpus(input.as_bytes()).map(|_| ());
+        assert!(matches!(
+            read("tomato\n"),
+            Err(Error::Format { line: 1, .. })
+        ));
+        assert!(matches!(
+            read("# comment\nngrammatic-corpus 1\narity x\nentries\n"),
+            Err(Error::Format { line: 3, .. })
+        ));
+        assert!(matches!(
+            read("ngrammatic-corpus 1\narity 2\n"),
+            Err(Error::Format { .. })
+        ));
+        assert!(matches!(
+            read("ngrammatic-corpus 1\nentries\n1\ttomato\nx\tpotato\n"),
+            Err(Error::Format { line: 4, .. })
+        ));
+        assert!(matches!(
+            read("ngrammatic-corpus 1\nstop_grams cap=x\nentries\n"),
+            Err(Error::Format { line: 2, .. })
+        ));
+        assert!(read("ngrammatic-corpus 1\nentries\n1\ttomato\n").is_ok());
+    }
+}
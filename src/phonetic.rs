@@ -0,0 +1,630 @@
+#![deny(missing_docs)]
+
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{Corpus, CorpusBuilder};
+use crate::{KeyTransformer, SearchResult};
+
+/// Uppercase the ASCII letters of `word`, dropping everything else, which is
+/// the alphabet all of the phonetic encoders in this module operate on.
+fn ascii_letters(word: &str) -> Vec<u8> {
+    word.bytes()
+        .filter(u8::is_ascii_alphabetic)
+        .map(|b| b.to_ascii_uppercase())
+        .collect()
+}
+
+/// Encode each whitespace-separated word of `key` with `encode`, joining the
+/// resulting codes with single spaces. Words that produce no code (e.g.
+/// because they contain no ASCII letters) are dropped.
+fn encode_words(key: &str, encode: fn(&[u8]) -> String) -> String {
+    key.split_whitespace()
+        .map(|word| encode(&ascii_letters(word)))
+        .filter(|code| !code.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_vowel(b: u8) -> bool {
+    matches!(b, b'A' | b'E' | b'I' | b'O' | b'U')
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Soundex key transformer.
+///
+/// Replaces each word of the key with its four character American Soundex
+/// code, so that "Robert" and "Rupert" both become "R163".
+pub struct SoundexKeyTransformer;
+
+impl SoundexKeyTransformer {
+    fn code(b: u8) -> u8 {
+        match b {
+            b'B' | b'F' | b'P' | b'V' => b'1',
+            b'C' | b'G' | b'J' | b'K' | b'Q' | b'S' | b'X' | b'Z' => b'2',
+            b'D' | b'T' => b'3',
+            b'L' => b'4',
+            b'M' | b'N' => b'5',
+            b'R' => b'6',
+            // H and W are transparent, so they never separate two equal codes
+            b'H' | b'W' => b'-',
+            // Vowels (and Y) separate two equal codes, but aren't coded
+            _ => b'0',
+        }
+    }
+
+    fn encode(word: &[u8]) -> String {
+        let (first, rest) = match word.split_first() {
+            Some((&first, rest)) => (first, rest),
+            None => return String::new(),
+        };
+        let mut code = vec![first];
+        let mut last = Self::code(first);
+        for &b in rest {
+            match Self::code(b) {
+                b'-' => {}
+                b'0' => last = b'0',
+                digit => {
+                    if digit != last {
+                        code.push(digit);
+                    }
+                    last = digit;
+                }
+            }
+            if code.len() == 4 {
+                break;
+            }
+        }
+        code.resize(4, b'0');
+        String::from_utf8(code).unwrap_or_default()
+    }
+}
+
+impl KeyTransformer for SoundexKeyTransformer {
+    fn transform(&self, key: &str) -> String {
+        encode_words(key, Self::encode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Metaphone key transformer.
+///
+/// Replaces each word of the key with its (original, Lawrence Philips)
+/// Metaphone code, so that "Stephen" and "Steven" both become "STFN". The
+/// "th" sound is encoded as `0`.
+pub struct MetaphoneKeyTransformer;
+
+impl MetaphoneKeyTransformer {
+    fn encode(word: &[u8]) -> String {
+        let at = |i: usize| word.get(i).copied().unwrap_or(0);
+        let mut code = String::with_capacity(word.len());
+        let mut start = 0;
+        match (at(0), at(1)) {
+            (b'A', b'E') | (b'G', b'N') | (b'K', b'N') | (b'P', b'N') | (b'W', b'R') => start = 1,
+            (b'X', _) => {
+                code.push('S');
+                start = 1;
+            }
+            (b'W', b'H') => {
+                code.push('W');
+                start = 2;
+            }
+            _ => {}
+        }
+
+        for (i, &b) in word.iter().enumerate().skip(start) {
+            let prev = if i > 0 { at(i - 1) } else { 0 };
+            let next = at(i + 1);
+            let after = at(i + 2);
+            // Doubled letters are only encoded once, except for C
+            if b == prev && b != b'C' {
+                continue;
+            }
+            match b {
+                b'A' | b'E' | b'I' | b'O' | b'U' => {
+                    if i == start && code.is_empty() {
+                        code.push(b as char);
+                    }
+                }
+                b'B' => {
+                    if !(prev == b'M' && i + 1 == word.len()) {
+                        code.push('B');
+                    }
+                }
+                b'C' => {
+                    if next == b'I' && after == b'A' {
+                        code.push('X');
+                    } else if next == b'H' {
+                        code.push(if prev == b'S' { 'K' } else { 'X' });
+                    } else if matches!(next, b'I' | b'E' | b'Y') {
+                        if prev != b'S' {
+                            code.push('S');
+                        }
+                    } else {
+                        code.push('K');
+                    }
+                }
+                b'D' => {
+                    if next == b'G' && matches!(after, b'E' | b'I' | b'Y') {
+                        code.push('J');
+                    } else {
+                        code.push('T');
+                    }
+                }
+                b'G' => {
+                    if next == b'H' && i + 2 < word.len() && !is_vowel(after) {
+                        // silent, as in "night"
+                    } else if next == b'N' && (i + 2 == word.len() || &word[i + 1..] == b"NED") {
+                        // silent, as in "sign" or "signed"
+                    } else if prev == b'D' && matches!(next, b'E' | b'I' | b'Y') {
+                        // already encoded by the D, as in "edge"
+                    } else if matches!(next, b'I' | b'E' | b'Y') {
+                        code.push('J');
+                    } else {
+                        code.push('K');
+                    }
+                }
+                b'H' => {
+                    // silent after the letters it modifies, and after a vowel
+                    // when no vowel follows
+                    let modifies = matches!(prev, b'C' | b'G' | b'P' | b'S' | b'T');
+                    let trails_vowel = is_vowel(prev) && !is_vowel(next);
+                    if !modifies && !trails_vowel {
+                        code.push('H');
+                    }
+                }
+                b'K' => {
+                    if prev != b'C' {
+                        code.push('K');
+                    }
+                }
+                b'P' => code.push(if next == b'H' { 'F' } else { 'P' }),
+                b'Q' => code.push('K'),
+                b'S' => {
+                    if next == b'H' || (next == b'I' && matches!(after, b'O' | b'A')) {
+                        code.push('X');
+                    } else {
+                        code.push('S');
+                    }
+                }
+                b'T' => {
+                    if next == b'I' && matches!(after, b'O' | b'A') {
+                        code.push('X');
+                    } else if next == b'H' {
+                        code.push('0');
+                    } else if !(next == b'C' && after == b'H') {
+                        code.push('T');
+                    }
+                }
+                b'V' => code.push('F'),
+                b'W' | b'Y' => {
+                    if is_vowel(next) {
+                        code.push(b as char);
+                    }
+                }
+                b'X' => code.push_str("KS"),
+                b'Z' => code.push('S'),
+                _ => code.push(b as char),
+            }
+        }
+        code
+    }
+}
+
+impl KeyTransformer for MetaphoneKeyTransformer {
+    fn transform(&self, key: &str) -> String {
+        encode_words(key, Self::encode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// NYSIIS key transformer.
+///
+/// Replaces each word of the key with its New York State Identification and
+/// Intelligence System code, so that "Stephen" and "Steven" both become
+/// "STAFAN". Codes are not truncated to the traditional six characters, as
+/// the extra length is useful to the n-gram similarity.
+pub struct NysiisKeyTransformer;
+
+impl NysiisKeyTransformer {
+    fn encode(word: &[u8]) -> String {
+        if word.is_empty() {
+            return String::new();
+        }
+        let mut name = word.to_vec();
+        if name.starts_with(b"MAC") {
+            name[1] = b'C';
+        } else if name.starts_with(b"KN") {
+            name.remove(0);
+        } else if name.starts_with(b"K") {
+            name[0] = b'C';
+        } else if name.starts_with(b"PH") || name.starts_with(b"PF") {
+            name[0] = b'F';
+            name[1] = b'F';
+        } else if name.starts_with(b"SCH") {
+            name[1] = b'S';
+            name[2] = b'S';
+        }
+        if name.ends_with(b"EE") || name.ends_with(b"IE") {
+            name.truncate(name.len() - 2);
+            name.push(b'Y');
+        } else if [&b"DT"[..], b"RT", b"RD", b"NT", b"ND"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+        {
+            name.truncate(name.len() - 2);
+            name.push(b'D');
+        }
+
+        let at = |i: usize| name.get(i).copied().unwrap_or(0);
+        let mut key = vec![name[0]];
+        let mut i = 1;
+        while i < name.len() {
+            let b = name[i];
+            let prev = name[i - 1];
+            let next = at(i + 1);
+            let chunk: &[u8] = match b {
+                b'E' if next == b'V' => {
+                    i += 1;
+                    b"AF"
+                }
+                b'A' | b'E' | b'I' | b'O' | b'U' => b"A",
+                b'Q' => b"G",
+                b'Z' => b"S",
+                b'M' => b"N",
+                b'K' if next == b'N' => b"N",
+                b'K' => b"C",
+                b'S' if next == b'C' && at(i + 2) == b'H' => {
+                    i += 2;
+                    b"SS"
+                }
+                b'P' if next == b'H' => {
+                    i += 1;
+                    b"F"
+                }
+                b'H' if !is_vowel(prev) || !is_vowel(next) => {
+                    if is_vowel(prev) {
+                        b"A"
+                    } else {
+                        &name[i - 1..i]
+                    }
+                }
+                b'W' if is_vowel(prev) => &name[i - 1..i],
+                _ => &name[i..i + 1],
+            };
+            if chunk.last() != key.last() {
+                key.extend_from_slice(chunk);
+            }
+            i += 1;
+        }
+
+        if key.len() > 1 && key.ends_with(b"S") {
+            key.pop();
+        }
+        if key.ends_with(b"AY") {
+            key.truncate(key.len() - 2);
+            key.push(b'Y');
+        }
+        if key.len() > 1 && key.ends_with(b"A") {
+            key.pop();
+        }
+        String::from_utf8(key).unwrap_or_default()
+    }
+}
+
+impl KeyTransformer for NysiisKeyTransformer {
+    fn transform(&self, key: &str) -> String {
+        encode_words(key, Self::encode)
+    }
+}
+
+/// A corpus that indexes both the literal grams of its entries and the grams
+/// of their phonetic codes, blending the two similarities when searching.
+///
+/// The `similarity` of each `SearchResult` is the blended score, while
+/// `ngram_similarity` and `phonetic_similarity` hold the two scores it was
+/// blended from.
+pub struct PhoneticCorpus<KT, PE>
+where
+    KT: KeyTransformer,
+    PE: KeyTransformer,
+{
+    literal: Corpus<KT>,
+    phonetic: Corpus<PE>,
+    codes: HashMap<String, Vec<String>>,
+    phonetic_weight: f32,
+}
+
+impl<KT, PE> std::fmt::Debug for PhoneticCorpus<KT, PE>
+where
+    KT: KeyTransformer,
+    PE: KeyTransformer,
+{
+    /// Debug format for a `PhoneticCorpus`. Omits any representation of the
+    /// key transformers, as there's no meaningful representation we could
+    /// give.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "PhoneticCorpus {{")?;
+        writeln!(f, "  literal: {:?},", self.literal)?;
+        writeln!(f, "  phonetic: {:?},", self.phonetic)?;
+        writeln!(f, "  phonetic_weight: {:?},", self.phonetic_weight)?;
+        writeln!(f, "}}")
+    }
+}
+
+impl<KT, PE> PhoneticCorpus<KT, PE>
+where
+    KT: KeyTransformer + std::marker::Sync,
+    PE: KeyTransformer + std::marker::Sync,
+{
+    /// Index the phonetic code of an already transformed literal `key`.
+    fn add_code(&mut self, key: &str) {
+        let code = self.phonetic.transform_key(key);
+        if code.is_empty() {
+            return;
+        }
+        self.phonetic.add_text(key);
+        let keys = self.codes.entry(code).or_default();
+        if !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+        }
+    }
+
+    /// Generate the literal and phonetic `Ngram`s for the supplied `text`, and
+    /// add them to the `PhoneticCorpus`.
+    pub fn add_text(&mut self, text: &str) {
+        self.literal.add_text(text);
+        let key = self.literal.transform_key(text);
+        self.add_code(&key);
+    }
+
+    /// If the corpus is empty.
+    pub fn is_empty(&self) -> bool {
+        self.literal.is_empty()
+    }
+
+    /// Determines whether an exact match exists for the supplied `text` in the
+    /// literal index of the `PhoneticCorpus`.
+    pub fn key(&self, text: &str) -> Option<String> {
+        self.literal.key(text)
+    }
+
+    /// Perform a fuzzy search of the `PhoneticCorpus` for entries above some
+    /// `threshold` of blended similarity to the supplied `text`. Returns up to
+    /// `limit` results, sorted by highest similarity to lowest.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, MetaphoneKeyTransformer};
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .case_insensitive()
+    ///     .fill(vec!["Stephen", "Steve"])
+    ///     .finish_phonetic(MetaphoneKeyTransformer, 0.5);
+    /// let results = corpus.search("Steven", 0.40, 10);
+    /// assert_eq!(results[0].text, "stephen");
+    /// # }
+    /// ```
+    pub fn search(&self, text: &str, threshold: f32, limit: usize) -> Vec<SearchResult> {
+        self.search_with_warp(text, 2.0, threshold, limit)
+    }
+
+    /// Perform a fuzzy search of the `PhoneticCorpus` for entries with a custom
+    /// `warp` for results above some `threshold` of blended similarity to the
+    /// supplied `text`. Returns up to `limit` results, sorted by highest
+    /// similarity to lowest.
+    pub fn search_with_warp(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<SearchResult> {
+        // Any entry which shares no grams with the query on one side scores
+        // 0.0 on that side, so the union of both candidate sets is complete.
+        // As neither similarity exceeds 1.0, an entry can only reach the
+        // `threshold` if each side reaches its floor, so entries missing from
+        // either side's results fall below the threshold whatever they score.
+        let weight = self.phonetic_weight;
+        let literal_floor = similarity_floor(threshold, 1.0 - weight);
+        let phonetic_floor = similarity_floor(threshold, weight);
+        let mut scores: HashMap<String, (f32, f32)> = HashMap::new();
+        for result in self
+            .literal
+            .search_with_warp(text, warp, literal_floor, usize::MAX)
+        {
+            scores.entry(result.text).or_default().0 = result.similarity;
+        }
+        let key = self.literal.transform_key(text);
+        for result in self
+            .phonetic
+            .search_with_warp(&key, warp, phonetic_floor, usize::MAX)
+        {
+            for key in self.codes.get(&result.text).into_iter().flatten() {
+                scores.entry(key.clone()).or_default().1 = result.similarity;
+            }
+        }
+
+        let mut results: Vec<SearchResult> = scores
+            .into_iter()
+            .map(|(text, (literal, phonetic))| {
+                let similarity =
+                    literal * (1.0 - self.phonetic_weight) + phonetic * self.phonetic_weight;
+                let mut result = SearchResult::new(text, similarity);
+                result.ngram_similarity = literal;
+                result.phonetic_similarity = Some(phonetic);
+                result
+            })
+            .filter(|result| result.similarity >= threshold)
+            .collect();
+
+        // Sort highest similarity to lowest, then by text
+        results.sort_by(|a, b| b.cmp(a));
+        results.truncate(limit);
+        results
+    }
+}
+
+/// The lowest similarity a side given `weight` in a blend can have for the
+/// blend to reach `threshold`, allowing for rounding in the blend.
+fn similarity_floor(threshold: f32, weight: f32) -> f32 {
+    if weight <= 0.0 {
+        return 0.0;
+    }
+    ((threshold - (1.0 - weight)) / weight - f32::EPSILON).max(0.0)
+}
+
+impl<KT> CorpusBuilder<KT>
+where
+    KT: KeyTransformer + std::marker::Sync,
+{
+    /// Yield a `PhoneticCorpus` instance with all the properties set with this
+    /// builder, which additionally indexes the codes produced by the phonetic
+    /// `encoder` for every entry. Searches blend the literal and phonetic
+    /// similarities, giving the phonetic one the supplied `phonetic_weight`
+    /// (clamped to the range 0.0 to 1.0).
+    pub fn finish_phonetic<PE>(self, encoder: PE, phonetic_weight: f32) -> PhoneticCorpus<KT, PE>
+    where
+        PE: KeyTransformer + std::marker::Sync,
+    {
+        let literal = self.finish();
+        let keys: Vec<String> = literal.keys().map(str::to_string).collect();
+        let mut corpus = PhoneticCorpus {
+            phonetic: literal.empty_with_key_transformer(encoder),
+            literal,
+            codes: HashMap::new(),
+            phonetic_weight: phonetic_weight.clamp(0.0, 1.0),
+        };
+        for key in keys {
+            corpus.add_code(&key);
+        }
+        corpus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soundex_codes() {
+        let soundex = SoundexKeyTransformer;
+        assert_eq!(soundex.transform("Robert"), "R163");
+        assert_eq!(soundex.transform("Rupert"), "R163");
+        assert_eq!(soundex.transform("Rubin"), "R150");
+        assert_eq!(soundex.transform("Ashcraft"), "A261");
+        assert_eq!(soundex.transform("Tymczak"), "T522");
+        assert_eq!(soundex.transform("Pfister"), "P236");
+        assert_eq!(soundex.transform("Honeyman"), "H555");
+        assert_eq!(soundex.transform("Lee"), "L000");
+    }
+
+    #[test]
+    fn metaphone_codes() {
+        let metaphone = MetaphoneKeyTransformer;
+        assert_eq!(metaphone.transform("Stephen"), "STFN");
+        assert_eq!(metaphone.transform("Steven"), "STFN");
+        assert_eq!(metaphone.transform("Knight"), "NT");
+        assert_eq!(metaphone.transform("Wright"), "RT");
+        assert_eq!(metaphone.transform("Xavier"), "SFR");
+        assert_eq!(metaphone.transform("Thumb"), "0M");
+    }
+
+    #[test]
+    fn nysiis_codes() {
+        let nysiis = NysiisKeyTransformer;
+        assert_eq!(nysiis.transform("Stephen"), "STAFAN");
+        assert_eq!(nysiis.transform("Steven"), "STAFAN");
+        assert_eq!(nysiis.transform("Macintosh"), "MCANT");
+        assert_eq!(nysiis.transform("Knight"), "NAGT");
+    }
+
+    #[test]
+    fn phonetic_multiple_words() {
+        assert_eq!(
+            SoundexKeyTransformer.transform("Robert  Rubin"),
+            "R163 R150"
+        );
+        assert_eq!(SoundexKeyTransformer.transform("42 Robert"), "R163");
+        assert_eq!(MetaphoneKeyTransformer.transform(""), "");
+    }
+
+    #[test]
+    fn linked_phonetic_key_transformer_indexes_codes() {
+        let corpus = CorpusBuilder::default()
+            .link_key_transformer(MetaphoneKeyTransformer)
+            .fill(vec!["Stephen"])
+            .finish();
+        assert_eq!(corpus.key("Steven"), Some("Steven".to_string()));
+        assert_eq!(corpus.search("Steven", 0.99, 10)[0].text, "STFN");
+    }
+
+    #[test]
+    fn phonetic_corpus_indexes_codes() {
+        let corpus = CorpusBuilder::default()
+            .fill(vec!["Stephen", "Stefan"])
+            .finish_phonetic(MetaphoneKeyTransformer, 1.0);
+        let results = corpus.search("Steven", 0.99, 10);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.similarity == 1.0));
+        assert!(results.iter().all(|result| result.ngram_similarity < 1.0));
+        assert_eq!(corpus.search("Robert", 0.5, 10), vec![]);
+    }
+
+    #[test]
+    fn phonetic_corpus_blends_scores() {
+        let literal = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(vec!["Stephen", "Steve"])
+            .finish();
+        assert_eq!(literal.search("Steven", 0.4, 10)[0].text, "steve");
+
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(vec!["Stephen", "Steve"])
+            .finish_phonetic(MetaphoneKeyTransformer, 0.5);
+        let results = corpus.search("Steven", 0.4, 10);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].text, "stephen");
+        assert_eq!(results[0].phonetic_similarity, Some(1.0));
+        assert!(results[0].ngram_similarity < results[1].ngram_similarity);
+    }
+
+    #[test]
+    fn phonetic_corpus_threshold_floors() {
+        assert_eq!(similarity_floor(0.5, 0.0), 0.0);
+        assert_eq!(similarity_floor(0.25, 0.5), 0.0);
+        assert!(similarity_floor(0.75, 0.5) < 0.5);
+        assert!(similarity_floor(0.75, 0.5) > 0.49);
+
+        let names = vec!["Stephen", "Steve", "Stefan", "Stevenson", "Esteban"];
+        for weight in [0.0, 0.3, 0.5, 0.8, 1.0] {
+            let corpus = CorpusBuilder::default()
+                .fill(names.clone())
+                .finish_phonetic(MetaphoneKeyTransformer, weight);
+            let everything = corpus.search("Steven", 0.0, usize::MAX);
+            for threshold in [0.2, 0.4, 0.6, 0.8] {
+                let expected: Vec<_> = everything
+                    .iter()
+                    .filter(|result| result.similarity >= threshold)
+                    .cloned()
+                    .collect();
+                assert_eq!(corpus.search("Steven", threshold, usize::MAX), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn phonetic_corpus_add_text() {
+        let mut corpus = CorpusBuilder::default().finish_phonetic(SoundexKeyTransformer, 1.0);
+        assert!(corpus.is_empty());
+        corpus.add_text("Robert");
+        let results = corpus.search("Rupert", 0.99, 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].text, "Robert");
+        assert_eq!(results[0].similarity, 1.0);
+    }
+}
@@ -0,0 +1,529 @@
+#![deny(missing_docs)]
+
+use std::iter::FromIterator;
+use std::mem::size_of;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use string_interner::{DefaultSymbol, Symbol};
+
+/// How many symbols appending to a `PostingList` puts in each block. Blocks
+/// edited in the middle may grow to twice this before they're split.
+const BLOCK_LEN: usize = 128;
+
+/// A compressed posting list: the entries having a gram, as interned
+/// symbols sorted in ascending order.
+///
+/// The symbols are split into blocks of about `BLOCK_LEN`. The first symbol
+/// of each block is stored as is, and the others as their difference to the
+/// previous one, all in a variable length encoding of seven bits per byte,
+/// so most take a single byte instead of four. Symbols are decoded on the
+/// fly while iterating.
+///
+/// A skip table holds where each block after the first starts, and its
+/// first symbol. Appending a symbol larger than all the others is cheap.
+/// Inserting or removing one elsewhere only decodes and re-encodes the block
+/// it belongs in, though the bytes after it still have to be moved.
+///
+/// Not part of the public API, only exported for the benchmarks by the bench
+/// feature.
+#[doc(hidden)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PostingList {
+    bytes: Vec<u8>,
+    skips: Vec<Skip>,
+    len: u32,
+    last: u32,
+}
+
+/// Where a block of a `PostingList`, other than the first, starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Skip {
+    /// The index of the first symbol of the block in the list
+    index: u32,
+    /// The offset of the block in the encoded bytes
+    offset: u32,
+    /// The value of the first symbol of the block
+    first: u32,
+}
+
+/// The symbols and encoded bytes a block of a `PostingList` spans.
+struct Block {
+    start: usize,
+    end: usize,
+    byte_start: usize,
+    byte_end: usize,
+}
+
+/// Iterates over the symbols of a `PostingList`, in ascending order.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct PostingIter<'a> {
+    bytes: &'a [u8],
+    skips: &'a [Skip],
+    position: u32,
+    previous: u32,
+    remaining: usize,
+}
+
+impl PostingList {
+    /// The number of symbols in the list.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// If the list holds no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the symbols of the list, in ascending order.
+    pub fn iter(&self) -> PostingIter<'_> {
+        PostingIter {
+            bytes: &self.bytes,
+            skips: &self.skips,
+            position: 0,
+            previous: 0,
+            remaining: self.len(),
+        }
+    }
+
+    /// Add the symbol `sym` to the list. Returns whether it wasn't there
+    /// already.
+    pub fn insert(&mut self, sym: DefaultSymbol) -> bool {
+        let value = sym.to_usize() as u32;
+        if self.is_empty() || value > self.last {
+            self.push(value);
+            return true;
+        }
+        let block = self.block_of(value);
+        let mut values = self.decode_block(block);
+        match values.binary_search(&value) {
+            Ok(_) => false,
+            Err(index) => {
+                values.insert(index, value);
+                self.replace_block(block, &values);
+                true
+            }
+        }
+    }
+
+    /// Remove the symbol `sym` from the list. Returns whether it was there.
+    pub fn remove(&mut self, sym: DefaultSymbol) -> bool {
+        let value = sym.to_usize() as u32;
+        if self.is_empty() || value > self.last {
+            return false;
+        }
+        let block = self.block_of(value);
+        let mut values = self.decode_block(block);
+        match values.binary_search(&value) {
+            Ok(index) => {
+                values.remove(index);
+                self.replace_block(block, &values);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Keep only the `len` smallest symbols of the list.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        if len == 0 {
+            self.clear();
+            return;
+        }
+        // The block holding the last symbol kept
+        let block = self
+            .skips
+            .partition_point(|skip| (skip.index as usize) < len);
+        let bounds = self.bounds(block);
+        let mut kept = PostingIter {
+            bytes: &self.bytes[bounds.byte_start..],
+            skips: &[],
+            position: 0,
+            previous: 0,
+            remaining: len - bounds.start,
+        };
+        let mut last = 0;
+        while let Some(value) = kept.next_value() {
+            last = value;
+        }
+        let end = self.bytes.len() - kept.bytes.len();
+        self.bytes.truncate(end);
+        self.skips.truncate(block);
+        self.len = len as u32;
+        self.last = last;
+    }
+
+    /// Shrink the capacity of the encoded list as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.bytes.shrink_to_fit();
+        self.skips.shrink_to_fit();
+    }
+
+    /// Estimated heap bytes used by the list.
+    pub fn heap_bytes(&self) -> usize {
+        self.bytes.capacity() + self.skips.capacity() * size_of::<Skip>()
+    }
+
+    /// Append `value`, which must be larger than all the others.
+    fn push(&mut self, value: u32) {
+        let block_start = self.skips.last().map_or(0, |skip| skip.index as usize);
+        if self.is_empty() {
+            write_varint(&mut self.bytes, value);
+        } else if self.len() - block_start >= BLOCK_LEN {
+            self.skips.push(Skip {
+                index: self.len,
+                offset: self.bytes.len() as u32,
+                first: value,
+            });
+            write_varint(&mut self.bytes, value);
+        } else {
+            write_varint(&mut self.bytes, value - self.last);
+        }
+        self.len += 1;
+        self.last = value;
+    }
+
+    /// Empty the list.
+    fn clear(&mut self) {
+        self.bytes.clear();
+        self.skips.clear();
+        self.len = 0;
+        self.last = 0;
+    }
+
+    /// The index of the block `value` belongs in.
+    fn block_of(&self, value: u32) -> usize {
+        self.skips.partition_point(|skip| skip.first <= value)
+    }
+
+    /// The symbols and bytes the block at `index` spans.
+    fn bounds(&self, index: usize) -> Block {
+        let (start, byte_start) = match index.checked_sub(1) {
+            Some(skip) => (
+                self.skips[skip].index as usize,
+                self.skips[skip].offset as usize,
+            ),
+            None => (0, 0),
+        };
+        let (end, byte_end) = match self.skips.get(index) {
+            Some(skip) => (skip.index as usize, skip.offset as usize),
+            None => (self.len(), self.bytes.len()),
+        };
+        Block {
+            start,
+            end,
+            byte_start,
+            byte_end,
+        }
+    }
+
+    /// Decode the values of the symbols of the block at `index`.
+    fn decode_block(&self, index: usize) -> Vec<u32> {
+        let bounds = self.bounds(index);
+        let mut iter = PostingIter {
+            bytes: &self.bytes[bounds.byte_start..bounds.byte_end],
+            skips: &[],
+            position: 0,
+            previous: 0,
+            remaining: bounds.end - bounds.start,
+        };
+        let mut values = Vec::with_capacity(bounds.end - bounds.start);
+        while let Some(value) = iter.next_value() {
+            values.push(value);
+        }
+        values
+    }
+
+    /// Replace the symbols of the block at `index` with the sorted and
+    /// distinct `values`, which must still sort between those of the blocks
+    /// around it. Splits the block if it grew too large, and drops it if
+    /// it's left empty.
+    fn replace_block(&mut self, index: usize, values: &[u32]) {
+        let bounds = self.bounds(index);
+        let was_last = index == self.skips.len();
+        let chunks: Vec<&[u32]> = if values.len() > 2 * BLOCK_LEN {
+            let (left, right) = values.split_at(values.len() / 2);
+            vec![left, right]
+        } else if values.is_empty() {
+            Vec::new()
+        } else {
+            vec![values]
+        };
+        let mut encoded = Vec::new();
+        let mut skips = Vec::new();
+        let mut position = bounds.start;
+        for chunk in &chunks {
+            skips.push(Skip {
+                index: position as u32,
+                offset: (bounds.byte_start + encoded.len()) as u32,
+                first: chunk[0],
+            });
+            let mut previous = 0;
+            for value in chunk.iter() {
+                write_varint(&mut encoded, value - previous);
+                previous = *value;
+            }
+            position += chunk.len();
+        }
+        let byte_shift = encoded.len() as i64 - (bounds.byte_end - bounds.byte_start) as i64;
+        let index_shift = values.len() as i64 - (bounds.end - bounds.start) as i64;
+        self.bytes
+            .splice(bounds.byte_start..bounds.byte_end, encoded);
+        for skip in &mut self.skips[index..] {
+            skip.offset = (i64::from(skip.offset) + byte_shift) as u32;
+            skip.index = (i64::from(skip.index) + index_shift) as u32;
+        }
+        // The first block has no skip, so neither has its first chunk
+        match index.checked_sub(1) {
+            Some(skip) => {
+                self.skips.splice(skip..index, skips);
+            }
+            None => {
+                self.skips.splice(0..0, skips.into_iter().skip(1));
+            }
+        }
+        if self.skips.first().map_or(false, |skip| skip.index == 0) {
+            self.skips.remove(0);
+        }
+        self.len = (i64::from(self.len) + index_shift) as u32;
+        if self.is_empty() {
+            self.clear();
+        } else if was_last {
+            self.last = match values.last() {
+                Some(last) => *last,
+                None => {
+                    let last_block = self.decode_block(self.skips.len());
+                    last_block.last().copied().unwrap_or(0)
+                }
+            };
+        }
+    }
+}
+
+/// Append `value` to `bytes`, seven bits at a time, setting the high bit of
+/// every byte but the last.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl<'a> PostingIter<'a> {
+    /// Decode the next value, if any remain.
+    fn next_value(&mut self) -> Option<u32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        if let Some((skip, rest)) = self.skips.split_first() {
+            if skip.index == self.position {
+                // Blocks start with a value of their own
+                self.previous = 0;
+                self.skips = rest;
+            }
+        }
+        let mut delta: u32 = 0;
+        let mut shift = 0;
+        loop {
+            let (byte, rest) = self.bytes.split_first()?;
+            self.bytes = rest;
+            delta |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        self.position += 1;
+        self.remaining -= 1;
+        self.previous += delta;
+        Some(self.previous)
+    }
+}
+
+impl<'a> Iterator for PostingIter<'a> {
+    type Item = DefaultSymbol;
+
+    fn next(&mut self) -> Option<DefaultSymbol> {
+        let value = self.next_value()?;
+        DefaultSymbol::try_from_usize(value as usize)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> IntoIterator for &'a PostingList {
+    type Item = DefaultSymbol;
+    type IntoIter = PostingIter<'a>;
+
+    fn into_iter(self) -> PostingIter<'a> {
+        self.iter()
+    }
+}
+
+impl Extend<DefaultSymbol> for PostingList {
+    /// Add all the symbols, in any order. Symbols larger than all those in
+    /// the list are appended, otherwise the list is merged and re-encoded.
+    fn extend<It: IntoIterator<Item = DefaultSymbol>>(&mut self, syms: It) {
+        let mut added: Vec<u32> = syms.into_iter().map(|sym| sym.to_usize() as u32).collect();
+        added.sort_unstable();
+        added.dedup();
+        match added.first() {
+            None => {}
+            Some(first) if self.is_empty() || *first > self.last => {
+                for value in added {
+                    self.push(value);
+                }
+            }
+            Some(_) => {
+                let mut iter = self.iter();
+                let mut values = Vec::with_capacity(self.len() + added.len());
+                while let Some(value) = iter.next_value() {
+                    values.push(value);
+                }
+                values.extend(added);
+                values.sort_unstable();
+                values.dedup();
+                self.clear();
+                for value in values {
+                    self.push(value);
+                }
+            }
+        }
+    }
+}
+
+impl FromIterator<DefaultSymbol> for PostingList {
+    fn from_iter<It: IntoIterator<Item = DefaultSymbol>>(syms: It) -> Self {
+        let mut list = PostingList::default();
+        list.extend(syms);
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn sym(value: usize) -> DefaultSymbol {
+        DefaultSymbol::try_from_usize(value).unwrap()
+    }
+
+    fn values(list: &PostingList) -> Vec<usize> {
+        list.iter().map(Symbol::to_usize).collect()
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let mut list = PostingList::default();
+        assert!(list.is_empty());
+        assert_eq!(list.iter().next(), None);
+        let expected = [0, 1, 5, 127, 128, 300, 16_384, 16_385, 2_000_000];
+        for value in expected {
+            assert!(list.insert(sym(value)));
+        }
+        assert_eq!(values(&list), expected);
+        assert_eq!(list.len(), expected.len());
+        assert_eq!(
+            list.iter().size_hint(),
+            (expected.len(), Some(expected.len()))
+        );
+        // Small gaps take a byte each, and the first symbol of each of the
+        // eight blocks two
+        let dense: PostingList = (1000..2000).map(sym).collect();
+        assert_eq!(dense.skips.len(), 7);
+        assert_eq!(dense.bytes.len(), 8 * 2 + 992);
+        assert_eq!(values(&dense), (1000..2000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut list: PostingList = [9, 3, 40, 3].iter().map(|&value| sym(value)).collect();
+        assert_eq!(values(&list), [3, 9, 40]);
+        assert!(list.insert(sym(20)));
+        assert!(!list.insert(sym(20)));
+        assert!(list.insert(sym(1)));
+        assert!(!list.insert(sym(40)));
+        assert_eq!(values(&list), [1, 3, 9, 20, 40]);
+        assert!(list.remove(sym(9)));
+        assert!(!list.remove(sym(9)));
+        assert!(!list.remove(sym(100)));
+        assert!(list.remove(sym(40)));
+        assert_eq!(values(&list), [1, 3, 20]);
+        // The last symbol is tracked through removals
+        assert!(list.insert(sym(30)));
+        assert_eq!(values(&list), [1, 3, 20, 30]);
+        list.extend([sym(2), sym(300)]);
+        assert_eq!(values(&list), [1, 2, 3, 20, 30, 300]);
+        list.extend([sym(400), sym(301)]);
+        assert_eq!(values(&list), [1, 2, 3, 20, 30, 300, 301, 400]);
+        for value in [1, 2, 3, 20, 30, 300, 301, 400] {
+            assert!(list.remove(sym(value)));
+        }
+        assert!(list.is_empty());
+        assert_eq!(list, PostingList::default());
+    }
+
+    #[test]
+    fn edit_blocks() {
+        // Compare many edits against a set, with blocks being split, emptied
+        // and dropped along the way
+        let mut list: PostingList = (0..1000).map(|value| sym(value * 3)).collect();
+        let mut expected: BTreeSet<usize> = (0..1000).map(|value| value * 3).collect();
+        let mut state: u32 = 12345;
+        for step in 0..20_000 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let value = (state >> 8) as usize % 3500;
+            if step % 3 == 0 {
+                assert_eq!(list.remove(sym(value)), expected.remove(&value));
+            } else {
+                assert_eq!(list.insert(sym(value)), expected.insert(value));
+            }
+            assert_eq!(list.len(), expected.len());
+        }
+        assert_eq!(values(&list), expected.iter().copied().collect::<Vec<_>>());
+        assert!(list.skips.len() > 1);
+        for value in expected.clone() {
+            assert!(list.remove(sym(value)));
+            expected.remove(&value);
+            if let Some(last) = expected.iter().next_back() {
+                // Appending checks that the last symbol is kept up to date
+                assert!(!list.insert(sym(*last)));
+            }
+        }
+        assert_eq!(list, PostingList::default());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut list: PostingList = [5, 200, 70_000, 70_001].iter().map(|&v| sym(v)).collect();
+        list.truncate(10);
+        assert_eq!(list.len(), 4);
+        list.truncate(2);
+        assert_eq!(values(&list), [5, 200]);
+        assert!(list.insert(sym(201)));
+        assert!(list.insert(sym(6)));
+        assert_eq!(values(&list), [5, 6, 200, 201]);
+        list.truncate(0);
+        assert!(list.is_empty());
+        assert_eq!(list.iter().next(), None);
+
+        for len in [1, 127, 128, 129, 300, 640] {
+            let mut list: PostingList = (0..1000).map(sym).collect();
+            list.truncate(len);
+            assert_eq!(values(&list), (0..len).collect::<Vec<_>>());
+            assert!(list.insert(sym(5000)));
+            assert_eq!(list.len(), len + 1);
+        }
+    }
+}
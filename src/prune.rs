@@ -0,0 +1,234 @@
+#![deny(missing_docs)]
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Settings for pruning stop grams: grams that occur in so many entries of a
+/// `Corpus` that gathering the entries having them would make a search touch
+/// most of the corpus, like the padding gram `" a"`, or `"e "`.
+///
+/// When gathering the candidate matches of a query, at most `cap` of the
+/// entries having each stop gram are considered, unless every gram of the
+/// query is a stop gram. Pruning never changes the similarity of an entry,
+/// only whether it's considered, and results found while pruning say so in
+/// `SearchResult::pruned`.
+///
+/// Stop grams are chosen from the document frequencies of grams when a
+/// `Corpus` is finished or loaded, and again by
+/// `Corpus::update_stop_grams`. Entries added in between don't make new stop
+/// grams. By default, no gram is a stop gram.
+/// ```rust
+/// # use ngrammatic::{CorpusBuilder, StopGrams};
+/// # fn main() {
+/// let corpus = CorpusBuilder::default()
+///     .stop_grams(StopGrams::new().max_document_ratio(0.5))
+///     .fill(vec!["tomato", "potato", "avocado", "carrot"])
+///     .finish();
+/// // Only "o " occurs in more than half of the entries
+/// assert_eq!(corpus.stop_gram_count(), 1);
+/// let results = corpus.search("tomacco", 0.0, 10);
+/// assert_eq!(results[0].text, "tomato");
+/// assert!(results[0].pruned);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StopGrams {
+    /// Grams occurring in more than this fraction of the entries are stop
+    /// grams
+    pub max_document_ratio: Option<f32>,
+    /// This many of the grams occurring in the most entries are stop grams
+    pub most_frequent: Option<usize>,
+    /// How many of the entries having a stop gram are considered as
+    /// candidates
+    pub cap: usize,
+    /// Whether to store only `cap` of the entries having each stop gram in
+    /// the index, saving memory
+    pub prune_postings: bool,
+}
+
+impl Default for StopGrams {
+    /// Default settings choose no stop grams, skip stop grams entirely, and
+    /// don't prune postings.
+    fn default() -> Self {
+        StopGrams {
+            max_document_ratio: None,
+            most_frequent: None,
+            cap: 0,
+            prune_postings: false,
+        }
+    }
+}
+
+impl StopGrams {
+    /// Initialize a new instance of `StopGrams` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make grams occurring in more than the given `ratio` of entries stop
+    /// grams (clamped to the range 0.0 to 1.0).
+    pub fn max_document_ratio(mut self, ratio: f32) -> Self {
+        self.max_document_ratio = Some(ratio.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Make the `count` grams occurring in the most entries stop grams.
+    pub fn most_frequent(mut self, count: usize) -> Self {
+        self.most_frequent = Some(count);
+        self
+    }
+
+    /// Consider up to `cap` of the entries having each stop gram as
+    /// candidates, instead of none.
+    pub fn cap(mut self, cap: usize) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    /// Set whether only `cap` of the entries having each stop gram are
+    /// stored in the index. Their document frequencies are still kept.
+    pub fn prune_postings(mut self, prune_postings: bool) -> Self {
+        self.prune_postings = prune_postings;
+        self
+    }
+
+    /// Choose the stop grams among grams with the given document
+    /// `frequencies`, in a corpus of `entries`. Ties for the most frequent
+    /// grams are broken by the grams' order.
+    pub(crate) fn choose<G>(&self, frequencies: &HashMap<G, usize>, entries: usize) -> HashSet<G>
+    where
+        G: Copy + Eq + Hash + Ord,
+    {
+        let mut stop_grams = HashSet::new();
+        if let Some(ratio) = self.max_document_ratio {
+            let max_frequency = ratio * entries as f32;
+            stop_grams.extend(
+                frequencies
+                    .iter()
+                    .filter(|(_, &frequency)| frequency as f32 > max_frequency)
+                    .map(|(gram, _)| *gram),
+            );
+        }
+        if let Some(count) = self.most_frequent.filter(|&count| count > 0) {
+            let mut grams: Vec<(usize, G)> = frequencies
+                .iter()
+                .map(|(gram, frequency)| (*frequency, *gram))
+                .collect();
+            let most_frequent_first =
+                |a: &(usize, G), b: &(usize, G)| b.0.cmp(&a.0).then(a.1.cmp(&b.1));
+            if grams.len() > count {
+                grams.select_nth_unstable_by(count - 1, most_frequent_first);
+                grams.truncate(count);
+            }
+            stop_grams.extend(grams.into_iter().map(|(_, gram)| gram));
+        }
+        stop_grams
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CorpusBuilder, SearchOptions};
+
+    #[test]
+    fn choose_stop_grams() {
+        let frequencies: HashMap<&str, usize> =
+            [("a", 9), ("b", 6), ("c", 5), ("d", 5), ("e", 1)].into();
+        let chosen = |stop_grams: StopGrams| {
+            let mut chosen: Vec<&str> = stop_grams.choose(&frequencies, 10).into_iter().collect();
+            chosen.sort_unstable();
+            chosen
+        };
+        assert!(chosen(StopGrams::new()).is_empty());
+        assert_eq!(chosen(StopGrams::new().max_document_ratio(0.5)), ["a", "b"]);
+        assert_eq!(chosen(StopGrams::new().most_frequent(3)), ["a", "b", "c"]);
+        assert_eq!(chosen(StopGrams::new().most_frequent(10)).len(), 5);
+        assert_eq!(
+            chosen(StopGrams::new().max_document_ratio(0.8).most_frequent(2)),
+            ["a", "b"]
+        );
+        assert!(chosen(StopGrams::new().max_document_ratio(1.5)).is_empty());
+    }
+
+    fn words() -> Vec<String> {
+        (0..200)
+            .map(|i| format!("a{}{}", ["x", "y", "z", "w"][i % 4], i))
+            .collect()
+    }
+
+    #[test]
+    fn pruning_keeps_scores() {
+        let unpruned = CorpusBuilder::default().fill(words()).finish();
+        for prune_postings in [false, true] {
+            let stop_grams = StopGrams::new()
+                .max_document_ratio(0.3)
+                .cap(5)
+                .prune_postings(prune_postings);
+            let corpus = CorpusBuilder::default()
+                .stop_grams(stop_grams)
+                .fill(words())
+                .finish();
+            // " a" occurs in every entry
+            assert_eq!(corpus.document_frequency(" a"), 200);
+            assert_eq!(corpus.stats().top_grams[0], (" a".to_string(), 200));
+            let options = SearchOptions::new().limit(500);
+            let results = corpus.search_with("ax16", &options).unwrap();
+            assert_eq!(results[0].text, "ax16");
+            assert!(results.iter().all(|result| result.pruned));
+            let all = unpruned.search_with("ax16", &options).unwrap();
+            assert!(results.len() < all.len());
+            assert!(all.iter().all(|result| !result.pruned));
+            for result in &results {
+                let same = all.iter().find(|other| other.text == result.text).unwrap();
+                assert_eq!(result.similarity, same.similarity);
+            }
+            // Queries with only stop grams fall back to considering them
+            assert!(!corpus.search("a", 0.0, 10).is_empty());
+            // Each shard chooses its own stop grams
+            let sharded = CorpusBuilder::default()
+                .stop_grams(stop_grams)
+                .fill(words())
+                .finish_sharded(2);
+            let results = sharded.search_with("ax16", &options).unwrap();
+            assert_eq!(results[0].text, "ax16");
+            assert!(results.iter().all(|result| result.pruned));
+        }
+    }
+
+    #[test]
+    fn stop_grams_updates() {
+        let stop_grams = StopGrams::new()
+            .max_document_ratio(0.5)
+            .prune_postings(true);
+        let mut corpus = CorpusBuilder::default()
+            .stop_grams(stop_grams)
+            .fill(vec!["ab", "ac", "bd"])
+            .finish();
+        // " a" is in two of three entries
+        assert_eq!(corpus.stop_gram_count(), 1);
+        assert_eq!(corpus.document_frequency(" a"), 2);
+        assert!(corpus.search("ab", 0.0, 10)[0].pruned);
+        for text in ["be", "bf", "bg"] {
+            corpus.add_text(text);
+        }
+        assert_eq!(corpus.document_frequency(" b"), 4);
+        assert!(!corpus.search("bd", 0.0, 10)[0].pruned);
+        corpus.update_stop_grams();
+        // " b" becomes a stop gram, while " a" no longer is one, and the
+        // entries having it are indexed again
+        assert_eq!(corpus.stop_gram_count(), 1);
+        let results = corpus.search("bd", 0.0, 10);
+        assert_eq!(results[0].text, "bd");
+        assert!(results[0].pruned);
+        let results = corpus.search("ax", 0.0, 10);
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].pruned);
+        assert!(corpus.remove_text("be"));
+        assert_eq!(corpus.document_frequency(" b"), 3);
+    }
+}
@@ -0,0 +1,422 @@
+#![deny(missing_docs)]
+
+use std::io::BufRead;
+
+use crate::Error;
+
+/// Determines how each line read by `CorpusBuilder::fill_from_reader` is
+/// split into texts.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Splitter {
+    /// Each line is a single text.
+    Line,
+    /// Each whitespace separated word of a line is a text.
+    Whitespace,
+    /// Each field of a line, separated by the given character, is a text.
+    Delimiter(char),
+}
+
+impl Default for Splitter {
+    /// Default is `Line`, one text per line.
+    fn default() -> Self {
+        Splitter::Line
+    }
+}
+
+/// Settings for reading texts line by line with
+/// `CorpusBuilder::fill_from_reader`.
+///
+/// Texts are trimmed of surrounding whitespace, and blank texts are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub(crate) splitter: Splitter,
+    pub(crate) comment_prefix: Option<String>,
+}
+
+impl ReadOptions {
+    /// Initialize a new instance of `ReadOptions`, reading one text per line,
+    /// without skipping comments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how lines are split into texts.
+    pub fn splitter(mut self, splitter: Splitter) -> Self {
+        self.splitter = splitter;
+        self
+    }
+
+    /// Skip lines starting with `prefix`, ignoring leading whitespace.
+    pub fn comment_prefix(mut self, prefix: &str) -> Self {
+        self.comment_prefix = Some(prefix.to_string());
+        self
+    }
+}
+
+/// Identifies a column of delimited data, either by its zero-based index or
+/// by its name in the header row.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Column {
+    /// The zero-based index of the column
+    Index(usize),
+    /// The name of the column in the header row
+    Name(String),
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+/// Settings for reading texts, and optionally a payload for each of them,
+/// from the columns of delimited data such as CSV or TSV with
+/// `CorpusBuilder::fill_from_delimited`.
+///
+/// Rows whose text is blank are skipped.
+#[derive(Debug, Clone)]
+pub struct DelimitedOptions {
+    pub(crate) delimiter: char,
+    pub(crate) quote: Option<char>,
+    pub(crate) header: bool,
+    pub(crate) column: Column,
+    pub(crate) payload: Option<Column>,
+    pub(crate) comment_prefix: Option<String>,
+}
+
+impl Default for DelimitedOptions {
+    /// Default is `csv`.
+    fn default() -> Self {
+        Self::csv()
+    }
+}
+
+impl DelimitedOptions {
+    /// Comma separated values with a header row, where fields may be quoted
+    /// with `"` (doubled to escape it), and the texts are in the first column.
+    pub fn csv() -> Self {
+        DelimitedOptions {
+            delimiter: ',',
+            quote: Some('"'),
+            header: true,
+            column: Column::Index(0),
+            payload: None,
+            comment_prefix: None,
+        }
+    }
+
+    /// Tab separated values with a header row, where fields are never quoted,
+    /// and the texts are in the first column.
+    pub fn tsv() -> Self {
+        DelimitedOptions {
+            delimiter: '\t',
+            quote: None,
+            ..Self::csv()
+        }
+    }
+
+    /// Set the character separating fields.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set the character fields may be quoted with, if any.
+    pub fn quote(mut self, quote: Option<char>) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Set whether the first row is a header naming the columns, rather than
+    /// data.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Set the column holding the texts.
+    pub fn column<C: Into<Column>>(mut self, column: C) -> Self {
+        self.column = column.into();
+        self
+    }
+
+    /// Set the column holding the payload of each text.
+    pub fn payload_column<C: Into<Column>>(mut self, column: C) -> Self {
+        self.payload = Some(column.into());
+        self
+    }
+
+    /// Skip lines starting with `prefix`, ignoring leading whitespace.
+    pub fn comment_prefix(mut self, prefix: &str) -> Self {
+        self.comment_prefix = Some(prefix.to_string());
+        self
+    }
+}
+
+/// If `line` is a comment, as identified by the `prefix`.
+fn is_comment(line: &str, prefix: &Option<String>) -> bool {
+    prefix
+        .as_deref()
+        .map_or(false, |prefix| line.trim_start().starts_with(prefix))
+}
+
+/// Read texts line by line from the `reader`, as configured by the
+/// `options`, passing each one to `add`.
+pub(crate) fn read_texts<R, F>(
+    mut reader: R,
+    options: &ReadOptions,
+    mut add: F,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&str),
+{
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        if is_comment(&line, &options.comment_prefix) {
+            continue;
+        }
+        let mut add_trimmed = |text: &str| {
+            let text = text.trim();
+            if !text.is_empty() {
+                add(text);
+            }
+        };
+        match options.splitter {
+            Splitter::Line => add_trimmed(&line),
+            Splitter::Whitespace => line.split_whitespace().for_each(add_trimmed),
+            Splitter::Delimiter(delimiter) => line.split(delimiter).for_each(add_trimmed),
+        }
+    }
+}
+
+/// Read rows of delimited data from the `reader`, as configured by the
+/// `options`, passing the text and payload of each one to `add`.
+pub(crate) fn read_delimited<R, F>(
+    mut reader: R,
+    options: &DelimitedOptions,
+    mut add: F,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&str, Option<&str>),
+{
+    let mut record = String::new();
+    let mut fields: Vec<String> = Vec::new();
+    let mut line_number = 0;
+    let mut columns: Option<(usize, Option<usize>)> = None;
+    loop {
+        record.clear();
+        let start = line_number + 1;
+        line_number += read_record(&mut reader, options.quote, &mut record, start)?;
+        if record.is_empty() {
+            return Ok(());
+        }
+        let line = record.trim_end_matches(&['\r', '\n'][..]);
+        if line.trim().is_empty() || is_comment(line, &options.comment_prefix) {
+            continue;
+        }
+        split_fields(line, options.delimiter, options.quote, &mut fields);
+        let (column, payload) = match columns {
+            Some(columns) => columns,
+            None => {
+                let header = if options.header {
+                    Some(&fields[..])
+                } else {
+                    None
+                };
+                let resolved = (
+                    column_index(&options.column, header, start)?,
+                    options
+                        .payload
+                        .as_ref()
+                        .map(|payload| column_index(payload, header, start))
+                        .transpose()?,
+                );
+                columns = Some(resolved);
+                if options.header {
+                    continue;
+                }
+                resolved
+            }
+        };
+        let field = |index: usize| {
+            fields.get(index).map(String::as_str).ok_or(Error::Format {
+                line: start,
+                message: format!("missing column {}", index),
+            })
+        };
+        let text = field(column)?.trim();
+        let payload = payload.map(field).transpose()?;
+        if !text.is_empty() {
+            add(text, payload);
+        }
+    }
+}
+
+/// Resolve a `column` to its index, looking up named columns in the
+/// `header` read from line `line`.
+fn column_index(column: &Column, header: Option<&[String]>, line: usize) -> Result<usize, Error> {
+    match (column, header) {
+        (Column::Index(index), _) => Ok(*index),
+        (Column::Name(name), Some(header)) => header
+            .iter()
+            .position(|field| field.trim() == name)
+            .ok_or_else(|| Error::Format {
+                line,
+                message: format!("no column named {:?} in the header", name),
+            }),
+        (Column::Name(name), None) => Err(Error::Format {
+            line,
+            message: format!("column {:?} named without a header", name),
+        }),
+    }
+}
+
+/// Read a single record into `record`, which spans several lines when a
+/// quoted field contains line breaks. Returns the number of lines read.
+fn read_record<R: BufRead>(
+    reader: &mut R,
+    quote: Option<char>,
+    record: &mut String,
+    start: usize,
+) -> Result<usize, Error> {
+    let mut lines = 0;
+    loop {
+        if reader.read_line(record)? == 0 {
+            if lines > 0 {
+                return Err(Error::Format {
+                    line: start,
+                    message: "unterminated quoted field".to_string(),
+                });
+            }
+            return Ok(0);
+        }
+        lines += 1;
+        // Quotes are balanced unless a quoted field continues onto the next
+        // line
+        let balanced = quote.map_or(true, |quote| record.matches(quote).count() % 2 == 0);
+        if balanced {
+            return Ok(lines);
+        }
+    }
+}
+
+/// Split a `line` into its `fields`, separated by the `delimiter`, and
+/// optionally quoted with `quote`.
+fn split_fields(line: &str, delimiter: char, quote: Option<char>, fields: &mut Vec<String>) {
+    fields.clear();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if Some(c) == quote {
+            if quoted && chars.peek() == Some(&c) {
+                field.push(c);
+                chars.next();
+            } else {
+                quoted = !quoted;
+            }
+        } else if c == delimiter && !quoted {
+            fields.push(std::mem::take(&mut field));
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(input: &str, options: &ReadOptions) -> Vec<String> {
+        let mut texts = Vec::new();
+        read_texts(input.as_bytes(), options, |text| {
+            texts.push(text.to_string())
+        })
+        .unwrap();
+        texts
+    }
+
+    fn rows(
+        input: &str,
+        options: &DelimitedOptions,
+    ) -> Result<Vec<(String, Option<String>)>, Error> {
+        let mut rows = Vec::new();
+        read_delimited(input.as_bytes(), options, |text, payload| {
+            rows.push((text.to_string(), payload.map(str::to_string)))
+        })?;
+        Ok(rows)
+    }
+
+    #[test]
+    fn read_texts_splitters() {
+        let input = "# fruit\ntomato  potato\r\n\n  # more\ncarrot,pea\n";
+        let options = ReadOptions::new().comment_prefix("#");
+        assert_eq!(texts(input, &options), vec!["tomato  potato", "carrot,pea"]);
+        let options = options.splitter(Splitter::Whitespace);
+        assert_eq!(
+            texts(input, &options),
+            vec!["tomato", "potato", "carrot,pea"]
+        );
+        let options = options.splitter(Splitter::Delimiter(','));
+        assert_eq!(
+            texts(input, &options),
+            vec!["tomato  potato", "carrot", "pea"]
+        );
+        assert_eq!(texts(input, &ReadOptions::new()).len(), 4);
+    }
+
+    #[test]
+    fn read_delimited_columns() {
+        let input =
+            "id,name,note\n1,tomato,\"red, round\"\n2,\"po\"\"tato\",\"two\nlines\"\n3,,x\n";
+        let options = DelimitedOptions::csv().column("name").payload_column(2);
+        assert_eq!(
+            rows(input, &options).unwrap(),
+            vec![
+                ("tomato".to_string(), Some("red, round".to_string())),
+                ("po\"tato".to_string(), Some("two\nlines".to_string())),
+            ]
+        );
+        let options = DelimitedOptions::tsv().header(false).column(1);
+        assert_eq!(
+            rows("# comment\n1\ttomato\n", &options.comment_prefix("#")).unwrap(),
+            vec![("tomato".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn read_delimited_errors() {
+        let options = DelimitedOptions::csv().column("name");
+        assert!(matches!(
+            rows("id,title\n1,tomato\n", &options),
+            Err(Error::Format { line: 1, .. })
+        ));
+        let options = DelimitedOptions::csv().column(1);
+        assert!(matches!(
+            rows("id,name\n1,tomato\n2\n", &options),
+            Err(Error::Format { line: 3, .. })
+        ));
+        assert!(matches!(
+            rows("id,name\n1,\"tomato\n", &options),
+            Err(Error::Format { line: 2, .. })
+        ));
+        let options = DelimitedOptions::csv().header(false).column("name");
+        assert!(matches!(
+            rows("1,tomato\n", &options),
+            Err(Error::Format { .. })
+        ));
+    }
+}
@@ -0,0 +1,384 @@
+#![deny(missing_docs)]
+
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{Corpus, CorpusBuilder};
+use crate::search::compare_similarity;
+use crate::{KeyTransformer, SearchResult};
+
+/// The subset of `Corpus` functionality a `RecordCorpus` needs from each of
+/// its fields, allowing every field to use its own `KeyTransformer`.
+trait FieldCorpus: std::fmt::Debug + Send + Sync {
+    fn add_text(&mut self, text: &str);
+    fn transform_key(&self, text: &str) -> String;
+    fn search_with_warp(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<SearchResult>;
+}
+
+impl<KT> FieldCorpus for Corpus<KT>
+where
+    KT: KeyTransformer + Send + Sync,
+{
+    fn add_text(&mut self, text: &str) {
+        Corpus::add_text(self, text)
+    }
+
+    fn transform_key(&self, text: &str) -> String {
+        Corpus::transform_key(self, text)
+    }
+
+    fn search_with_warp(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<SearchResult> {
+        Corpus::search_with_warp(self, text, warp, threshold, limit)
+    }
+}
+
+/// A single named field of a `RecordCorpus`, with its own index.
+#[derive(Debug)]
+struct Field {
+    name: String,
+    weight: f32,
+    corpus: Box<dyn FieldCorpus>,
+    /// Maps the keys in `corpus` to the ids of the records they came from.
+    owners: HashMap<String, Vec<usize>>,
+}
+
+/// Holds a fuzzy match search result for a `RecordCorpus`, with the combined
+/// similarity of the record to the query, and the similarity of each of the
+/// fields that were queried.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordSearchResult {
+    /// The id of the matching record, as returned by `RecordCorpus::add_record`
+    pub id: usize,
+    /// The weighted mean of the similarities of all the queried fields
+    pub similarity: f32,
+    /// The similarity of each queried field, in the order the fields were
+    /// declared on the `RecordCorpusBuilder`
+    pub field_similarities: Vec<(String, f32)>,
+}
+
+/// Holds a corpus of records made up of named fields, each with its own
+/// index, allowing fuzzy matches of partial or complete records without
+/// producing grams that span two fields.
+#[derive(Debug)]
+pub struct RecordCorpus {
+    fields: Vec<Field>,
+    records: Vec<Vec<Option<String>>>,
+}
+
+impl RecordCorpus {
+    fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+
+    /// Add a record made up of `(field name, text)` pairs to the
+    /// `RecordCorpus`, returning the id used to identify it in search
+    /// results. Pairs naming a field that was not declared on the
+    /// `RecordCorpusBuilder` are ignored.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, RecordCorpusBuilder};
+    /// # fn main() {
+    /// let mut corpus = RecordCorpusBuilder::default()
+    ///     .field("name", 2.0, CorpusBuilder::default().case_insensitive())
+    ///     .field("city", 1.0, CorpusBuilder::default().case_insensitive())
+    ///     .finish();
+    /// let id = corpus.add_record(vec![("name", "Acme Corp"), ("city", "Berlin")]);
+    /// let results = corpus.search(&[("name", "acme corporation"), ("city", "berlin")], 0.4, 10);
+    /// assert_eq!(results[0].id, id);
+    /// # }
+    /// ```
+    pub fn add_record<'a, I>(&mut self, fields: I) -> usize
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let id = self.records.len();
+        let mut record = vec![None; self.fields.len()];
+        for (name, text) in fields {
+            if let Some(index) = self.field_index(name) {
+                let field = &mut self.fields[index];
+                field.corpus.add_text(text);
+                let owners = field
+                    .owners
+                    .entry(field.corpus.transform_key(text))
+                    .or_default();
+                if owners.last() != Some(&id) {
+                    owners.push(id);
+                }
+                record[index] = Some(text.to_string());
+            }
+        }
+        self.records.push(record);
+        id
+    }
+
+    /// Look up the `(field name, text)` pairs of the record with the given
+    /// `id`.
+    pub fn record(&self, id: usize) -> Option<Vec<(&str, &str)>> {
+        self.records.get(id).map(|record| {
+            self.fields
+                .iter()
+                .zip(record)
+                .filter_map(|(field, text)| text.as_deref().map(|t| (field.name.as_str(), t)))
+                .collect()
+        })
+    }
+
+    /// The number of records in the corpus.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// If the corpus is empty.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Perform a fuzzy search of the `RecordCorpus` for records above some
+    /// `threshold` of combined similarity to the supplied `(field name,
+    /// text)` pairs. Returns up to `limit` results, sorted by highest
+    /// similarity to lowest.
+    pub fn search(
+        &self,
+        query: &[(&str, &str)],
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<RecordSearchResult> {
+        self.search_with_warp(query, 2.0, threshold, limit)
+    }
+
+    /// Perform a fuzzy search of the `RecordCorpus` with a custom `warp` for
+    /// records above some `threshold` of combined similarity to the supplied
+    /// `(field name, text)` pairs. Returns up to `limit` results, sorted by
+    /// highest similarity to lowest.
+    ///
+    /// Only the fields present in the query contribute to the combined
+    /// similarity, which is the mean of their similarities weighted by the
+    /// field weights. A record without a value for a queried field scores 0.0
+    /// for that field. Pairs naming an undeclared field are ignored.
+    pub fn search_with_warp(
+        &self,
+        query: &[(&str, &str)],
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<RecordSearchResult> {
+        let mut queried: Vec<(usize, &str)> = query
+            .iter()
+            .filter_map(|(name, text)| self.field_index(name).map(|index| (index, *text)))
+            .collect();
+        queried.sort_by_key(|(index, _)| *index);
+        queried.dedup_by_key(|(index, _)| *index);
+        let total_weight: f32 = queried.iter().map(|(i, _)| self.fields[*i].weight).sum();
+
+        // Records which share no grams with a queried field score 0.0 for it,
+        // so the union of the per field candidates is complete.
+        let mut scores: HashMap<usize, Vec<f32>> = HashMap::new();
+        for (slot, (index, text)) in queried.iter().enumerate() {
+            let field = &self.fields[*index];
+            for result in field.corpus.search_with_warp(text, warp, 0.0, usize::MAX) {
+                for id in field.owners.get(&result.text).into_iter().flatten() {
+                    scores
+                        .entry(*id)
+                        .or_insert_with(|| vec![0.0; queried.len()])[slot] = result.similarity;
+                }
+            }
+        }
+
+        let mut results: Vec<RecordSearchResult> = scores
+            .into_iter()
+            .map(|(id, similarities)| {
+                let weighted: f32 = queried
+                    .iter()
+                    .zip(&similarities)
+                    .map(|((index, _), similarity)| self.fields[*index].weight * similarity)
+                    .sum();
+                RecordSearchResult {
+                    id,
+                    similarity: if total_weight > 0.0 {
+                        weighted / total_weight
+                    } else {
+                        0.0
+                    },
+                    field_similarities: queried
+                        .iter()
+                        .zip(similarities)
+                        .map(|((index, _), similarity)| {
+                            (self.fields[*index].name.clone(), similarity)
+                        })
+                        .collect(),
+                }
+            })
+            .filter(|result| result.similarity >= threshold)
+            .collect();
+
+        // Sort highest similarity to lowest, then by id
+        results.sort_by(|a, b| {
+            compare_similarity(b.similarity, a.similarity).then_with(|| a.id.cmp(&b.id))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+/// Build a `RecordCorpus`, one field at a time.
+#[derive(Debug, Default)]
+pub struct RecordCorpusBuilder {
+    fields: Vec<Field>,
+}
+
+impl RecordCorpusBuilder {
+    /// Declare a field named `name`, indexed with the arity, padding and key
+    /// transformer set on the supplied `CorpusBuilder`, whose similarity
+    /// carries the given `weight` (clamped to be non-negative) in combined
+    /// scores. Declaring a field a second time replaces the earlier
+    /// declaration.
+    ///
+    /// Only the settings of the `CorpusBuilder` are used. Any texts filled
+    /// into it don't belong to a record, and are left out of the index.
+    pub fn field<KT>(mut self, name: &str, weight: f32, builder: CorpusBuilder<KT>) -> Self
+    where
+        KT: KeyTransformer + Send + Sync + 'static,
+    {
+        let field = Field {
+            name: name.to_string(),
+            weight: weight.max(0.0),
+            corpus: Box::new(builder.into_settings()),
+            owners: HashMap::new(),
+        };
+        match self.fields.iter_mut().find(|f| f.name == name) {
+            Some(existing) => *existing = field,
+            None => self.fields.push(field),
+        }
+        self
+    }
+
+    /// Yield a `RecordCorpus` instance with all the fields declared with this
+    /// builder.
+    pub fn finish(self) -> RecordCorpus {
+        RecordCorpus {
+            fields: self.fields,
+            records: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pad;
+
+    fn companies() -> RecordCorpus {
+        let mut corpus = RecordCorpusBuilder::default()
+            .field(
+                "name",
+                2.0,
+                CorpusBuilder::default().arity(3).case_insensitive(),
+            )
+            .field("city", 1.0, CorpusBuilder::default().case_insensitive())
+            .field(
+                "street",
+                1.0,
+                CorpusBuilder::default().arity(1).pad_full(Pad::None),
+            )
+            .finish();
+        corpus.add_record(vec![
+            ("name", "Acme"),
+            ("city", "Berlin"),
+            ("street", "Hauptstr"),
+        ]);
+        corpus.add_record(vec![("name", "Acme"), ("city", "Hamburg")]);
+        corpus.add_record(vec![("name", "Initech"), ("city", "Berlin")]);
+        corpus
+    }
+
+    #[test]
+    fn record_corpus_records() {
+        let corpus = companies();
+        assert_eq!(corpus.len(), 3);
+        assert_eq!(
+            corpus.record(1),
+            Some(vec![("name", "Acme"), ("city", "Hamburg")])
+        );
+        assert_eq!(corpus.record(3), None);
+    }
+
+    #[test]
+    fn record_corpus_search_all_fields() {
+        let corpus = companies();
+        let results = corpus.search(&[("name", "ACME"), ("city", "berlin")], 0.1, 10);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].id, 0);
+        assert_eq!(results[0].similarity, 1.0);
+        assert_eq!(
+            results[0].field_similarities,
+            vec![("name".to_string(), 1.0), ("city".to_string(), 1.0)]
+        );
+    }
+
+    #[test]
+    fn record_corpus_search_some_fields() {
+        let corpus = companies();
+        let results = corpus.search(&[("city", "Berlin")], 0.9, 10);
+        let mut ids: Vec<usize> = results.iter().map(|r| r.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 2]);
+    }
+
+    #[test]
+    fn record_corpus_field_weights() {
+        let corpus = companies();
+        // A perfect name match outweighs a perfect city match
+        let results = corpus.search(&[("name", "acme"), ("city", "hamburg")], 0.0, 10);
+        assert_eq!(results[0].id, 1);
+        let results = corpus.search(&[("name", "initech"), ("city", "hamburg")], 0.0, 10);
+        assert_eq!(results[0].id, 2);
+        assert!((results[0].similarity - 2.0 / 3.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn record_corpus_no_cross_field_grams() {
+        let corpus = companies();
+        // "meberlin" only resembles record 0 when its name and city are
+        // concatenated
+        assert!(corpus.search(&[("name", "meberlin")], 0.5, 10).is_empty());
+    }
+
+    #[test]
+    fn record_corpus_unknown_field() {
+        let corpus = companies();
+        assert!(corpus.search(&[("country", "Germany")], 0.0, 10).is_empty());
+    }
+
+    #[test]
+    fn record_corpus_filled_builder() {
+        let builder = CorpusBuilder::default().fill(vec!["Acme", "Initech"]);
+        let mut corpus = RecordCorpusBuilder::default()
+            .field("name", 1.0, builder)
+            .finish();
+        let id = corpus.add_record(vec![("name", "Acme Corp")]);
+        let texts: Vec<String> = corpus.fields[0]
+            .corpus
+            .search_with_warp("Acme", 2.0, 0.0, usize::MAX)
+            .into_iter()
+            .map(|result| result.text)
+            .collect();
+        assert_eq!(texts, vec!["Acme Corp"]);
+        let results = corpus.search(&[("name", "Acme")], 0.0, 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, id);
+    }
+}
@@ -0,0 +1,551 @@
+#![deny(missing_docs)]
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Corpus, Error, KeyTransformer, SearchOptions, SearchResult};
+
+/// The most bytes of a line read at a time by a `Scanner`.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Settings for scanning text for fuzzy matches of the entries of a
+/// `Corpus` with `Corpus::scan`.
+///
+/// Each line of the input is split into tokens, the runs of alphanumeric
+/// characters, and every window of up to `max_words` consecutive tokens is
+/// looked up in the corpus. By default only single tokens are looked up,
+/// only the best entry with a similarity of at least 0.8 is reported for
+/// each window, and overlapping matches are resolved in favour of the most
+/// similar.
+///
+/// Lines are read 64 KiB at a time. The windows of a longer line are looked
+/// up a piece at a time, and its matches are resolved against those already
+/// reported. Tokens longer than that are split.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub(crate) max_words: usize,
+    pub(crate) search: SearchOptions,
+    pub(crate) overlapping: bool,
+    pub(crate) chunk_bytes: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            max_words: 1,
+            search: SearchOptions::new().threshold(0.8).limit(1),
+            overlapping: false,
+            chunk_bytes: CHUNK_BYTES,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Initialize a new instance of `ScanOptions` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the largest number of consecutive tokens looked up together, for
+    /// matching entries of several words. At least one.
+    pub fn max_words(mut self, max_words: usize) -> Self {
+        self.max_words = max_words.max(1);
+        self
+    }
+
+    /// Set the minimum similarity of matches, in the range of 0.0 to 1.0.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.search = self.search.threshold(threshold);
+        self
+    }
+
+    /// Set the maximum number of entries matched by each window.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.search = self.search.limit(limit);
+        self
+    }
+
+    /// Set the options each window is looked up with, replacing any
+    /// `threshold` or `limit` set before.
+    pub fn search_options(mut self, options: SearchOptions) -> Self {
+        self.search = options;
+        self
+    }
+
+    /// Set whether matches may overlap. If not, of any overlapping matches
+    /// only the most similar is reported, preferring the longest, then the
+    /// earliest.
+    pub fn overlapping(mut self, overlapping: bool) -> Self {
+        self.overlapping = overlapping;
+        self
+    }
+}
+
+/// A span of scanned text that fuzzily matches an entry of a `Corpus`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanMatch {
+    /// The line of the span, counting from 1
+    pub line: usize,
+    /// The byte offset of the span within its line
+    pub column: usize,
+    /// The byte offset of the start of the span within the input
+    pub start: usize,
+    /// The byte offset of the end of the span within the input
+    pub end: usize,
+    /// The text of the span
+    pub text: String,
+    /// The matched entry, and its similarity to the text of the span
+    pub result: SearchResult,
+}
+
+/// Streams the fuzzy matches of the entries of a `Corpus` in some input, a
+/// line at a time, as returned by `Corpus::scan`.
+///
+/// Yields the matches of each line in the order they appear, or an error if
+/// reading fails, after which it ends. Bytes that aren't valid UTF-8 are
+/// treated as separators between tokens.
+pub struct Scanner<'a, R, KT>
+where
+    KT: KeyTransformer,
+{
+    corpus: &'a Corpus<KT>,
+    reader: BufReader<R>,
+    options: ScanOptions,
+    buffer: Vec<u8>,
+    line: usize,
+    column: usize,
+    offset: usize,
+    continued: bool,
+    reported: Vec<ScanMatch>,
+    pending: std::vec::IntoIter<ScanMatch>,
+    done: bool,
+}
+
+impl<'a, R, KT> std::fmt::Debug for Scanner<'a, R, KT>
+where
+    KT: KeyTransformer,
+{
+    /// Debug format for a `Scanner`. Omits the corpus and reader, showing
+    /// only how far it has read.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Scanner")
+            .field("options", &self.options)
+            .field("line", &self.line)
+            .field("column", &self.column)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+impl<KT> Corpus<KT>
+where
+    KT: KeyTransformer + Sync,
+{
+    /// Scan the text read from `reader` for fuzzy matches of the entries of
+    /// the `Corpus`, as configured by the `options`, such as to find the
+    /// lines of a log that roughly mention any of a list of names.
+    ///
+    /// Fails with `Error::InvalidThreshold` or `Error::InvalidWarp` if the
+    /// search options are invalid.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, ScanOptions};
+    /// # fn main() -> Result<(), ngrammatic::Error> {
+    /// let corpus = CorpusBuilder::default()
+    ///     .case_insensitive()
+    ///     .fill(vec!["acme widget", "sprocket"])
+    ///     .finish();
+    /// let log = "shipped 2 sprokets\nreturned: Acme Widgt (damaged)\n";
+    /// let options = ScanOptions::new().max_words(2).threshold(0.5);
+    /// let matches = corpus
+    ///     .scan(log.as_bytes(), &options)?
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(matches.len(), 2);
+    /// assert_eq!((matches[0].line, matches[0].column), (1, 10));
+    /// assert_eq!(matches[0].result.text, "sprocket");
+    /// assert_eq!(matches[1].text, "Acme Widgt");
+    /// assert_eq!(&log[matches[1].start..matches[1].end], "Acme Widgt");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scan<R: Read>(
+        &self,
+        reader: R,
+        options: &ScanOptions,
+    ) -> Result<Scanner<'_, R, KT>, Error> {
+        options.search.validate()?;
+        Ok(Scanner {
+            corpus: self,
+            reader: BufReader::new(reader),
+            options: options.clone(),
+            buffer: Vec::new(),
+            line: 0,
+            column: 0,
+            offset: 0,
+            continued: false,
+            reported: Vec::new(),
+            pending: Vec::new().into_iter(),
+            done: false,
+        })
+    }
+}
+
+impl<'a, R, KT> Scanner<'a, R, KT>
+where
+    R: Read,
+    KT: KeyTransformer + Sync,
+{
+    /// Find the matches in the text in `buffer`, and drop the text they were
+    /// found in. Unless the buffer holds the `line_end`, the windows that may
+    /// go on past the end of the buffer are left to be scanned with the text
+    /// that follows.
+    fn scan_chunk(&mut self, line_end: bool) -> Vec<ScanMatch> {
+        let (text, split) = if line_end {
+            (trim_line_end(&self.buffer), self.buffer.len())
+        } else {
+            match self.split_point() {
+                split if self.buffer.len() - split < self.options.chunk_bytes => {
+                    (&self.buffer[..], split)
+                }
+                // A token too long to carry over is split
+                _ => (&self.buffer[..], self.buffer.len()),
+            }
+        };
+        let mut matches = Vec::new();
+        for (segment_start, segment) in utf8_segments(text) {
+            let tokens = tokens(segment);
+            for (first, &(start, _)) in tokens.iter().enumerate() {
+                let position = segment_start + start;
+                if position >= split {
+                    break;
+                }
+                let windows = tokens[first..].iter().take(self.options.max_words);
+                for &(_, end) in windows {
+                    let text = &segment[start..end];
+                    // Can't fail, as the options were validated before scanning
+                    let results = self
+                        .corpus
+                        .search_with(text, &self.options.search)
+                        .unwrap_or_default();
+                    matches.extend(results.into_iter().map(|result| ScanMatch {
+                        line: self.line,
+                        column: self.column + position,
+                        start: self.offset + position,
+                        end: self.offset + segment_start + end,
+                        text: text.to_string(),
+                        result,
+                    }));
+                }
+            }
+        }
+        if !self.options.overlapping {
+            matches = without_overlaps(matches, &self.reported);
+        }
+        matches.sort_by_key(|m| (m.start, m.end));
+
+        self.buffer.drain(..split);
+        self.offset += split;
+        if line_end {
+            self.column = 0;
+            self.reported.clear();
+        } else {
+            self.column += split;
+            // Only matches reaching past the split can overlap later ones
+            let offset = self.offset;
+            self.reported = matches.iter().filter(|m| m.end > offset).cloned().collect();
+        }
+        self.continued = !line_end;
+        matches
+    }
+
+    /// The offset in the unfinished line in `buffer` of the first token with
+    /// windows that may go on past the end of the buffer, or of the end of
+    /// the text, if there are none.
+    fn split_point(&self) -> usize {
+        let end = self.buffer.len() - incomplete_char_len(&self.buffer);
+        match utf8_segments(&self.buffer[..end]).last() {
+            Some(&(segment_start, segment)) if segment_start + segment.len() == end => {
+                let tokens = tokens(segment);
+                let open = tokens
+                    .last()
+                    .map_or(false, |&(_, last)| last == segment.len());
+                let complete = tokens.len() - usize::from(open);
+                let first_open = (complete + 1).saturating_sub(self.options.max_words);
+                tokens
+                    .get(first_open)
+                    .map_or(end, |&(start, _)| segment_start + start)
+            }
+            // Invalid bytes separate the text from what follows
+            _ => end,
+        }
+    }
+}
+
+impl<'a, R, KT> Iterator for Scanner<'a, R, KT>
+where
+    R: Read,
+    KT: KeyTransformer + Sync,
+{
+    type Item = Result<ScanMatch, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(next) = self.pending.next() {
+                return Some(Ok(next));
+            }
+            if self.done {
+                return None;
+            }
+            let carried = self.buffer.len();
+            let limit = self.options.chunk_bytes;
+            let mut chunk = (&mut self.reader).take(limit as u64);
+            match chunk.read_until(b'\n', &mut self.buffer) {
+                Ok(0) if carried == 0 => self.done = true,
+                Ok(read) => {
+                    if !self.continued {
+                        self.line += 1;
+                    }
+                    let line_end = read < limit || self.buffer.ends_with(b"\n");
+                    self.pending = self.scan_chunk(line_end).into_iter();
+                }
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error.into()));
+                }
+            }
+        }
+    }
+}
+
+/// Strip the line break from the end of a `line`.
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// The number of bytes at the end of `bytes` that start a UTF-8 character,
+/// without finishing it.
+fn incomplete_char_len(bytes: &[u8]) -> usize {
+    for len in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - len];
+        if byte & 0xc0 != 0x80 {
+            // The leading byte of a character, or an ASCII byte
+            let width = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            return if width > len { len } else { 0 };
+        }
+    }
+    0
+}
+
+/// Split `bytes` into its valid UTF-8 segments, with their byte offsets.
+fn utf8_segments(mut bytes: &[u8]) -> Vec<(usize, &str)> {
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    while !bytes.is_empty() {
+        let (valid, skip) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, bytes.len()),
+            Err(error) => {
+                let valid_up_to = error.valid_up_to();
+                // Can't fail, as the bytes up to there are valid
+                let valid = std::str::from_utf8(&bytes[..valid_up_to]).unwrap_or_default();
+                let invalid = error.error_len().unwrap_or(bytes.len() - valid_up_to);
+                (valid, valid_up_to + invalid)
+            }
+        };
+        if !valid.is_empty() {
+            segments.push((offset, valid));
+        }
+        offset += skip;
+        bytes = &bytes[skip..];
+    }
+    segments
+}
+
+/// The byte ranges of the runs of alphanumeric characters in `text`.
+fn tokens(text: &str) -> Vec<(usize, usize)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(index),
+            (false, Some(token_start)) => {
+                tokens.push((token_start, index));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(token_start) = start {
+        tokens.push((token_start, text.len()));
+    }
+    tokens
+}
+
+/// Keep only the best of any overlapping `matches`: the most similar, then
+/// the longest, then the earliest. Matches overlapping any of those already
+/// `reported` are dropped.
+fn without_overlaps(mut matches: Vec<ScanMatch>, reported: &[ScanMatch]) -> Vec<ScanMatch> {
+    matches.sort_by(|a, b| {
+        b.result
+            .cmp(&a.result)
+            .then_with(|| (b.end - b.start).cmp(&(a.end - a.start)))
+            .then_with(|| a.start.cmp(&b.start))
+    });
+    // The spans kept so far don't overlap, so a candidate can only overlap
+    // the last of them that starts before it ends
+    let mut kept: BTreeMap<usize, usize> = reported.iter().map(|m| (m.start, m.end)).collect();
+    matches.retain(|candidate| {
+        let overlaps = kept
+            .range(..candidate.end)
+            .next_back()
+            .map_or(false, |(_, &end)| candidate.start < end);
+        if !overlaps {
+            kept.insert(candidate.start, candidate.end);
+        }
+        !overlaps
+    });
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CorpusBuilder;
+    use std::io;
+
+    fn spans(matches: &[ScanMatch]) -> Vec<(usize, usize, &str, &str)> {
+        matches
+            .iter()
+            .map(|m| (m.line, m.column, m.text.as_str(), m.result.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn scan_windows_and_overlaps() {
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(vec!["acme widget", "widget", "gizmo pro"])
+            .finish();
+        let input = "order: ACME widget, gizmo-pro\r\n\nwidgets gizmo\n";
+        let options = ScanOptions::new().max_words(2).threshold(0.6);
+        let matches: Vec<ScanMatch> = corpus
+            .scan(input.as_bytes(), &options)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            spans(&matches),
+            vec![
+                (1, 7, "ACME widget", "acme widget"),
+                (1, 20, "gizmo-pro", "gizmo pro"),
+                (3, 0, "widgets", "widget"),
+                (3, 8, "gizmo", "gizmo pro"),
+            ]
+        );
+        for m in &matches {
+            assert_eq!(&input[m.start..m.end], m.text);
+        }
+
+        let options = options.overlapping(true).threshold(0.9);
+        let matches: Vec<ScanMatch> = corpus
+            .scan(input.as_bytes(), &options)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            spans(&matches),
+            vec![
+                (1, 7, "ACME widget", "acme widget"),
+                (1, 12, "widget", "widget"),
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_long_lines() {
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(vec!["acme widget", "widget", "gizmo pro", "café"])
+            .finish();
+        let input = "order: ACME widget, gizmo-pro, café widgets\nacme widget gizmo\n";
+        let options = ScanOptions::new().max_words(2).threshold(0.6);
+        let scan = |options: &ScanOptions| -> Vec<ScanMatch> {
+            corpus
+                .scan(input.as_bytes(), options)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        let expected = scan(&options);
+        assert_eq!(expected.len(), 6);
+        // Lines read in pieces long enough to hold any two tokens have the
+        // same matches
+        for chunk_bytes in 16..=input.len() {
+            let mut options = options.clone();
+            options.chunk_bytes = chunk_bytes;
+            assert_eq!(scan(&options), expected, "chunks of {}", chunk_bytes);
+        }
+    }
+
+    #[test]
+    fn scan_without_line_breaks() {
+        let corpus = CorpusBuilder::default().fill(vec!["tomato"]).finish();
+        let input = "tomato, ".repeat(1000);
+        let mut options = ScanOptions::new().max_words(3);
+        options.chunk_bytes = 64;
+        let mut scanner = corpus.scan(input.as_bytes(), &options).unwrap();
+        let mut starts = Vec::new();
+        while let Some(m) = scanner.next() {
+            let m = m.unwrap();
+            assert_eq!((m.line, m.column), (1, m.start));
+            assert!(scanner.buffer.len() < 2 * 64);
+            starts.push(m.start);
+        }
+        let expected: Vec<usize> = (0..1000).map(|i| i * 8).collect();
+        assert_eq!(starts, expected);
+        // Tokens longer than a piece are split
+        let input = "x".repeat(100);
+        let matches = corpus.scan(input.as_bytes(), &options).unwrap().count();
+        assert_eq!(matches, 0);
+    }
+
+    #[test]
+    fn scan_invalid_utf8() {
+        let corpus = CorpusBuilder::default().fill(vec!["tomato"]).finish();
+        let input = b"\xfftomato\xc3 tomato\n";
+        let matches: Vec<ScanMatch> = corpus
+            .scan(&input[..], &ScanOptions::new())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let starts: Vec<usize> = matches.iter().map(|m| m.start).collect();
+        assert_eq!(starts, vec![1, 9]);
+    }
+
+    #[test]
+    fn scan_errors() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "broken"))
+            }
+        }
+        let corpus = CorpusBuilder::default().fill(vec!["tomato"]).finish();
+        let mut scanner = corpus.scan(Failing, &ScanOptions::new()).unwrap();
+        assert!(matches!(scanner.next(), Some(Err(Error::Io { .. }))));
+        assert!(scanner.next().is_none());
+        let invalid = ScanOptions::new().threshold(1.5);
+        assert!(matches!(
+            corpus.scan(&b""[..], &invalid),
+            Err(Error::InvalidThreshold(_))
+        ));
+    }
+}
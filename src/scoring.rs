@@ -0,0 +1,351 @@
+#![deny(missing_docs)]
+
+use std::collections::HashMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::{Corpus, KeyTransformer, Ngram};
+
+/// Determines how the similarity of a corpus entry to a query is computed
+/// from the grams they share.
+///
+/// The weighted modes weigh each gram by its inverse document frequency
+/// (IDF) in the corpus, so that common grams like `" t"` or `"e "` count for
+/// less than rare ones, and ignore the `warp` factor. Their similarities are
+/// in the range of 0.0 to 1.0 too, but aren't on the same scale as n-gram
+/// similarities, so thresholds may need adjusting. A `ShardedCorpus` or
+/// `ConcurrentCorpus` weighs grams by their document frequencies over all
+/// its shards, so its scores are the same as those of a single `Corpus`
+/// with the same entries.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Scoring {
+    /// The warped ratio of shared grams to all grams, where every gram
+    /// counts the same.
+    Ngram,
+    /// The IDF weighted Jaccard similarity: the weight of the shared gram
+    /// occurrences over the weight of all gram occurrences.
+    WeightedJaccard,
+    /// The cosine of the angle between the IDF weighted gram count vectors.
+    /// Gram positions are ignored.
+    WeightedCosine,
+}
+
+impl Default for Scoring {
+    /// Default is `Ngram`.
+    fn default() -> Self {
+        Scoring::Ngram
+    }
+}
+
+/// Weighs a gram by its inverse document frequency in a corpus.
+pub(crate) type Idf<'a> = dyn Fn(&str) -> f32 + Sync + 'a;
+
+/// The inverse document frequencies of grams over all the `shards` of a
+/// corpus, so that every shard weighs grams the same. Those of the query's
+/// grams, which every candidate needs, are computed up front.
+pub(crate) struct ShardedIdf<'a, KT>
+where
+    KT: KeyTransformer,
+{
+    shards: Vec<&'a Corpus<KT>>,
+    entries: usize,
+    query: HashMap<SmolStr, f32>,
+}
+
+impl<'a, KT> ShardedIdf<'a, KT>
+where
+    KT: KeyTransformer + Sync,
+{
+    /// Weigh grams over the given `shards`, starting with those of the
+    /// `query`, unless the `scoring` isn't weighted.
+    pub(crate) fn new(shards: Vec<&'a Corpus<KT>>, query: &Ngram, scoring: Scoring) -> Self {
+        let entries = shards.iter().map(|shard| shard.len()).sum();
+        let mut idf = ShardedIdf {
+            shards,
+            entries,
+            query: HashMap::new(),
+        };
+        if scoring != Scoring::Ngram {
+            idf.query = query
+                .grams
+                .keys()
+                .map(|gram| (gram.clone(), idf.compute(gram)))
+                .collect();
+        }
+        idf
+    }
+
+    /// The inverse document frequency of the `gram` over all the shards.
+    pub(crate) fn weight(&self, gram: &str) -> f32 {
+        match self.query.get(gram) {
+            Some(weight) => *weight,
+            None => self.compute(gram),
+        }
+    }
+
+    fn compute(&self, gram: &str) -> f32 {
+        let document_frequency = self
+            .shards
+            .iter()
+            .map(|shard| shard.document_frequency(gram))
+            .sum();
+        inverse_document_frequency(document_frequency, self.entries)
+    }
+}
+
+/// The inverse document frequency of a gram occurring in
+/// `document_frequency` of `documents` entries, as defined by BM25 but never
+/// negative. Grams that occur in no entry get the largest weight.
+pub(crate) fn inverse_document_frequency(document_frequency: usize, documents: usize) -> f32 {
+    let documents = documents.max(document_frequency) as f32;
+    let document_frequency = document_frequency as f32;
+    (1.0 + (documents - document_frequency + 0.5) / (document_frequency + 0.5)).ln()
+}
+
+impl Scoring {
+    /// The similarity of the `candidate` to the `query`, with the given
+    /// `warp` factor for n-gram similarities, and the `idf` of each gram for
+    /// weighted ones. When grams of several sizes were generated, this is
+    /// the weighted mean of the similarities of each size.
+    pub(crate) fn similarity<F>(self, query: &Ngram, candidate: &Ngram, warp: f32, idf: F) -> f32
+    where
+        F: Fn(&str) -> f32,
+    {
+        if self == Scoring::Ngram {
+            return query.similarity_to(candidate, warp);
+        }
+        if query.arities.is_empty() {
+            return self.weighted_similarity(query, candidate, None, &idf);
+        }
+        let mut weighted = 0.0;
+        let mut total_weight = 0.0;
+        for &(arity, weight) in &query.arities {
+            weighted += weight * self.weighted_similarity(query, candidate, Some(arity), &idf);
+            total_weight += weight;
+        }
+        if total_weight > 0.0 {
+            weighted / total_weight
+        } else {
+            0.0
+        }
+    }
+
+    /// The weighted similarity of the `candidate` to the `query`, over the
+    /// grams of the given `size`, or all grams if `None`. Zero for the
+    /// `Ngram` scoring, which isn't weighted.
+    pub(crate) fn weighted_similarity<F>(
+        self,
+        query: &Ngram,
+        candidate: &Ngram,
+        size: Option<usize>,
+        idf: &F,
+    ) -> f32
+    where
+        F: Fn(&str) -> f32,
+    {
+        let sized = |gram: &&str| size.map_or(true, |size| gram.chars().count() == size);
+        let query_grams = query
+            .grams
+            .iter()
+            .map(|(gram, count)| (gram.as_str(), *count as f64))
+            .filter(|(gram, _)| sized(gram));
+        let candidate_grams = candidate
+            .grams
+            .iter()
+            .map(|(gram, count)| (gram.as_str(), *count as f64))
+            .filter(|(gram, _)| sized(gram));
+        // Summing in f64 keeps the result the same whatever order the grams
+        // are visited in
+        let idf = |gram: &str| f64::from(idf(gram));
+        let similarity = match self {
+            Scoring::Ngram => return 0.0,
+            Scoring::WeightedJaccard => {
+                // Shared occurrences count once in the weight of all of them
+                let mut same = 0.0;
+                let mut all = 0.0;
+                for (gram, count) in query_grams {
+                    let weight = idf(gram);
+                    same += weight * query.count_shared(candidate, gram) as f64;
+                    all += weight * count;
+                }
+                for (gram, count) in candidate_grams {
+                    let unshared = count - candidate.count_shared(query, gram) as f64;
+                    if unshared > 0.0 {
+                        all += idf(gram) * unshared;
+                    }
+                }
+                if all > 0.0 {
+                    same / all
+                } else {
+                    0.0
+                }
+            }
+            Scoring::WeightedCosine => {
+                let mut dot = 0.0;
+                let mut query_norm = 0.0;
+                for (gram, count) in query_grams {
+                    let weight = idf(gram);
+                    let weight = weight * weight;
+                    dot += weight * count * candidate.count_gram(gram) as f64;
+                    query_norm += weight * count * count;
+                }
+                let candidate_norm: f64 = candidate_grams
+                    .map(|(gram, count)| {
+                        let weight = idf(gram);
+                        weight * weight * count * count
+                    })
+                    .sum();
+                if query_norm > 0.0 && candidate_norm > 0.0 {
+                    dot / (query_norm.sqrt() * candidate_norm.sqrt())
+                } else {
+                    0.0
+                }
+            }
+        };
+        (similarity as f32).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CorpusBuilder, NgramBuilder, SearchOptions};
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0001
+    }
+
+    #[test]
+    fn idf_weights() {
+        assert!(inverse_document_frequency(0, 100) > inverse_document_frequency(1, 100));
+        assert!(inverse_document_frequency(1, 100) > inverse_document_frequency(50, 100));
+        assert!(inverse_document_frequency(100, 100) > 0.0);
+        assert!(inverse_document_frequency(5, 3) > 0.0);
+    }
+
+    #[test]
+    fn weighted_similarities() {
+        let a = NgramBuilder::new("banana").arity(2).finish();
+        let b = NgramBuilder::new("bandana").arity(2).finish();
+        let unweighted = |_: &str| 1.0;
+        // With equal weights, the weighted Jaccard similarity is the
+        // unwarped n-gram similarity
+        assert!(approx_eq(
+            Scoring::WeightedJaccard.similarity(&a, &b, 2.0, unweighted),
+            a.similarity_to(&b, 1.0)
+        ));
+        // a: " b" "ba" "an"x2 "na"x2 "a ", b: " b" "ba" "an"x2 "nd" "da" "na" "a "
+        let dot = 1.0 + 1.0 + 4.0 + 2.0 + 1.0;
+        let cosine = dot / (11.0f32.sqrt() * 10.0f32.sqrt());
+        assert!(approx_eq(
+            Scoring::WeightedCosine.similarity(&a, &b, 2.0, unweighted),
+            cosine
+        ));
+        for scoring in [Scoring::WeightedJaccard, Scoring::WeightedCosine] {
+            assert!(approx_eq(scoring.similarity(&a, &a, 2.0, unweighted), 1.0));
+            let c = NgramBuilder::new("xyz").arity(2).finish();
+            assert_eq!(scoring.similarity(&a, &c, 2.0, unweighted), 0.0);
+        }
+        // Weighing the grams they differ by more lowers the similarity
+        let rare = |gram: &str| {
+            if gram == "nd" || gram == "da" {
+                5.0
+            } else {
+                1.0
+            }
+        };
+        assert!(
+            Scoring::WeightedJaccard.similarity(&a, &b, 2.0, rare)
+                < Scoring::WeightedJaccard.similarity(&a, &b, 2.0, unweighted)
+        );
+    }
+
+    #[test]
+    fn corpus_search_weighted() {
+        // Every entry shares the common "the " prefix with the query, but
+        // only one shares its rare grams
+        let corpus = CorpusBuilder::default()
+            .fill(vec![
+                "the cat",
+                "the dog",
+                "the cow",
+                "the hen",
+                "the pig",
+                "the quokka",
+            ])
+            .finish();
+        let ngram = corpus
+            .search_with("the quokkas", &SearchOptions::new().limit(6))
+            .unwrap();
+        for scoring in [Scoring::WeightedJaccard, Scoring::WeightedCosine] {
+            let options = SearchOptions::new().scoring(scoring).limit(6);
+            let weighted = corpus.search_with("the quokkas", &options).unwrap();
+            assert_eq!(weighted[0].text, "the quokka");
+            assert!(weighted.iter().all(|result| result.similarity <= 1.0));
+            // Common grams count for less, so the entries sharing only them
+            // score lower relative to the best match
+            let ratio =
+                |results: &[crate::SearchResult]| results[1].similarity / results[0].similarity;
+            assert!(ratio(&weighted) < ratio(&ngram));
+            let threshold = weighted[1].similarity + 0.01;
+            let options = options.threshold(threshold);
+            assert_eq!(
+                corpus.search_with("the quokkas", &options).unwrap().len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn sharded_weights_match_corpus() {
+        let words = vec![
+            "tomato",
+            "potato",
+            "tornado",
+            "avocado",
+            "carrot",
+            "tomatillo",
+            "pomato",
+        ];
+        let corpus = CorpusBuilder::default().fill(&words).finish();
+        let sharded = CorpusBuilder::default().fill(&words).finish_sharded(8);
+        let concurrent = CorpusBuilder::default().fill(&words).finish_concurrent(3);
+        for scoring in [Scoring::WeightedJaccard, Scoring::WeightedCosine] {
+            let options = SearchOptions::new().scoring(scoring).limit(10);
+            let expected = corpus.search_with("tomacco", &options).unwrap();
+            assert!(!expected.is_empty());
+            let similarities = |results: Vec<crate::SearchResult>| -> Vec<(String, f32)> {
+                results
+                    .into_iter()
+                    .map(|result| (result.text, result.similarity))
+                    .collect()
+            };
+            let expected = similarities(expected);
+            let found = similarities(sharded.search_with("tomacco", &options).unwrap());
+            assert_eq!(found, expected);
+            let found = similarities(concurrent.search_with("tomacco", &options).unwrap());
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn weighted_mixed_arity() {
+        let a = NgramBuilder::new("tomato").arities(1..=2).finish();
+        let b = NgramBuilder::new("potato").arities(1..=2).finish();
+        let idf = |_: &str| 1.0;
+        let expected = (Scoring::WeightedJaccard.weighted_similarity(&a, &b, Some(1), &idf)
+            + Scoring::WeightedJaccard.weighted_similarity(&a, &b, Some(2), &idf))
+            / 2.0;
+        assert!(approx_eq(
+            Scoring::WeightedJaccard.similarity(&a, &b, 2.0, idf),
+            expected
+        ));
+        assert!(approx_eq(
+            Scoring::WeightedJaccard.similarity(&a, &b, 2.0, idf),
+            a.similarity_to(&b, 1.0)
+        ));
+    }
+}
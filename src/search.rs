@@ -0,0 +1,292 @@
+#![deny(missing_docs)]
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Rerank, Scoring, SearchResult};
+
+// Import traits for rayon parallelization
+#[cfg(feature = "rayon")]
+use rayon::{iter::IntoParallelRefIterator, iter::ParallelIterator, slice::ParallelSliceMut};
+
+/// Determines how search results with equal similarity are ordered.
+///
+/// Results are always sorted by highest similarity to lowest. Ties are then
+/// broken by the chosen key, and any remaining ties by insertion order, so
+/// that the order of results is the same on every run, whether or not the
+/// search is parallelized.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TieBreak {
+    /// Results with equal similarity are ordered by their text.
+    Text,
+    /// Results with equal similarity are ordered by when their text was
+    /// first added to the `Corpus`, earliest first.
+    InsertionOrder,
+    /// Results with equal similarity are ordered by how many times their
+    /// text was added to the `Corpus`, most frequent first.
+    Frequency,
+}
+
+impl Default for TieBreak {
+    /// Default is `Text`.
+    fn default() -> Self {
+        TieBreak::Text
+    }
+}
+
+/// Order two similarities, with NaN below every other value, so that a
+/// result with a NaN similarity is never ranked as the best match.
+pub(crate) fn compare_similarity(a: f32, b: f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+impl TieBreak {
+    /// Order two results from highest similarity to lowest, breaking ties
+    /// according to this `TieBreak`.
+    pub(crate) fn compare(&self, a: &Ranked, b: &Ranked) -> Ordering {
+        let (a, a_stats) = a;
+        let (b, b_stats) = b;
+        let by_similarity = compare_similarity(b.similarity, a.similarity);
+        let by_order = || a_stats.order.cmp(&b_stats.order);
+        by_similarity.then_with(|| match *self {
+            TieBreak::Text => a.text.cmp(&b.text).then_with(by_order),
+            TieBreak::InsertionOrder => by_order(),
+            TieBreak::Frequency => b_stats
+                .frequency
+                .cmp(&a_stats.frequency)
+                .then_with(by_order),
+        })
+    }
+}
+
+/// When and how often an entry was added to a `Corpus`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct EntryStats {
+    /// The number of distinct entries added before this one
+    pub(crate) order: usize,
+    /// The number of times the entry was added
+    pub(crate) frequency: usize,
+}
+
+/// A search result, along with the stats of its corpus entry used to break
+/// ties.
+pub(crate) type Ranked = (SearchResult, EntryStats);
+
+/// A predicate on the text of corpus entries, deciding whether they may
+/// appear in search results.
+type Filter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Settings for a fuzzy search of a `Corpus`, one setting at a time.
+/// ```rust
+/// # use ngrammatic::{CorpusBuilder, SearchOptions};
+/// # fn main() -> Result<(), ngrammatic::Error> {
+/// let corpus = CorpusBuilder::default()
+///     .fill(vec!["tomato", "tomatoes", "potato"])
+///     .finish();
+/// let options = SearchOptions::new()
+///     .threshold(0.40)
+///     .limit(10)
+///     .filter(|text| text != "tomatoes");
+/// let results = corpus.search_with("tomacco", &options)?;
+/// assert_eq!(results[0].text, "tomato");
+/// assert!(results.iter().all(|result| result.text != "tomatoes"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SearchOptions {
+    pub(crate) threshold: f32,
+    pub(crate) limit: usize,
+    pub(crate) offset: usize,
+    pub(crate) warp: f32,
+    pub(crate) scoring: Scoring,
+    pub(crate) rerank: Option<Rerank>,
+    pub(crate) parallel: bool,
+    pub(crate) filter: Option<Filter>,
+    pub(crate) tie_break: TieBreak,
+}
+
+impl std::fmt::Debug for SearchOptions {
+    /// Debug format for `SearchOptions`. Only shows whether a `filter` is
+    /// set, as there's no meaningful representation we could give of it.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SearchOptions")
+            .field("threshold", &self.threshold)
+            .field("limit", &self.limit)
+            .field("offset", &self.offset)
+            .field("warp", &self.warp)
+            .field("scoring", &self.scoring)
+            .field("rerank", &self.rerank)
+            .field("parallel", &self.parallel)
+            .field("filter", &self.filter.is_some())
+            .field("tie_break", &self.tie_break)
+            .finish()
+    }
+}
+
+impl Default for SearchOptions {
+    /// Default options return up to 10 results of any similarity, with a
+    /// `warp` of 2.0, searching serially and without re-ranking or filtering.
+    fn default() -> Self {
+        SearchOptions {
+            threshold: 0.0,
+            limit: 10,
+            offset: 0,
+            warp: 2.0,
+            scoring: Scoring::Ngram,
+            rerank: None,
+            parallel: false,
+            filter: None,
+            tie_break: TieBreak::default(),
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Initialize a new instance of `SearchOptions` with the default
+    /// settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the minimum similarity of results, in the range of 0.0 to 1.0.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the maximum number of results.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set the number of best results to skip, for paginating through
+    /// results `limit` at a time.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Set the `warp` factor of the n-gram similarity, in the range of 1.0 to
+    /// 3.0.
+    pub fn warp(mut self, warp: f32) -> Self {
+        self.warp = warp;
+        self
+    }
+
+    /// Set how the similarity of candidates is computed from the grams they
+    /// share with the query.
+    pub fn scoring(mut self, scoring: Scoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    /// Re-rank the best n-gram candidates with an edit metric.
+    pub fn rerank(mut self, rerank: Rerank) -> Self {
+        self.rerank = Some(rerank);
+        self
+    }
+
+    /// Set whether the search is parallelized.
+    #[cfg(feature = "rayon")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Only consider corpus entries whose text satisfies the `filter`
+    /// predicate.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Set how results with equal similarity are ordered.
+    pub fn tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Check that the `threshold` and `warp` are within their valid ranges.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.threshold) {
+            return Err(Error::InvalidThreshold(self.threshold));
+        }
+        if !(1.0..=3.0).contains(&self.warp) {
+            return Err(Error::InvalidWarp(self.warp));
+        }
+        Ok(())
+    }
+
+    /// If an entry with the given `text` may appear in the results.
+    pub(crate) fn admits(&self, text: &str) -> bool {
+        self.filter.as_ref().map_or(true, |filter| filter(text))
+    }
+}
+
+/// Turn the candidate `results` for the transformed `query` into the final
+/// search results, as configured by the `options`: re-rank them, sort them
+/// from highest similarity to lowest, and select the requested page.
+pub(crate) fn rank(
+    query: &str,
+    mut results: Vec<Ranked>,
+    options: &SearchOptions,
+) -> Vec<SearchResult> {
+    if let Some(rerank) = options.rerank {
+        results.sort_by(|a, b| options.tie_break.compare(a, b));
+        results.truncate(rerank.candidates);
+        for (result, _) in results.iter_mut() {
+            let edit_similarity = rerank.metric.similarity(query, &result.text);
+            result.similarity = rerank.blend(result.ngram_similarity, edit_similarity);
+            result.edit_similarity = Some(edit_similarity);
+        }
+        results.retain(|(result, _)| result.similarity >= options.threshold);
+    }
+
+    // Sort highest similarity to lowest
+    #[cfg(feature = "rayon")]
+    if options.parallel {
+        results.par_sort_by(|a, b| options.tie_break.compare(a, b));
+    } else {
+        results.sort_by(|a, b| options.tie_break.compare(a, b));
+    }
+    #[cfg(not(feature = "rayon"))]
+    results.sort_by(|a, b| options.tie_break.compare(a, b));
+
+    results
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit)
+        .map(|(result, _)| result)
+        .collect()
+}
+
+/// Gather the candidate results of each of the `shards` of a corpus with
+/// `candidates`, visiting the shards in parallel for parallel searches.
+pub(crate) fn fan_out<S, F>(shards: &[S], options: &SearchOptions, candidates: F) -> Vec<Ranked>
+where
+    S: Sync,
+    F: Fn(&S) -> Vec<Ranked> + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    if options.parallel {
+        return shards.par_iter().flat_map_iter(candidates).collect();
+    }
+    #[cfg(not(feature = "rayon"))]
+    let _ = options;
+    shards.iter().flat_map(candidates).collect()
+}
@@ -0,0 +1,518 @@
+#![deny(missing_docs)]
+
+use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::{ConcurrentCorpus, Error, KeyTransformer, Scoring, SearchOptions};
+
+/// The largest request body accepted, in bytes.
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+/// Serves fuzzy searches of a `ConcurrentCorpus` over HTTP, with JSON
+/// request and response bodies, from a pool of worker threads.
+///
+/// The endpoints are:
+/// - `POST /search`, with a `query`, and optionally a `threshold`, `limit`,
+///   `offset`, `warp` and `scoring` as for `SearchOptions`, responds with the
+///   `results`.
+/// - `POST /batch_search`, with several `queries` and the same options,
+///   responds with the `results` of each query.
+/// - `POST /add`, with either a `text` and an optional `payload`, or several
+///   `texts`, responds with the number `added`.
+/// - `POST /remove`, with either a `text` or several `texts`, responds with
+///   the number `removed`.
+/// - `GET /stats` responds with the number of `entries` and `shards`.
+///
+/// Failed requests are answered with an `error` message, and a 4xx status.
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use ngrammatic::{CorpusBuilder, SearchServer};
+/// # fn main() -> Result<(), ngrammatic::Error> {
+/// let corpus = CorpusBuilder::default()
+///     .fill(vec!["tomato", "potato"])
+///     .finish_concurrent(8);
+/// let server = SearchServer::bind("127.0.0.1:8080", Arc::new(corpus))?.threads(4);
+/// println!("listening on {:?}", server.local_addr());
+/// server.run();
+/// # Ok(())
+/// # }
+/// ```
+pub struct SearchServer<KT>
+where
+    KT: KeyTransformer,
+{
+    corpus: Arc<ConcurrentCorpus<KT>>,
+    http: Arc<tiny_http::Server>,
+    stopped: Arc<AtomicBool>,
+    threads: usize,
+}
+
+impl<KT> std::fmt::Debug for SearchServer<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Debug format for a `SearchServer`. Only shows where it listens.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SearchServer")
+            .field("addr", &self.http.server_addr().to_ip())
+            .field("threads", &self.threads)
+            .finish()
+    }
+}
+
+/// Stops a running `SearchServer`, from any thread.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    http: Arc<tiny_http::Server>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for ShutdownHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShutdownHandle")
+            .field("stopped", &self.stopped.load(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+impl ShutdownHandle {
+    /// Stop the server once the requests being handled are answered, making
+    /// `SearchServer::run` return.
+    pub fn shutdown(&self) {
+        if !self.stopped.swap(true, Ordering::SeqCst) {
+            // Wakes one worker, which wakes the next as it stops
+            self.http.unblock();
+        }
+    }
+}
+
+impl<KT> SearchServer<KT>
+where
+    KT: KeyTransformer + Send + Sync + 'static,
+{
+    /// Listen on `addr` for requests to search the `corpus`, with a single
+    /// worker thread. Use port 0 to listen on any free port. Fails if the
+    /// address can't be bound.
+    pub fn bind<A: ToSocketAddrs>(
+        addr: A,
+        corpus: Arc<ConcurrentCorpus<KT>>,
+    ) -> Result<Self, Error> {
+        let http = tiny_http::Server::http(addr).map_err(|error| {
+            match error.downcast::<std::io::Error>() {
+                Ok(error) => Error::from(*error),
+                Err(error) => Error::Io {
+                    kind: std::io::ErrorKind::Other,
+                    message: error.to_string(),
+                },
+            }
+        })?;
+        Ok(SearchServer {
+            corpus,
+            http: Arc::new(http),
+            stopped: Arc::new(AtomicBool::new(false)),
+            threads: 1,
+        })
+    }
+
+    /// Set the number of worker threads handling requests, at least one.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// The corpus the server searches and updates.
+    pub fn corpus(&self) -> &Arc<ConcurrentCorpus<KT>> {
+        &self.corpus
+    }
+
+    /// A handle for stopping the server while it runs.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            http: Arc::clone(&self.http),
+            stopped: Arc::clone(&self.stopped),
+        }
+    }
+
+    /// Handle requests until stopped with a `ShutdownHandle`, on the
+    /// current thread and `threads - 1` more.
+    pub fn run(self) {
+        let workers: Vec<_> = (1..self.threads)
+            .map(|_| {
+                let (http, corpus, stopped) = (
+                    Arc::clone(&self.http),
+                    Arc::clone(&self.corpus),
+                    Arc::clone(&self.stopped),
+                );
+                thread::spawn(move || serve(&http, &corpus, &stopped))
+            })
+            .collect();
+        serve(&self.http, &self.corpus, &self.stopped);
+        for worker in workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Answer requests received by `http` until `stopped`.
+fn serve<KT>(http: &tiny_http::Server, corpus: &ConcurrentCorpus<KT>, stopped: &AtomicBool)
+where
+    KT: KeyTransformer + Send + Sync,
+{
+    loop {
+        match http.recv() {
+            Ok(request) => handle(corpus, request),
+            Err(_) if stopped.load(Ordering::SeqCst) => {
+                // Each unblock only wakes a single worker, so pass it on
+                http.unblock();
+                return;
+            }
+            // Failing to accept one connection doesn't affect the others
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Search settings common to `/search` and `/batch_search` requests.
+#[derive(Debug, Default, Deserialize)]
+struct OptionsRequest {
+    threshold: Option<f32>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    warp: Option<f32>,
+    scoring: Option<Scoring>,
+}
+
+impl OptionsRequest {
+    fn options(&self) -> SearchOptions {
+        let mut options = SearchOptions::new();
+        if let Some(threshold) = self.threshold {
+            options = options.threshold(threshold);
+        }
+        if let Some(limit) = self.limit {
+            options = options.limit(limit);
+        }
+        if let Some(offset) = self.offset {
+            options = options.offset(offset);
+        }
+        if let Some(warp) = self.warp {
+            options = options.warp(warp);
+        }
+        if let Some(scoring) = self.scoring {
+            options = options.scoring(scoring);
+        }
+        options
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchRequest {
+    query: String,
+    #[serde(flatten)]
+    options: OptionsRequest,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchSearchRequest {
+    queries: Vec<String>,
+    #[serde(flatten)]
+    options: OptionsRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AddRequest {
+    One {
+        text: String,
+        payload: Option<String>,
+    },
+    Many {
+        texts: Vec<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RemoveRequest {
+    One { text: String },
+    Many { texts: Vec<String> },
+}
+
+/// A failed request, answered with the status `code` and an error message.
+struct Failure {
+    code: u16,
+    message: String,
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        Failure {
+            code: 400,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Answer a single `request`.
+fn handle<KT>(corpus: &ConcurrentCorpus<KT>, mut request: Request)
+where
+    KT: KeyTransformer + Send + Sync,
+{
+    let (code, body) = match route(corpus, &mut request) {
+        Ok(body) => (200, body),
+        Err(failure) => (failure.code, json!({ "error": failure.message })),
+    };
+    let content_type =
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("valid header");
+    let response = Response::from_string(body.to_string())
+        .with_status_code(code)
+        .with_header(content_type);
+    // The client may have gone away, which only affects its own request
+    let _ = request.respond(response);
+}
+
+/// Dispatch a `request` by its method and path.
+fn route<KT>(corpus: &ConcurrentCorpus<KT>, request: &mut Request) -> Result<Value, Failure>
+where
+    KT: KeyTransformer + Send + Sync,
+{
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    let method = request.method().clone();
+    match (method, path.as_str()) {
+        (Method::Post, "/search") => {
+            let search: SearchRequest = body(request)?;
+            let results = corpus.search_with(&search.query, &search.options.options())?;
+            Ok(json!({ "results": results }))
+        }
+        (Method::Post, "/batch_search") => {
+            let batch: BatchSearchRequest = body(request)?;
+            let options = batch.options.options();
+            let results = batch
+                .queries
+                .iter()
+                .map(|query| corpus.search_with(query, &options))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(json!({ "results": results }))
+        }
+        (Method::Post, "/add") => {
+            let added = match body(request)? {
+                AddRequest::One {
+                    text,
+                    payload: Some(payload),
+                } => {
+                    corpus.add_text_with_payload(&text, &payload);
+                    1
+                }
+                AddRequest::One {
+                    text,
+                    payload: None,
+                } => {
+                    corpus.add_text(&text);
+                    1
+                }
+                AddRequest::Many { texts } => {
+                    texts.iter().for_each(|text| corpus.add_text(text));
+                    texts.len()
+                }
+            };
+            Ok(json!({ "added": added }))
+        }
+        (Method::Post, "/remove") => {
+            let removed = match body(request)? {
+                RemoveRequest::One { text } => usize::from(corpus.remove_text(&text)),
+                RemoveRequest::Many { texts } => {
+                    texts.iter().filter(|text| corpus.remove_text(text)).count()
+                }
+            };
+            Ok(json!({ "removed": removed }))
+        }
+        (Method::Get, "/stats") => Ok(json!({
+            "entries": corpus.len(),
+            "shards": corpus.shard_count(),
+        })),
+        (_, "/search" | "/batch_search" | "/add" | "/remove" | "/stats") => Err(Failure {
+            code: 405,
+            message: "method not allowed".to_string(),
+        }),
+        _ => Err(Failure {
+            code: 404,
+            message: format!("no such endpoint {:?}", path),
+        }),
+    }
+}
+
+/// Parse the JSON body of a `request`.
+fn body<T>(request: &mut Request) -> Result<T, Failure>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_end(&mut body)
+        .map_err(Error::from)?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(Failure {
+            code: 413,
+            message: "request body too large".to_string(),
+        });
+    }
+    serde_json::from_slice(&body).map_err(|error| Failure {
+        code: 400,
+        message: format!("invalid request body: {}", error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CorpusBuilder;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    /// Start a server on an ephemeral port, returning its address and a
+    /// handle to stop it, and the thread running it.
+    fn start(words: &[&str]) -> (SocketAddr, ShutdownHandle, thread::JoinHandle<()>) {
+        let corpus = CorpusBuilder::default()
+            .fill(words.iter().copied())
+            .finish_concurrent(4);
+        let server = SearchServer::bind("127.0.0.1:0", Arc::new(corpus))
+            .unwrap()
+            .threads(3);
+        let addr = server.local_addr().unwrap();
+        let handle = server.shutdown_handle();
+        (addr, handle, thread::spawn(move || server.run()))
+    }
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let code = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (code, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn server_endpoints() {
+        let (addr, handle, running) = start(&["tomato", "potato", "carrot"]);
+
+        let (code, body) = request(
+            addr,
+            "POST",
+            "/search",
+            r#"{"query": "tomacco", "limit": 1}"#,
+        );
+        assert_eq!(code, 200);
+        assert_eq!(body["results"].as_array().unwrap().len(), 1);
+        assert_eq!(body["results"][0]["text"], "tomato");
+
+        let (code, body) = request(
+            addr,
+            "POST",
+            "/batch_search",
+            r#"{"queries": ["tomacco", "carots"], "threshold": 0.5}"#,
+        );
+        assert_eq!(code, 200);
+        assert_eq!(body["results"][1][0]["text"], "carrot");
+
+        let (_, body) = request(
+            addr,
+            "POST",
+            "/add",
+            r#"{"text": "tornado", "payload": "x"}"#,
+        );
+        assert_eq!(body["added"], 1);
+        let (_, body) = request(addr, "POST", "/add", r#"{"texts": ["pea", "bean"]}"#);
+        assert_eq!(body["added"], 2);
+        let (_, body) = request(addr, "POST", "/remove", r#"{"texts": ["pea", "kale"]}"#);
+        assert_eq!(body["removed"], 1);
+        let (code, body) = request(addr, "GET", "/stats", "");
+        assert_eq!(code, 200);
+        assert_eq!(body, json!({ "entries": 5, "shards": 4 }));
+
+        let (code, body) = request(addr, "POST", "/search", r#"{"query": "x", "threshold": 2}"#);
+        assert_eq!(code, 400);
+        assert!(body["error"].as_str().unwrap().contains("threshold"));
+        assert_eq!(request(addr, "POST", "/search", "{").0, 400);
+        assert_eq!(request(addr, "GET", "/search", "").0, 405);
+        assert_eq!(request(addr, "GET", "/nowhere", "").0, 404);
+
+        handle.shutdown();
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn server_shutdown_before_threads() {
+        let corpus = CorpusBuilder::default().finish_concurrent(2);
+        let server = SearchServer::bind("127.0.0.1:0", Arc::new(corpus)).unwrap();
+        // The handle is taken before the number of threads is set
+        let handle = server.shutdown_handle();
+        let running = thread::spawn(move || server.threads(4).run());
+        thread::sleep(std::time::Duration::from_millis(50));
+        handle.shutdown();
+        running.join().unwrap();
+    }
+
+    #[test]
+    fn server_bind_errors() {
+        let corpus = Arc::new(CorpusBuilder::default().finish_concurrent(2));
+        let server = SearchServer::bind("127.0.0.1:0", corpus.clone()).unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(matches!(
+            SearchServer::bind(addr, corpus),
+            Err(Error::Io {
+                kind: std::io::ErrorKind::AddrInUse,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn server_concurrent_clients() {
+        let (addr, handle, running) = start(&["tomato"]);
+        let clients: Vec<_> = (0..8)
+            .map(|client| {
+                thread::spawn(move || {
+                    for i in 0..20 {
+                        let text = format!("word{}x{}", client, i);
+                        let add = json!({ "text": text }).to_string();
+                        assert_eq!(request(addr, "POST", "/add", &add).0, 200);
+                        let search = json!({ "query": text, "limit": 1 }).to_string();
+                        let (code, body) = request(addr, "POST", "/search", &search);
+                        assert_eq!(code, 200);
+                        assert_eq!(body["results"][0]["text"], text);
+                    }
+                })
+            })
+            .collect();
+        for client in clients {
+            client.join().unwrap();
+        }
+        assert_eq!(request(addr, "GET", "/stats", "").1["entries"], 161);
+        handle.shutdown();
+        running.join().unwrap();
+    }
+}
@@ -0,0 +1,483 @@
+#![deny(missing_docs)]
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::{shard_of, Corpus, CorpusBuilder};
+use crate::scoring::ShardedIdf;
+use crate::search::{fan_out, rank};
+use crate::{Error, KeyTransformer, Ngram, Rerank, SearchOptions, SearchResult};
+
+// Import traits for rayon parallelization
+#[cfg(feature = "rayon")]
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+/// Holds a corpus partitioned by the hash of each entry's key across a
+/// number of independent `Corpus` shards, with the same API as `Corpus`.
+///
+/// Each shard only indexes its own share of the entries, which keeps the
+/// posting lists of common grams short. Texts can be added to all of the
+/// shards in parallel, and searches fan out to every shard, in parallel for
+/// parallel searches, before the best results of each are merged. Results,
+/// including the order of ties, are the same as for a `Corpus` holding all
+/// of the entries, unless stop grams are pruned. Each shard chooses its own
+/// stop grams, from the document frequencies of its own entries, and
+/// considers up to the `StopGrams` cap of its own entries having each, so
+/// searches pruning stop grams may find different candidates.
+/// ```rust
+/// # use ngrammatic::CorpusBuilder;
+/// # fn main() {
+/// let mut corpus = CorpusBuilder::default()
+///     .fill(vec!["tomato", "potato"])
+///     .finish_sharded(4);
+/// corpus.add_text("tornado");
+/// let results = corpus.search("tomacco", 0.40, 10);
+/// assert_eq!(results[0].text, "tomato");
+/// assert_eq!(corpus.len(), 3);
+/// # }
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShardedCorpus<KT>
+where
+    KT: KeyTransformer,
+{
+    /// An empty corpus with the shards' settings, used to prepare queries
+    pub(crate) template: Corpus<KT>,
+    pub(crate) shards: Vec<Corpus<KT>>,
+    pub(crate) next_order: usize,
+}
+
+impl<KT> std::fmt::Debug for ShardedCorpus<KT>
+where
+    KT: KeyTransformer,
+{
+    /// Debug format for a `ShardedCorpus`. Omits any representation of the
+    /// `key_trans` field, as there's no meaningful representation we could
+    /// give.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ShardedCorpus")
+            .field("shards", &self.shards)
+            .finish()
+    }
+}
+
+impl<KT> ShardedCorpus<KT>
+where
+    KT: KeyTransformer + Clone + Send + Sync,
+{
+    /// Create a `ShardedCorpus` of `shards` (at least one) empty shards,
+    /// with the settings of the empty `template`.
+    fn empty(template: Corpus<KT>, shards: usize) -> Self {
+        ShardedCorpus {
+            shards: (0..shards.max(1)).map(|_| template.empty_clone()).collect(),
+            template,
+            next_order: 0,
+        }
+    }
+
+    /// Partition the entries of an existing `corpus` across `shards` (at
+    /// least one) shards, keeping their insertion order, frequencies and
+    /// payloads.
+    /// ```rust
+    /// # use ngrammatic::{CorpusBuilder, ShardedCorpus};
+    /// # fn main() {
+    /// let corpus = CorpusBuilder::default()
+    ///     .fill(vec!["tomato", "potato", "tornado"])
+    ///     .finish();
+    /// let expected = corpus.search("tomacco", 0.0, 10);
+    /// let sharded = ShardedCorpus::from_corpus(corpus, 4);
+    /// assert_eq!(sharded.search("tomacco", 0.0, 10), expected);
+    /// # }
+    /// ```
+    pub fn from_corpus(corpus: Corpus<KT>, shards: usize) -> Self {
+        let template = corpus.empty_clone();
+        let (shards, next_order) = corpus.into_shards(shards);
+        ShardedCorpus {
+            template,
+            shards,
+            next_order,
+        }
+    }
+
+    /// The index of the shard an already transformed `key` belongs to.
+    fn shard_index(&self, key: &str) -> usize {
+        shard_of(key, self.shards.len())
+    }
+
+    /// The shard an already transformed `key` belongs to.
+    fn shard(&self, key: &str) -> &Corpus<KT> {
+        &self.shards[self.shard_index(key)]
+    }
+
+    /// The shard an already transformed `key` belongs to, for updating.
+    fn shard_mut(&mut self, key: &str) -> &mut Corpus<KT> {
+        let index = self.shard_index(key);
+        &mut self.shards[index]
+    }
+
+    /// Add the supplied `ngram` to the `ShardedCorpus`.
+    pub fn add_ngram(&mut self, ngram: Ngram) {
+        let order = self.next_order;
+        self.next_order += 1;
+        let key = ngram.text.clone();
+        self.shard_mut(&key).insert_ngram(ngram, Some(order));
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ShardedCorpus`.
+    pub fn add_text(&mut self, text: &str) {
+        let key = self.template.transform_key(text);
+        // Can't fail, as keys without grams are allowed
+        let _ = self.add_key(key, true);
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ShardedCorpus`, along with a `payload` that can be looked up with
+    /// `payload`. Adding the same text again replaces its payload.
+    pub fn add_text_with_payload(&mut self, text: &str, payload: &str) {
+        let key = self.template.transform_key(text);
+        let index = self.shard_index(&key);
+        // Can't fail, as keys without grams are allowed
+        let _ = self.add_key(key, true);
+        self.shards[index].set_payload(text, payload.to_string());
+    }
+
+    /// Generate an `Ngram` for the supplied `text`, and add it to the
+    /// `ShardedCorpus`, unless it's too short to produce any grams.
+    pub fn try_add_text(&mut self, text: &str) -> Result<(), Error> {
+        let key = self.template.transform_key(text);
+        self.add_key(key, false)
+    }
+
+    fn add_key(&mut self, key: String, allow_empty: bool) -> Result<(), Error> {
+        let index = self.shard_index(&key);
+        let next_order = &mut self.next_order;
+        self.shards[index].add_key(key, allow_empty, || {
+            *next_order += 1;
+            Some(*next_order - 1)
+        })
+    }
+
+    /// Generate `Ngram`s for all of the supplied `texts` in parallel, and
+    /// add them to the `ShardedCorpus`, updating all of the shards at once.
+    /// The texts are numbered in the order given, just as if they were added
+    /// one at a time with `add_text`.
+    /// ```rust
+    /// # use ngrammatic::CorpusBuilder;
+    /// # fn main() {
+    /// let mut corpus = CorpusBuilder::default().finish_sharded(4);
+    /// corpus.add_texts_par(vec!["tomato", "potato", "tornado"]);
+    /// assert_eq!(corpus.search_par("tomacco", 0.40, 10)[0].text, "tomato");
+    /// # }
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn add_texts_par<It>(&mut self, texts: It)
+    where
+        It: IntoParallelIterator,
+        String: From<It::Item>,
+    {
+        let entries: Vec<(String, Option<String>)> = texts
+            .into_par_iter()
+            .map(|text| (String::from(text), None))
+            .collect();
+        self.add_entries_par(entries);
+    }
+
+    /// Add the `entries`, each a text with an optional payload, to all of
+    /// the shards in parallel.
+    #[cfg(feature = "rayon")]
+    fn add_entries_par(&mut self, entries: Vec<(String, Option<String>)>) {
+        let template = &self.template;
+        let shards = self.shards.len();
+        let keyed: Vec<(usize, String, String, Option<String>)> = entries
+            .into_par_iter()
+            .map(|(text, payload)| {
+                let key = template.transform_key(&text);
+                (shard_of(&key, shards), key, text, payload)
+            })
+            .collect();
+        let mut groups: Vec<Vec<_>> = (0..shards).map(|_| Vec::new()).collect();
+        for (shard, key, text, payload) in keyed {
+            groups[shard].push((self.next_order, key, text, payload));
+            self.next_order += 1;
+        }
+        self.shards
+            .par_iter_mut()
+            .zip(groups)
+            .for_each(|(shard, group)| {
+                for (order, key, text, payload) in group {
+                    // Can't fail, as keys without grams are allowed
+                    let _ = shard.add_key(key, true, || Some(order));
+                    if let Some(payload) = payload {
+                        shard.set_payload(&text, payload);
+                    }
+                }
+            });
+    }
+
+    /// Look up the payload of the entry for `text`, after processing it with
+    /// the key transformer, if it was added with one.
+    pub fn payload(&self, text: &str) -> Option<&str> {
+        self.shard(&self.template.transform_key(text)).payload(text)
+    }
+
+    /// Remove the entry for the supplied `text`, after processing it with the
+    /// key transformer, along with its payload. Returns whether there was
+    /// such an entry.
+    pub fn remove_text(&mut self, text: &str) -> bool {
+        let key = self.template.transform_key(text);
+        self.shard_mut(&key).remove_key(&key)
+    }
+
+    /// The number of entries in the corpus.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(Corpus::len).sum()
+    }
+
+    /// If the corpus is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(Corpus::is_empty)
+    }
+
+    /// The number of shards the entries are partitioned across.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Choose the stop grams of each shard again, from the current document
+    /// frequencies of its grams, like `Corpus::update_stop_grams`.
+    pub fn update_stop_grams(&mut self) {
+        for shard in self.shards.iter_mut() {
+            shard.update_stop_grams();
+        }
+    }
+
+    /// Determines whether an exact match exists for the supplied `text` in
+    /// the `ShardedCorpus`, after processing it with the key transformer.
+    pub fn key(&self, text: &str) -> Option<String> {
+        self.shard(&self.template.transform_key(text)).key(text)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` above some
+    /// `threshold` of similarity to the supplied `text`. Returns up to
+    /// `limit` results, sorted by highest similarity to lowest.
+    pub fn search(&self, text: &str, threshold: f32, limit: usize) -> Vec<SearchResult> {
+        self.search_with_warp(text, 2.0, threshold, limit)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` above some
+    /// `threshold` of similarity to the supplied `text`, searching the
+    /// shards in parallel. Returns up to `limit` results, sorted by highest
+    /// similarity to lowest.
+    #[cfg(feature = "rayon")]
+    pub fn search_par(&self, text: &str, threshold: f32, limit: usize) -> Vec<SearchResult> {
+        self.search_with_warp_par(text, 2.0, threshold, limit)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` with a
+    /// custom `warp` for results above some `threshold` of similarity to the
+    /// supplied `text`. Returns up to `limit` results, sorted by highest
+    /// similarity to lowest.
+    pub fn search_with_warp(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<SearchResult> {
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit);
+        self.search_unchecked(text, &options)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` with a
+    /// custom `warp`, re-ranking the best n-gram candidates with an edit
+    /// metric, just like `Corpus::search_with_rerank`.
+    pub fn search_with_rerank(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+        rerank: &Rerank,
+    ) -> Vec<SearchResult> {
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit)
+            .rerank(*rerank);
+        self.search_unchecked(text, &options)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` with a
+    /// custom `warp` for results above some `threshold` of similarity to the
+    /// supplied `text`, searching the shards in parallel. Returns up to
+    /// `limit` results, sorted by highest similarity to lowest.
+    #[cfg(feature = "rayon")]
+    pub fn search_with_warp_par(
+        &self,
+        text: &str,
+        warp: f32,
+        threshold: f32,
+        limit: usize,
+    ) -> Vec<SearchResult> {
+        let options = SearchOptions::new()
+            .warp(warp)
+            .threshold(threshold)
+            .limit(limit)
+            .parallel(true);
+        self.search_unchecked(text, &options)
+    }
+
+    /// Perform a fuzzy search of the `ShardedCorpus` for `Ngrams` similar to
+    /// the supplied `text`, as configured by the `options`, just like
+    /// `Corpus::search_with`. A parallel search visits the shards in
+    /// parallel.
+    pub fn search_with(
+        &self,
+        text: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchResult>, Error> {
+        options.validate()?;
+        Ok(self.search_unchecked(text, options))
+    }
+
+    fn search_unchecked(&self, text: &str, options: &SearchOptions) -> Vec<SearchResult> {
+        let item = self
+            .template
+            .build_ngram(&self.template.transform_key(text));
+        let idf = ShardedIdf::new(self.shards.iter().collect(), &item, options.scoring);
+        let idf = |gram: &str| idf.weight(gram);
+        let results = fan_out(&self.shards, options, |shard| {
+            shard.ranked_candidates(&item, options, &idf)
+        });
+        rank(&item.text, results, options)
+    }
+}
+
+impl<KT> CorpusBuilder<KT>
+where
+    KT: KeyTransformer + Clone + Send + Sync,
+{
+    /// Yield a `ShardedCorpus` instance with all the properties set with
+    /// this builder, partitioned into the given number of `shards` (at least
+    /// one).
+    pub fn finish_sharded(self, shards: usize) -> ShardedCorpus<KT> {
+        let (template, entries) = self.into_entries();
+        let mut corpus = ShardedCorpus::empty(template, shards);
+        for (text, payload) in entries {
+            match payload {
+                Some(payload) => corpus.add_text_with_payload(&text, &payload),
+                None => corpus.add_text(&text),
+            }
+        }
+        corpus.update_stop_grams();
+        corpus
+    }
+
+    /// Yield a `ShardedCorpus` instance with all the properties set with
+    /// this builder, partitioned into the given number of `shards` (at least
+    /// one), and filled in parallel. The result is the same as with
+    /// `finish_sharded`.
+    #[cfg(feature = "rayon")]
+    pub fn finish_sharded_par(self, shards: usize) -> ShardedCorpus<KT> {
+        let (template, entries) = self.into_entries();
+        let mut corpus = ShardedCorpus::empty(template, shards);
+        corpus.add_entries_par(entries);
+        corpus.update_stop_grams();
+        corpus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{EditMetric, Pad, TieBreak};
+
+    fn words() -> Vec<String> {
+        let mut words: Vec<String> = (0..300).map(|i| format!("word{}", i % 250)).collect();
+        words.extend(["tomato", "Tomato", "potato", "tomatoes", "tornado"].map(String::from));
+        words
+    }
+
+    #[test]
+    fn sharded_corpus_matches_corpus() {
+        let corpus = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(words())
+            .finish();
+        let sharded = CorpusBuilder::default()
+            .case_insensitive()
+            .fill(words())
+            .finish_sharded(5);
+        assert_eq!(sharded.len(), corpus.len());
+        assert_eq!(sharded.shard_count(), 5);
+        assert!(sharded.shards.iter().all(|shard| !shard.is_empty()));
+        let rerank = Rerank::new(EditMetric::Levenshtein).candidates(20);
+        for query in ["tomacco", "word12", "wrd99"] {
+            for tie_break in [
+                TieBreak::Text,
+                TieBreak::InsertionOrder,
+                TieBreak::Frequency,
+            ] {
+                let options = SearchOptions::new().tie_break(tie_break).offset(2).limit(5);
+                assert_eq!(
+                    sharded.search_with(query, &options),
+                    corpus.search_with(query, &options)
+                );
+                let options = options.rerank(rerank);
+                assert_eq!(
+                    sharded.search_with(query, &options),
+                    corpus.search_with(query, &options)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sharded_corpus_add_remove() {
+        let mut corpus = CorpusBuilder::default()
+            .arity(3)
+            .pad_full(Pad::None)
+            .finish_sharded(3);
+        assert!(corpus.is_empty());
+        corpus.add_text_with_payload("tomato", "red");
+        corpus.add_text("potato");
+        assert_eq!(
+            corpus.try_add_text("to"),
+            Err(Error::EmptyGrams {
+                text: "to".to_string()
+            })
+        );
+        assert_eq!(corpus.payload("tomato"), Some("red"));
+        assert_eq!(corpus.key("potato"), Some("potato".to_string()));
+        assert!(corpus.remove_text("tomato"));
+        assert!(!corpus.remove_text("tomato"));
+        assert_eq!(corpus.payload("tomato"), None);
+        assert!(corpus.search("tomato", 0.9, 10).is_empty());
+        assert_eq!(corpus.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn sharded_corpus_parallel() {
+        let serial = CorpusBuilder::default().fill(words()).finish_sharded(4);
+        let parallel = CorpusBuilder::default().fill(words()).finish_sharded_par(4);
+        let mut added = CorpusBuilder::default().finish_sharded(4);
+        added.add_texts_par(words());
+        for query in ["tomacco", "word12", "wrd99"] {
+            for tie_break in [TieBreak::InsertionOrder, TieBreak::Frequency] {
+                let options = SearchOptions::new().limit(100).tie_break(tie_break);
+                let expected = serial.search_with(query, &options);
+                let options = options.parallel(true);
+                assert_eq!(parallel.search_with(query, &options), expected);
+                assert_eq!(added.search_with(query, &options), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+#![deny(missing_docs)]
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use crate::Ngram;
+
+/// How many of the most frequent grams `Corpus::stats` reports.
+pub(crate) const TOP_GRAMS: usize = 10;
+
+/// The size of a `Corpus` index and the shape of its grams, for tuning the
+/// arity and padding to a dataset. Returned by `Corpus::stats`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CorpusStats {
+    /// The number of entries
+    pub entries: usize,
+    /// The number of distinct grams of all entries
+    pub distinct_grams: usize,
+    /// The number of stop grams chosen by the `StopGrams` settings
+    pub stop_grams: usize,
+    /// The lengths of the posting lists, which hold the entries having each
+    /// distinct gram
+    pub postings: PostingStats,
+    /// The grams held by the most entries, with their document frequency,
+    /// most frequent first
+    pub top_grams: Vec<(String, usize)>,
+    /// The mean number of gram occurrences per entry
+    pub mean_grams_per_entry: f32,
+    /// Estimated heap memory used by the index
+    pub heap: HeapStats,
+}
+
+/// The distribution of posting list lengths, that is of the number of
+/// entries each distinct gram occurs in. All zero for an empty `Corpus`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PostingStats {
+    /// The total length of all posting lists
+    pub total: usize,
+    /// The length of the shortest posting list
+    pub min: usize,
+    /// The median posting list length
+    pub median: usize,
+    /// The 99th percentile of posting list lengths
+    pub p99: usize,
+    /// The length of the longest posting list
+    pub max: usize,
+}
+
+/// Estimated heap bytes used by each structure of a `Corpus` index. These
+/// are lower bounds, from the capacity of its collections and the size of
+/// their elements, without allocator overhead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HeapStats {
+    /// The interned entry texts and grams
+    pub strings: usize,
+    /// The `Ngram` of each entry
+    pub ngrams: usize,
+    /// The posting lists mapping grams to entries
+    pub postings: usize,
+    /// The insertion order and frequency of each entry
+    pub entries: usize,
+    /// The payloads of entries
+    pub payloads: usize,
+}
+
+impl HeapStats {
+    /// The estimated heap bytes used by all structures.
+    pub fn total(&self) -> usize {
+        self.strings + self.ngrams + self.postings + self.entries + self.payloads
+    }
+}
+
+impl PostingStats {
+    /// Summarize the posting list `lengths`, in any order.
+    pub(crate) fn from_lengths(mut lengths: Vec<usize>) -> PostingStats {
+        if lengths.is_empty() {
+            return PostingStats::default();
+        }
+        lengths.sort_unstable();
+        // Nearest-rank percentiles
+        let percentile = |p: f64| lengths[((p * lengths.len() as f64).ceil() as usize).max(1) - 1];
+        PostingStats {
+            total: lengths.iter().sum(),
+            min: lengths[0],
+            median: percentile(0.5),
+            p99: percentile(0.99),
+            max: lengths[lengths.len() - 1],
+        }
+    }
+}
+
+/// Keep the `n` most frequent of the `(gram, document frequency)` pairs,
+/// most frequent first, breaking ties by gram.
+pub(crate) fn top_grams<'a, It>(frequencies: It, n: usize) -> Vec<(String, usize)>
+where
+    It: Iterator<Item = (&'a str, usize)>,
+{
+    let mut top: Vec<(&str, usize)> = frequencies.collect();
+    let by_frequency = |a: &(&str, usize), b: &(&str, usize)| b.1.cmp(&a.1).then(a.0.cmp(b.0));
+    if top.len() > n {
+        top.select_nth_unstable_by(n, by_frequency);
+        top.truncate(n);
+    }
+    top.sort_unstable_by(by_frequency);
+    top.into_iter()
+        .map(|(gram, frequency)| (gram.to_string(), frequency))
+        .collect()
+}
+
+/// Estimated heap bytes of a `HashMap` with `capacity` entries of type `K`
+/// and `V`, counting a control byte per entry but not what they point to.
+pub(crate) fn hash_map_bytes<K, V>(capacity: usize) -> usize {
+    capacity * (size_of::<K>() + size_of::<V>() + 1)
+}
+
+/// Estimated heap bytes of a `SmolStr`, which stores short strings inline.
+pub(crate) fn smol_str_bytes(text: &SmolStr) -> usize {
+    if text.is_heap_allocated() {
+        text.len()
+    } else {
+        0
+    }
+}
+
+/// Estimated heap bytes of an `Ngram`, not counting the `Ngram` itself.
+pub(crate) fn ngram_bytes(ngram: &Ngram) -> usize {
+    let grams: usize = ngram.grams.keys().map(smol_str_bytes).sum();
+    let positions: usize = ngram
+        .positions
+        .iter()
+        .map(|(gram, offsets)| smol_str_bytes(gram) + offsets.capacity() * size_of::<usize>())
+        .sum();
+    smol_str_bytes(&ngram.text)
+        + smol_str_bytes(&ngram.text_padded)
+        + hash_map_bytes::<SmolStr, usize>(ngram.grams.capacity())
+        + grams
+        + hash_map_bytes::<SmolStr, Vec<usize>>(ngram.positions.capacity())
+        + positions
+        + ngram.arities.capacity() * size_of::<(usize, f32)>()
+}
+
+/// Estimated heap bytes of a map of `Strings`, with their contents.
+pub(crate) fn string_map_bytes<K>(map: &HashMap<K, String>) -> usize {
+    hash_map_bytes::<K, String>(map.capacity()) + map.values().map(String::capacity).sum::<usize>()
+}
+
+impl fmt::Display for CorpusStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "entries: {}", self.entries)?;
+        writeln!(f, "distinct grams: {}", self.distinct_grams)?;
+        writeln!(f, "stop grams: {}", self.stop_grams)?;
+        writeln!(
+            f,
+            "postings: {} (min {}, median {}, p99 {}, max {})",
+            self.postings.total,
+            self.postings.min,
+            self.postings.median,
+            self.postings.p99,
+            self.postings.max
+        )?;
+        writeln!(f, "grams per entry: {:.2}", self.mean_grams_per_entry)?;
+        write!(f, "top grams:")?;
+        for (gram, frequency) in &self.top_grams {
+            write!(f, " {:?} {}", gram, frequency)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "heap bytes: {} (strings {}, ngrams {}, postings {}, entries {}, payloads {})",
+            self.heap.total(),
+            self.heap.strings,
+            self.heap.ngrams,
+            self.heap.postings,
+            self.heap.entries,
+            self.heap.payloads
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CorpusBuilder;
+
+    #[test]
+    fn posting_stats_percentiles() {
+        assert_eq!(
+            PostingStats::from_lengths(Vec::new()),
+            PostingStats::default()
+        );
+        let stats = PostingStats::from_lengths((1..=200).rev().collect());
+        assert_eq!(
+            stats,
+            PostingStats {
+                total: 20100,
+                min: 1,
+                median: 100,
+                p99: 198,
+                max: 200,
+            }
+        );
+        assert_eq!(PostingStats::from_lengths(vec![3]).p99, 3);
+    }
+
+    #[test]
+    fn corpus_stats() {
+        let mut corpus = CorpusBuilder::default()
+            .arity(2)
+            .fill(vec!["tomato", "potato", "tomatoes", "tomato"])
+            .finish();
+        corpus.add_text_with_payload("carrot", "orange");
+        let stats = corpus.stats();
+        assert_eq!(stats.entries, 4);
+        assert_eq!(stats.distinct_grams, corpus.gram_frequencies().count());
+        assert_eq!(
+            stats.top_grams[..3],
+            [
+                ("at".to_string(), 3),
+                ("to".to_string(), 3),
+                (" t".to_string(), 2)
+            ]
+        );
+        assert_eq!(corpus.document_frequency("at"), 3);
+        assert_eq!(corpus.document_frequency("zz"), 0);
+        assert_eq!(stats.postings.max, 3);
+        assert_eq!(stats.postings.min, 1);
+        assert_eq!(
+            stats.postings.total,
+            corpus.gram_frequencies().map(|(_, df)| df).sum::<usize>()
+        );
+        // " tomato ", " potato ", " tomatoes ", " carrot "
+        assert_eq!(stats.mean_grams_per_entry, (7.0 + 7.0 + 9.0 + 7.0) / 4.0);
+        assert!(stats.heap.strings > 0 && stats.heap.payloads > 0);
+        assert_eq!(
+            stats.heap.total(),
+            stats.heap.strings
+                + stats.heap.ngrams
+                + stats.heap.postings
+                + stats.heap.entries
+                + stats.heap.payloads
+        );
+        let shown = stats.to_string();
+        assert!(shown.starts_with("entries: 4\n"));
+        assert!(shown.contains("top grams: \"at\" 3 \"to\" 3 \" t\" 2"));
+
+        let empty = CorpusBuilder::default().finish().stats();
+        assert_eq!(empty.entries, 0);
+        assert_eq!(empty.postings, PostingStats::default());
+        assert_eq!(empty.mean_grams_per_entry, 0.0);
+        assert!(empty.top_grams.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+//! Checks that comparing strings with a warmed up `Comparer` doesn't
+//! allocate. The allocations are counted by a global allocator, which is why
+//! these tests are in a binary of their own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use ngrammatic::{CompareConfig, Comparer, IdentityKeyTransformer, KeyTransformer};
+
+/// Counts the allocations made by each thread, so tests running in parallel
+/// don't disturb each other's counts.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn comparer_reuses_buffers() {
+    let words = ["Tomato", "potato", "TORNADO", "avocado", "tomatoes"];
+    let config = CompareConfig::new().arity(2).case_insensitive();
+    let mut comparer = config.comparer();
+    let compare_all = |comparer: &mut Comparer<_>| {
+        for pair in words.windows(2) {
+            comparer.compare(pair[0], pair[1]);
+        }
+    };
+    // Warm up the buffers, then compare again without allocating
+    compare_all(&mut comparer);
+    let before = allocations();
+    compare_all(&mut comparer);
+    assert_eq!(allocations(), before);
+}
+
+#[test]
+fn linked_key_transformer_reuses_buffers() {
+    let linked = IdentityKeyTransformer.lower().lower();
+    let mut key = String::new();
+    linked.transform_into("ToMaTo", &mut key);
+    key.clear();
+    let before = allocations();
+    linked.transform_into("PoTaTo", &mut key);
+    assert_eq!(allocations(), before);
+    assert_eq!(key, "potato");
+}